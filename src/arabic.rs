@@ -0,0 +1,167 @@
+use crate::bidi::{bidi_class, BidiClass};
+use crate::gsub::{GlyphInfo, SubstitutionPlan};
+
+// Features that pick a joining form, in the order they apply
+pub const FORM_FEATURES: [[u8; 4]; 4] = [*b"isol", *b"fina", *b"medi", *b"init"];
+
+// Unicode Joining_Type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoiningType {
+    NonJoining,
+    // Joins to the character before it in logical order only
+    RightJoining,
+    // Joins to the character after it only
+    LeftJoining,
+    DualJoining,
+    // Joins on both sides without changing shape itself, like tatweel and ZWJ
+    JoinCausing,
+    // Marks, which joining looks through
+    Transparent,
+}
+
+use JoiningType::*;
+
+// Which of a letter's forms it takes from the letters it joins
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoiningForm {
+    Isolated,
+    Final,
+    Medial,
+    Initial,
+}
+
+// Joining types of the Arabic, Syriac and N'Ko letters, sorted by code point. Other characters
+// are transparent if they're marks and non-joining otherwise.
+const JOINING_RANGES: &[(u32, u32, JoiningType)] = &[
+    (0x0620, 0x0620, DualJoining), (0x0622, 0x0625, RightJoining), (0x0626, 0x0626, DualJoining),
+    (0x0627, 0x0627, RightJoining), (0x0628, 0x0628, DualJoining), (0x0629, 0x0629, RightJoining),
+    (0x062A, 0x062E, DualJoining), (0x062F, 0x0632, RightJoining), (0x0633, 0x063F, DualJoining),
+    (0x0640, 0x0640, JoinCausing), (0x0641, 0x0647, DualJoining), (0x0648, 0x0648, RightJoining),
+    (0x0649, 0x064A, DualJoining), (0x066E, 0x066F, DualJoining), (0x0671, 0x0673, RightJoining),
+    (0x0675, 0x0677, RightJoining), (0x0678, 0x0687, DualJoining), (0x0688, 0x0699, RightJoining),
+    (0x069A, 0x06BF, DualJoining), (0x06C0, 0x06C0, RightJoining), (0x06C1, 0x06C2, DualJoining),
+    (0x06C3, 0x06CB, RightJoining), (0x06CC, 0x06CC, DualJoining), (0x06CD, 0x06CD, RightJoining),
+    (0x06CE, 0x06CE, DualJoining), (0x06CF, 0x06CF, RightJoining), (0x06D0, 0x06D1, DualJoining),
+    (0x06D2, 0x06D3, RightJoining), (0x06D5, 0x06D5, RightJoining), (0x06EE, 0x06EF, RightJoining),
+    (0x06FA, 0x06FC, DualJoining), (0x06FF, 0x06FF, DualJoining), (0x0710, 0x0710, RightJoining),
+    (0x0712, 0x0714, DualJoining), (0x0715, 0x0719, RightJoining), (0x071A, 0x071D, DualJoining),
+    (0x071E, 0x071E, RightJoining), (0x071F, 0x0727, DualJoining), (0x0728, 0x0728, RightJoining),
+    (0x0729, 0x0729, DualJoining), (0x072A, 0x072A, RightJoining), (0x072B, 0x072B, DualJoining),
+    (0x072C, 0x072C, RightJoining), (0x072D, 0x072E, DualJoining), (0x072F, 0x072F, RightJoining),
+    (0x074D, 0x074D, RightJoining), (0x074E, 0x0758, DualJoining), (0x0759, 0x075B, RightJoining),
+    (0x075C, 0x076A, DualJoining), (0x076B, 0x076C, RightJoining), (0x076D, 0x0770, DualJoining),
+    (0x0771, 0x0771, RightJoining), (0x0772, 0x0772, DualJoining), (0x0773, 0x0774, RightJoining),
+    (0x0775, 0x0777, DualJoining), (0x0778, 0x0779, RightJoining), (0x077A, 0x077F, DualJoining),
+    (0x07CA, 0x07EA, DualJoining), (0x07FA, 0x07FA, JoinCausing), (0x0860, 0x0860, DualJoining),
+    (0x0862, 0x0865, DualJoining), (0x0867, 0x0867, RightJoining), (0x0868, 0x0868, DualJoining),
+    (0x0869, 0x086A, RightJoining), (0x08A0, 0x08A9, DualJoining), (0x08AA, 0x08AC, RightJoining),
+    (0x08AE, 0x08AE, RightJoining), (0x08AF, 0x08B0, DualJoining), (0x08B1, 0x08B2, RightJoining),
+    (0x08B3, 0x08B8, DualJoining), (0x08B9, 0x08B9, RightJoining), (0x08BA, 0x08C8, DualJoining),
+    (0x200D, 0x200D, JoinCausing),
+];
+
+pub fn joining_type(ch: char) -> JoiningType {
+    let code_point = ch as u32;
+    let index = JOINING_RANGES.partition_point(|&(_, end, _)| end < code_point);
+    match JOINING_RANGES.get(index) {
+        Some(&(start, _, joining_type)) if start <= code_point => joining_type,
+        _ => match (ch, bidi_class(ch)) {
+            ('\u{200C}', _) => NonJoining,
+            // Marks and invisible format characters don't break a join
+            (_, BidiClass::NSM | BidiClass::BN) => Transparent,
+            _ => NonJoining,
+        },
+    }
+}
+
+// The form each character of a run in logical order takes. Characters without forms, like
+// marks, non-joining letters and tatweel, get None.
+pub fn joining_forms(text: &[char]) -> Vec<Option<JoiningForm>> {
+    let mut forms = vec![None; text.len()];
+    let mut previous: Option<(usize, JoiningType)> = None;
+    for (index, &ch) in text.iter().enumerate() {
+        let joining_type = joining_type(ch);
+        if joining_type == Transparent {
+            continue;
+        }
+        let joins_previous = matches!(joining_type, RightJoining | DualJoining | JoinCausing)
+            && previous.is_some_and(|(_, previous_type)| matches!(previous_type, LeftJoining | DualJoining | JoinCausing));
+        if let (true, Some((previous_index, _))) = (joins_previous, previous) {
+            forms[previous_index] = match forms[previous_index] {
+                Some(JoiningForm::Isolated) => Some(JoiningForm::Initial),
+                Some(JoiningForm::Final) => Some(JoiningForm::Medial),
+                form => form,
+            };
+        }
+        forms[index] = match (joining_type, joins_previous) {
+            (RightJoining | DualJoining, true) => Some(JoiningForm::Final),
+            (RightJoining | DualJoining | LeftJoining, false) => Some(JoiningForm::Isolated),
+            _ => None,
+        };
+        previous = Some((index, joining_type));
+    }
+    forms
+}
+
+// Substitutes a run of Arabic-like script: composition and localised forms first, then each
+// joining form for the letters that take it, then required ligatures, contextual alternates and
+// the remaining features. Clusters index `text`, the run's characters.
+pub fn substitute(plan: &mut SubstitutionPlan, glyphs: &mut Vec<GlyphInfo>, text: &[char]) {
+    let forms = joining_forms(text);
+    plan.apply_stage(glyphs, &[*b"ccmp", *b"locl"]);
+    for (tag, form) in FORM_FEATURES.iter().zip([JoiningForm::Isolated, JoiningForm::Final, JoiningForm::Medial, JoiningForm::Initial]) {
+        plan.apply_stage_to(glyphs, &[*tag], &|info| forms.get(info.cluster).copied().flatten() == Some(form));
+    }
+    plan.apply_stage(glyphs, &[*b"rlig"]);
+    plan.apply_stage(glyphs, &[*b"calt"]);
+    plan.apply_remaining(glyphs);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use JoiningForm::*;
+
+    const BEH: char = '\u{0628}';
+    const ALEF: char = '\u{0627}';
+    const DAL: char = '\u{062F}';
+    const FATHA: char = '\u{064E}';
+    const TATWEEL: char = '\u{0640}';
+    const ZWJ: char = '\u{200D}';
+    const ZWNJ: char = '\u{200C}';
+
+    #[test]
+    fn dual_joining_letters_take_each_form_by_position() {
+        assert_eq!(joining_forms(&[BEH]), [Some(Isolated)]);
+        assert_eq!(joining_forms(&[BEH, BEH]), [Some(Initial), Some(Final)]);
+        assert_eq!(joining_forms(&[BEH, BEH, BEH, BEH]), [Some(Initial), Some(Medial), Some(Medial), Some(Final)]);
+    }
+
+    #[test]
+    fn marks_between_letters_dont_break_the_join() {
+        assert_eq!(joining_forms(&[BEH, FATHA, BEH, FATHA]), [Some(Initial), None, Some(Final), None]);
+        assert_eq!(joining_forms(&[BEH, FATHA, FATHA, BEH, BEH]), [Some(Initial), None, None, Some(Medial), Some(Final)]);
+    }
+
+    #[test]
+    fn right_joining_letters_end_the_chain() {
+        // Alef and dal join the letter before them but never the one after
+        assert_eq!(joining_forms(&[BEH, ALEF, BEH]), [Some(Initial), Some(Final), Some(Isolated)]);
+        assert_eq!(joining_forms(&[BEH, DAL, BEH, BEH]), [Some(Initial), Some(Final), Some(Initial), Some(Final)]);
+        assert_eq!(joining_forms(&[ALEF, DAL]), [Some(Isolated), Some(Isolated)]);
+    }
+
+    #[test]
+    fn tatweel_and_zwj_cause_joins_without_taking_a_form() {
+        assert_eq!(joining_forms(&[BEH, TATWEEL, BEH]), [Some(Initial), None, Some(Final)]);
+        assert_eq!(joining_forms(&[BEH, ZWJ]), [Some(Initial), None]);
+        assert_eq!(joining_forms(&[ZWJ, ALEF]), [None, Some(Final)]);
+        assert_eq!(joining_forms(&[TATWEEL, BEH, TATWEEL]), [None, Some(Medial), None]);
+    }
+
+    #[test]
+    fn zwnj_keeps_letters_apart() {
+        assert_eq!(joining_forms(&[BEH, ZWNJ, BEH]), [Some(Isolated), None, Some(Isolated)]);
+        assert_eq!(joining_forms(&[BEH, BEH, ZWNJ, BEH, BEH]), [Some(Initial), Some(Final), None, Some(Initial), Some(Final)]);
+    }
+}
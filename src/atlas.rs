@@ -0,0 +1,455 @@
+use std::collections::HashMap;
+
+use crate::glyph::Glyph;
+use crate::raster::{rasterize_glyph, FillRule, GlyphBitmap};
+
+// Subpixel offsets are snapped to this many positions per pixel so nearby offsets share a slot
+pub const SUBPIXEL_STEPS: u8 = 4;
+
+// Empty pixels kept around every glyph so bilinear sampling never bleeds into a neighbour
+const GLYPH_PADDING: usize = 1;
+
+// Identifies one rasterization of a glyph. The pixel size is stored in 1/64 pixel units so the
+// key can be hashed, and the font index tells apart glyphs of a fallback chain that share ids.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AtlasKey {
+    pub font_index: usize,
+    pub glyph_index: u16,
+    pub pixel_size: u32,
+    pub subpixel_offset: (u8, u8),
+}
+
+impl AtlasKey {
+    // Offsets snap down to the step below them, so the snapped offset stays within the same
+    // pixel and never loses most of a pixel by wrapping round to zero
+    pub fn new(font_index: usize, glyph_index: u16, pixel_size: f32, subpixel_offset: (f32, f32)) -> Self {
+        let quantize = |offset: f32| ((offset.rem_euclid(1.0) * SUBPIXEL_STEPS as f32).floor() as u8).min(SUBPIXEL_STEPS - 1);
+        AtlasKey {
+            font_index,
+            glyph_index,
+            pixel_size: (pixel_size * 64.0).round() as u32,
+            subpixel_offset: (quantize(subpixel_offset.0), quantize(subpixel_offset.1)),
+        }
+    }
+
+    pub fn pixel_size(&self) -> f32 {
+        self.pixel_size as f32 / 64.0
+    }
+
+    // The offset actually rasterized, after snapping
+    pub fn subpixel_offset(&self) -> (f32, f32) {
+        (
+            self.subpixel_offset.0 as f32 / SUBPIXEL_STEPS as f32,
+            self.subpixel_offset.1 as f32 / SUBPIXEL_STEPS as f32,
+        )
+    }
+}
+
+// Texture coordinates in 0..1, with v pointing down like the texture rows
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct UvRect {
+    pub u0: f32,
+    pub v0: f32,
+    pub u1: f32,
+    pub v1: f32,
+}
+
+// Where a glyph lives in the atlas and how to place it: draw the `width` x `height` rectangle
+// with its top-left corner `left` pixels right of and `top` pixels above the pen position, then
+// move the pen by `advance` pixels
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct AtlasEntry {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    pub uv: UvRect,
+    pub left: i32,
+    pub top: i32,
+    pub advance: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SkylineNode {
+    x: usize,
+    y: usize,
+    width: usize,
+}
+
+struct Slot {
+    entry: AtlasEntry,
+    last_used: u64,
+}
+
+// Single-channel coverage texture filled with skyline packing. When a glyph no longer fits, the
+// least recently used half of the glyphs is evicted and the rest are repacked, as many times as
+// it takes. Repacking moves glyphs, so `generation` changes whenever it happens to tell callers
+// to refresh any entries they hold.
+pub struct GlyphAtlas {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+    skyline: Vec<SkylineNode>,
+    slots: HashMap<AtlasKey, Slot>,
+    clock: u64,
+    generation: u64,
+}
+
+impl GlyphAtlas {
+    pub fn new(width: usize, height: usize) -> Self {
+        GlyphAtlas {
+            width,
+            height,
+            pixels: vec![0; width * height],
+            skyline: vec![SkylineNode { x: 0, y: 0, width }],
+            slots: HashMap::new(),
+            clock: 0,
+            generation: 0,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Row-major coverage, top row first, ready to upload as a one-channel texture
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    pub fn get(&mut self, key: &AtlasKey) -> Option<AtlasEntry> {
+        self.clock += 1;
+        let clock = self.clock;
+        self.slots.get_mut(key).map(|slot| {
+            slot.last_used = clock;
+            slot.entry
+        })
+    }
+
+    // Looks the glyph up, rasterizing and packing it on a miss. Returns None only when the glyph
+    // is larger than the whole atlas. `font_index` is which font of a fallback chain it is from.
+    pub fn get_or_insert(&mut self, font_index: usize, glyph: &Glyph, units_per_em: u16, pixel_size: f32, subpixel_offset: (f32, f32)) -> Option<AtlasEntry> {
+        let key = AtlasKey::new(font_index, glyph.glyph_index, pixel_size, subpixel_offset);
+        if let Some(entry) = self.get(&key) {
+            return Some(entry);
+        }
+
+        let bitmap = rasterize_glyph(glyph, units_per_em, key.pixel_size(), key.subpixel_offset(), FillRule::NonZero);
+        let scale = key.pixel_size() / units_per_em as f32;
+        self.insert(key, &bitmap, glyph.advance_width as f32 * scale)
+    }
+
+    // Packs an already rasterized bitmap under `key`, replacing any previous entry. Evicts until
+    // the bitmap fits, so this returns None only when it is larger than the whole atlas.
+    pub fn insert(&mut self, key: AtlasKey, bitmap: &GlyphBitmap, advance: f32) -> Option<AtlasEntry> {
+        if !self.fits_empty(bitmap.width, bitmap.height) {
+            return None;
+        }
+        self.slots.remove(&key);
+        // Once nothing is left to evict the skyline starts over empty, where the bitmap fits
+        let position = loop {
+            if let Some(position) = self.allocate(bitmap.width, bitmap.height) {
+                break position;
+            }
+            self.evict();
+        };
+
+        self.blit(position, bitmap.width, bitmap.height, &bitmap.coverage, bitmap.width);
+        let entry = AtlasEntry {
+            x: position.0,
+            y: position.1,
+            width: bitmap.width,
+            height: bitmap.height,
+            uv: self.uv_rect(position, bitmap.width, bitmap.height),
+            left: bitmap.left,
+            top: bitmap.top,
+            advance,
+        };
+        self.clock += 1;
+        self.slots.insert(key, Slot { entry, last_used: self.clock });
+        Some(entry)
+    }
+
+    pub fn clear(&mut self) {
+        self.pixels.fill(0);
+        self.skyline = vec![SkylineNode { x: 0, y: 0, width: self.width }];
+        self.slots.clear();
+        self.generation += 1;
+    }
+
+    // Binary PGM (P5) of the atlas texture, handy for inspecting what has been packed
+    pub fn to_pgm(&self) -> Vec<u8> {
+        let mut data = format!("P5\n{} {}\n255\n", self.width, self.height).into_bytes();
+        data.extend_from_slice(&self.pixels);
+        data
+    }
+
+    fn uv_rect(&self, position: (usize, usize), width: usize, height: usize) -> UvRect {
+        UvRect {
+            u0: position.0 as f32 / self.width as f32,
+            v0: position.1 as f32 / self.height as f32,
+            u1: (position.0 + width) as f32 / self.width as f32,
+            v1: (position.1 + height) as f32 / self.height as f32,
+        }
+    }
+
+    fn blit(&mut self, position: (usize, usize), width: usize, height: usize, source: &[u8], source_stride: usize) {
+        for row in 0..height {
+            let target = (position.1 + row) * self.width + position.0;
+            let source_start = row * source_stride;
+            self.pixels[target..target + width].copy_from_slice(&source[source_start..source_start + width]);
+        }
+    }
+
+    // Finds the lowest (then leftmost) spot along the skyline for a padded rectangle and raises
+    // the skyline over it. Returns the top-left corner of the unpadded glyph.
+    fn allocate(&mut self, width: usize, height: usize) -> Option<(usize, usize)> {
+        if !self.fits_empty(width, height) {
+            return None;
+        }
+        let padded_width = width + 2 * GLYPH_PADDING;
+        let padded_height = height + 2 * GLYPH_PADDING;
+
+        let mut best: Option<(usize, usize, usize)> = None;
+        for (index, node) in self.skyline.iter().enumerate() {
+            if let Some(y) = self.fit(index, padded_width, padded_height) {
+                if best.is_none_or(|(_, best_y, best_x)| (y, node.x) < (best_y, best_x)) {
+                    best = Some((index, y, node.x));
+                }
+            }
+        }
+        let (index, y, x) = best?;
+
+        let new_node = SkylineNode { x, y: y + padded_height, width: padded_width };
+        self.skyline.insert(index, new_node);
+
+        // Shrink or drop the nodes now covered by the new one
+        let new_right = x + padded_width;
+        let next = index + 1;
+        while next < self.skyline.len() {
+            let node = self.skyline[next];
+            if node.x >= new_right {
+                break;
+            }
+            let overlap = new_right - node.x;
+            if overlap >= node.width {
+                self.skyline.remove(next);
+            } else {
+                self.skyline[next].x += overlap;
+                self.skyline[next].width -= overlap;
+                break;
+            }
+        }
+
+        // Merge neighbours at the same height
+        let mut i = 0;
+        while i + 1 < self.skyline.len() {
+            if self.skyline[i].y == self.skyline[i + 1].y {
+                self.skyline[i].width += self.skyline[i + 1].width;
+                self.skyline.remove(i + 1);
+            } else {
+                i += 1;
+            }
+        }
+
+        Some((x + GLYPH_PADDING, y + GLYPH_PADDING))
+    }
+
+    // Whether a glyph fits the atlas at all, padding included
+    fn fits_empty(&self, width: usize, height: usize) -> bool {
+        width + 2 * GLYPH_PADDING <= self.width && height + 2 * GLYPH_PADDING <= self.height
+    }
+
+    // Height at which a rectangle starting at skyline node `index` would rest, if it fits
+    fn fit(&self, index: usize, width: usize, height: usize) -> Option<usize> {
+        let x = self.skyline[index].x;
+        if x + width > self.width {
+            return None;
+        }
+        let mut y = 0;
+        let mut remaining = width as isize;
+        for node in &self.skyline[index..] {
+            if remaining <= 0 {
+                break;
+            }
+            y = y.max(node.y);
+            if y + height > self.height {
+                return None;
+            }
+            remaining -= node.width as isize;
+        }
+        Some(y)
+    }
+
+    // Drops the least recently used half of the glyphs and repacks the rest, tallest first
+    fn evict(&mut self) {
+        let mut slots: Vec<(AtlasKey, Slot)> = self.slots.drain().collect();
+        slots.sort_by_key(|(_, slot)| std::cmp::Reverse(slot.last_used));
+        slots.truncate(slots.len() / 2);
+        slots.sort_by_key(|(_, slot)| std::cmp::Reverse(slot.entry.height));
+
+        let old_pixels = std::mem::replace(&mut self.pixels, vec![0; self.width * self.height]);
+        self.skyline = vec![SkylineNode { x: 0, y: 0, width: self.width }];
+        self.generation += 1;
+
+        for (key, mut slot) in slots {
+            let entry = slot.entry;
+            let position = match self.allocate(entry.width, entry.height) {
+                Some(position) => position,
+                None => continue,
+            };
+            let source_start = entry.y * self.width + entry.x;
+            let source_stride = self.width;
+            self.blit(position, entry.width, entry.height, &old_pixels[source_start..], source_stride);
+            slot.entry.x = position.0;
+            slot.entry.y = position.1;
+            slot.entry.uv = self.uv_rect(position, entry.width, entry.height);
+            self.slots.insert(key, slot);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::glyph::process_points;
+
+    fn key(glyph_index: u16) -> AtlasKey {
+        AtlasKey::new(0, glyph_index, 16.0, (0.0, 0.0))
+    }
+
+    // Solid bitmap filled with `value`, so repacked glyphs can be recognised by their pixels
+    fn bitmap(width: usize, height: usize, value: u8) -> GlyphBitmap {
+        GlyphBitmap { width, height, left: 0, top: height as i32, coverage: vec![value; width * height] }
+    }
+
+    fn overlaps(a: &AtlasEntry, b: &AtlasEntry) -> bool {
+        // Compare padded rectangles, since the padding must stay empty too
+        let pad = GLYPH_PADDING;
+        a.x < b.x + b.width + 2 * pad && b.x < a.x + a.width + 2 * pad && a.y < b.y + b.height + 2 * pad && b.y < a.y + a.height + 2 * pad
+    }
+
+    #[test]
+    fn skyline_packs_without_overlap() {
+        let mut atlas = GlyphAtlas::new(64, 64);
+        let sizes = [(10, 20), (15, 8), (30, 12), (5, 5), (20, 20), (12, 30), (8, 8), (25, 6)];
+        let entries: Vec<AtlasEntry> = sizes.iter().enumerate()
+            .map(|(i, &(width, height))| atlas.insert(key(i as u16), &bitmap(width, height, 255), 0.0).unwrap())
+            .collect();
+
+        // Nothing was evicted, so every glyph kept its place
+        assert_eq!(atlas.generation(), 0);
+        assert_eq!(atlas.len(), sizes.len());
+        // The first glyph sits in the corner, the second on the skyline next to it
+        assert_eq!((entries[0].x, entries[0].y), (1, 1));
+        assert_eq!((entries[1].x, entries[1].y), (13, 1));
+        for (i, a) in entries.iter().enumerate() {
+            assert!(a.x + a.width < 64 && a.y + a.height < 64);
+            for b in &entries[i + 1..] {
+                assert!(!overlaps(a, b), "{:?} overlaps {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn eviction_drops_the_least_recently_used_half() {
+        // Four padded 8x8 glyphs fill the 16x16 atlas exactly
+        let mut atlas = GlyphAtlas::new(16, 16);
+        for glyph_index in 0..4 {
+            atlas.insert(key(glyph_index), &bitmap(6, 6, 10 + glyph_index as u8), 0.0).unwrap();
+        }
+        atlas.get(&key(2));
+        atlas.get(&key(0));
+
+        atlas.insert(key(4), &bitmap(6, 6, 14), 0.0).unwrap();
+        assert_eq!(atlas.len(), 3);
+        assert!(atlas.get(&key(1)).is_none());
+        assert!(atlas.get(&key(3)).is_none());
+        assert!(atlas.get(&key(0)).is_some());
+        assert!(atlas.get(&key(2)).is_some());
+        assert!(atlas.get(&key(4)).is_some());
+    }
+
+    #[test]
+    fn repacking_bumps_the_generation_and_moves_pixels_with_entries() {
+        let mut atlas = GlyphAtlas::new(16, 16);
+        for glyph_index in 0..4 {
+            atlas.insert(key(glyph_index), &bitmap(6, 6, 10 + glyph_index as u8), 0.0).unwrap();
+        }
+        atlas.get(&key(3));
+        atlas.get(&key(2));
+        assert_eq!(atlas.generation(), 0);
+
+        atlas.insert(key(4), &bitmap(6, 6, 14), 0.0).unwrap();
+        assert_eq!(atlas.generation(), 1);
+        for glyph_index in 2..5 {
+            let entry = atlas.get(&key(glyph_index)).unwrap();
+            assert_eq!(atlas.pixels()[entry.y * 16 + entry.x], 10 + glyph_index as u8);
+            assert_eq!(entry.uv, UvRect {
+                u0: entry.x as f32 / 16.0,
+                v0: entry.y as f32 / 16.0,
+                u1: (entry.x + 6) as f32 / 16.0,
+                v1: (entry.y + 6) as f32 / 16.0,
+            });
+        }
+
+        // Inserting into free space leaves the generation alone, clearing bumps it
+        let mut roomy = GlyphAtlas::new(64, 64);
+        roomy.insert(key(0), &bitmap(6, 6, 1), 0.0);
+        roomy.insert(key(1), &bitmap(6, 6, 1), 0.0);
+        assert_eq!(roomy.generation(), 0);
+        roomy.clear();
+        assert_eq!((roomy.generation(), roomy.len()), (1, 0));
+    }
+
+    #[test]
+    fn glyph_larger_than_the_atlas_is_not_inserted() {
+        // A 1000 unit square at 100 pixels per em, in an atlas only 32 pixels across
+        let x_coordinates = vec![0, 0, 1000, 1000];
+        let y_coordinates = vec![0, 1000, 1000, 0];
+        let flags = vec![1; 4];
+        let (processed_points, end_pts_of_contours) = process_points(&x_coordinates, &y_coordinates, &flags, &[3]);
+        let glyph = Glyph {
+            glyph_index: 1,
+            num_contours: 1,
+            xmin: 0,
+            ymin: 0,
+            xmax: 1000,
+            ymax: 1000,
+            end_pts_of_contours,
+            x_coordinates,
+            y_coordinates,
+            flags,
+            processed_points,
+            raw_end_pts_of_contours: vec![3],
+            instructions: vec![],
+            advance_width: 1000.0,
+            left_side_bearing: 0,
+        };
+        let mut atlas = GlyphAtlas::new(32, 32);
+        atlas.insert(key(0), &bitmap(6, 6, 1), 0.0).unwrap();
+
+        assert!(atlas.get_or_insert(0, &glyph, 1000, 100.0, (0.0, 0.0)).is_none());
+        // Nothing was evicted to make room for it
+        assert_eq!((atlas.len(), atlas.generation()), (1, 0));
+        // The padding counts too, so 31 pixels is already too wide
+        assert!(atlas.insert(key(2), &bitmap(31, 4, 1), 0.0).is_none());
+        assert!(atlas.get_or_insert(0, &glyph, 1000, 20.0, (0.0, 0.0)).is_some());
+    }
+}
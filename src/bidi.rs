@@ -0,0 +1,698 @@
+use std::ops::Range;
+
+use crate::shaping::Direction;
+
+// Bidirectional character types from UAX #9
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BidiClass {
+    L,
+    R,
+    AL,
+    EN,
+    ES,
+    ET,
+    AN,
+    CS,
+    NSM,
+    BN,
+    B,
+    S,
+    WS,
+    ON,
+    LRE,
+    LRO,
+    RLE,
+    RLO,
+    PDF,
+    LRI,
+    RLI,
+    FSI,
+    PDI,
+}
+
+use BidiClass::*;
+
+// Deepest explicit embedding the algorithm tracks
+const MAX_DEPTH: u8 = 125;
+
+// Classes of the characters that aren't L, sorted by code point. Unassigned code points in the
+// Hebrew and Arabic blocks take their block's default.
+const CLASS_RANGES: &[(u32, u32, BidiClass)] = &[
+    (0x0000, 0x0008, BN), (0x0009, 0x0009, S), (0x000A, 0x000A, B), (0x000B, 0x000B, S), (0x000C, 0x000C, WS),
+    (0x000D, 0x000D, B), (0x000E, 0x001B, BN), (0x001C, 0x001E, B), (0x001F, 0x001F, S), (0x0020, 0x0020, WS),
+    (0x0021, 0x0022, ON), (0x0023, 0x0025, ET), (0x0026, 0x002A, ON), (0x002B, 0x002B, ES), (0x002C, 0x002C, CS),
+    (0x002D, 0x002D, ES), (0x002E, 0x002F, CS), (0x0030, 0x0039, EN), (0x003A, 0x003A, CS), (0x003B, 0x0040, ON),
+    (0x005B, 0x0060, ON), (0x007B, 0x007E, ON), (0x007F, 0x0084, BN), (0x0085, 0x0085, B), (0x0086, 0x009F, BN),
+    (0x00A0, 0x00A0, CS), (0x00A1, 0x00A1, ON), (0x00A2, 0x00A5, ET), (0x00A6, 0x00A9, ON), (0x00AB, 0x00AC, ON),
+    (0x00AD, 0x00AD, BN), (0x00AE, 0x00AF, ON), (0x00B0, 0x00B1, ET), (0x00B2, 0x00B3, EN), (0x00B4, 0x00B4, ON),
+    (0x00B6, 0x00B8, ON), (0x00B9, 0x00B9, EN), (0x00BB, 0x00BF, ON), (0x00D7, 0x00D7, ON), (0x00F7, 0x00F7, ON),
+    (0x02B9, 0x02BA, ON), (0x02C2, 0x02CF, ON), (0x02D2, 0x02DF, ON), (0x02E5, 0x02ED, ON), (0x02EF, 0x02FF, ON),
+    (0x0300, 0x036F, NSM), (0x0374, 0x0375, ON), (0x037E, 0x037E, ON), (0x0384, 0x0385, ON), (0x0387, 0x0387, ON),
+    (0x03F6, 0x03F6, ON), (0x0483, 0x0489, NSM), (0x058A, 0x058A, ON), (0x058D, 0x058E, ON), (0x058F, 0x058F, ET),
+    (0x0590, 0x0590, R), (0x0591, 0x05BD, NSM), (0x05BE, 0x05BE, R), (0x05BF, 0x05BF, NSM), (0x05C0, 0x05C0, R),
+    (0x05C1, 0x05C2, NSM), (0x05C3, 0x05C3, R), (0x05C4, 0x05C5, NSM), (0x05C6, 0x05C6, R), (0x05C7, 0x05C7, NSM),
+    (0x05C8, 0x05FF, R), (0x0600, 0x0605, AN), (0x0606, 0x0607, ON), (0x0608, 0x0608, AL), (0x0609, 0x060A, ET),
+    (0x060B, 0x060B, AL), (0x060C, 0x060C, CS), (0x060D, 0x060D, AL), (0x060E, 0x060F, ON), (0x0610, 0x061A, NSM),
+    (0x061B, 0x064A, AL), (0x064B, 0x065F, NSM), (0x0660, 0x0669, AN), (0x066A, 0x066A, ET), (0x066B, 0x066C, AN),
+    (0x066D, 0x066F, AL), (0x0670, 0x0670, NSM), (0x0671, 0x06D5, AL), (0x06D6, 0x06DC, NSM), (0x06DD, 0x06DD, AN),
+    (0x06DE, 0x06DE, ON), (0x06DF, 0x06E4, NSM), (0x06E5, 0x06E6, AL), (0x06E7, 0x06E8, NSM), (0x06E9, 0x06E9, ON),
+    (0x06EA, 0x06ED, NSM), (0x06EE, 0x06EF, AL), (0x06F0, 0x06F9, EN), (0x06FA, 0x0710, AL), (0x0711, 0x0711, NSM),
+    (0x0712, 0x072F, AL), (0x0730, 0x074A, NSM), (0x074B, 0x07A5, AL), (0x07A6, 0x07B0, NSM), (0x07B1, 0x07BF, AL),
+    (0x07C0, 0x07EA, R), (0x07EB, 0x07F3, NSM), (0x07F4, 0x07F5, R), (0x07F6, 0x07F9, ON), (0x07FA, 0x07FC, R),
+    (0x07FD, 0x07FD, NSM), (0x07FE, 0x0815, R), (0x0816, 0x082D, NSM), (0x082E, 0x0858, R), (0x0859, 0x085B, NSM),
+    (0x085C, 0x085F, R), (0x0860, 0x0897, AL), (0x0898, 0x089F, NSM), (0x08A0, 0x08C9, AL), (0x08CA, 0x08E1, NSM),
+    (0x08E2, 0x08E2, AN), (0x08E3, 0x0902, NSM), (0x093A, 0x093A, NSM), (0x093C, 0x093C, NSM), (0x0941, 0x0948, NSM),
+    (0x094D, 0x094D, NSM), (0x0951, 0x0957, NSM), (0x0962, 0x0963, NSM), (0x0981, 0x0981, NSM), (0x09BC, 0x09BC, NSM),
+    (0x09C1, 0x09C4, NSM), (0x09CD, 0x09CD, NSM), (0x09E2, 0x09E3, NSM), (0x09F2, 0x09F3, ET), (0x09FE, 0x09FE, NSM),
+    (0x0E31, 0x0E31, NSM), (0x0E34, 0x0E3A, NSM), (0x0E3F, 0x0E3F, ET), (0x0E47, 0x0E4E, NSM), (0x0EB1, 0x0EB1, NSM),
+    (0x0EB4, 0x0EBC, NSM), (0x0EC8, 0x0ECE, NSM), (0x1680, 0x1680, WS), (0x180B, 0x180D, NSM), (0x180E, 0x180E, BN),
+    (0x1AB0, 0x1AFF, NSM), (0x1DC0, 0x1DFF, NSM), (0x2000, 0x200A, WS), (0x200B, 0x200D, BN), (0x200F, 0x200F, R),
+    (0x2010, 0x2027, ON), (0x2028, 0x2028, WS), (0x2029, 0x2029, B), (0x202A, 0x202A, LRE), (0x202B, 0x202B, RLE),
+    (0x202C, 0x202C, PDF), (0x202D, 0x202D, LRO), (0x202E, 0x202E, RLO), (0x202F, 0x202F, CS), (0x2030, 0x2034, ET),
+    (0x2035, 0x2043, ON), (0x2044, 0x2044, CS), (0x2045, 0x205E, ON), (0x205F, 0x205F, WS), (0x2060, 0x2064, BN),
+    (0x2066, 0x2066, LRI), (0x2067, 0x2067, RLI), (0x2068, 0x2068, FSI), (0x2069, 0x2069, PDI), (0x206A, 0x206F, BN),
+    (0x2070, 0x2070, EN), (0x2074, 0x2079, EN), (0x207A, 0x207B, ES), (0x207C, 0x207E, ON), (0x2080, 0x2089, EN),
+    (0x208A, 0x208B, ES), (0x208C, 0x208E, ON), (0x20A0, 0x20CF, ET), (0x20D0, 0x20F0, NSM), (0x2100, 0x2101, ON),
+    (0x2103, 0x2106, ON), (0x2108, 0x2109, ON), (0x2114, 0x2114, ON), (0x2116, 0x2118, ON), (0x211E, 0x2123, ON),
+    (0x2125, 0x2125, ON), (0x2127, 0x2127, ON), (0x2129, 0x2129, ON), (0x212E, 0x212E, ET), (0x213A, 0x213B, ON),
+    (0x2140, 0x2144, ON), (0x214A, 0x214D, ON), (0x2150, 0x215F, ON), (0x2189, 0x218B, ON), (0x2190, 0x2211, ON),
+    (0x2212, 0x2212, ES), (0x2213, 0x2213, ET), (0x2214, 0x2335, ON), (0x237B, 0x2394, ON), (0x2396, 0x2429, ON),
+    (0x2440, 0x244A, ON), (0x2460, 0x2487, ON), (0x2488, 0x249B, EN), (0x24EA, 0x26AB, ON), (0x26AD, 0x27FF, ON),
+    (0x2900, 0x2B73, ON), (0x2B76, 0x2BFF, ON), (0x2CEF, 0x2CF1, NSM), (0x2E00, 0x2E5D, ON), (0x2E80, 0x2FFB, ON),
+    (0x3000, 0x3000, WS), (0x3001, 0x3004, ON), (0x3008, 0x3020, ON), (0x302A, 0x302D, NSM), (0x3030, 0x3030, ON),
+    (0x3099, 0x309A, NSM), (0x309B, 0x309C, ON), (0x30A0, 0x30A0, ON), (0x30FB, 0x30FB, ON), (0xA66F, 0xA672, NSM),
+    (0xA674, 0xA67D, NSM), (0xA69E, 0xA69F, NSM), (0xFB1D, 0xFB1D, R), (0xFB1E, 0xFB1E, NSM), (0xFB1F, 0xFB28, R),
+    (0xFB29, 0xFB29, ES), (0xFB2A, 0xFB4F, R), (0xFB50, 0xFD3D, AL), (0xFD3E, 0xFD4F, ON), (0xFD50, 0xFDCE, AL),
+    (0xFDCF, 0xFDCF, ON), (0xFDF0, 0xFDFC, AL), (0xFDFD, 0xFDFF, ON), (0xFE00, 0xFE0F, NSM), (0xFE10, 0xFE19, ON),
+    (0xFE20, 0xFE2F, NSM), (0xFE30, 0xFE4F, ON), (0xFE50, 0xFE50, CS), (0xFE51, 0xFE51, ON), (0xFE52, 0xFE52, CS),
+    (0xFE54, 0xFE54, ON), (0xFE55, 0xFE55, CS), (0xFE56, 0xFE5E, ON), (0xFE5F, 0xFE5F, ET), (0xFE60, 0xFE61, ON),
+    (0xFE62, 0xFE63, ES), (0xFE64, 0xFE68, ON), (0xFE69, 0xFE6A, ET), (0xFE6B, 0xFE6B, ON), (0xFE70, 0xFEFE, AL),
+    (0xFEFF, 0xFEFF, BN), (0xFF01, 0xFF02, ON), (0xFF03, 0xFF05, ET), (0xFF06, 0xFF0A, ON), (0xFF0B, 0xFF0B, ES),
+    (0xFF0C, 0xFF0C, CS), (0xFF0D, 0xFF0D, ES), (0xFF0E, 0xFF0F, CS), (0xFF10, 0xFF19, EN), (0xFF1A, 0xFF1A, CS),
+    (0xFF1B, 0xFF20, ON), (0xFF3B, 0xFF40, ON), (0xFF5B, 0xFF65, ON), (0xFFE0, 0xFFE1, ET), (0xFFE2, 0xFFE4, ON),
+    (0xFFE5, 0xFFE6, ET), (0xFFE8, 0xFFEE, ON), (0xFFF9, 0xFFFD, ON), (0x10800, 0x10CFF, R), (0x10D00, 0x10D3F, AL),
+    (0x10D40, 0x10EBF, R), (0x10EC0, 0x10EFF, AL), (0x10F00, 0x10F2F, R), (0x10F30, 0x10F6F, AL), (0x10F70, 0x10FFF, R),
+    (0x1E800, 0x1EDFF, R), (0x1EE00, 0x1EEEF, AL), (0x1EEF0, 0x1EEF1, ON), (0x1EEF2, 0x1EFFF, R), (0xE0001, 0xE007F, BN),
+    (0xE0100, 0xE01EF, NSM),
+];
+
+// Characters with a mirrored counterpart (Bidi_Mirroring_Glyph), as pairs that map both ways
+const MIRROR_PAIRS: &[(u32, u32)] = &[
+    (0x0028, 0x0029), (0x003C, 0x003E), (0x005B, 0x005D), (0x007B, 0x007D), (0x00AB, 0x00BB), (0x0F3A, 0x0F3B),
+    (0x0F3C, 0x0F3D), (0x169B, 0x169C), (0x2039, 0x203A), (0x2045, 0x2046), (0x207D, 0x207E), (0x208D, 0x208E),
+    (0x2208, 0x220B), (0x2209, 0x220C), (0x220A, 0x220D), (0x2215, 0x29F5), (0x223C, 0x223D), (0x2243, 0x22CD),
+    (0x2252, 0x2253), (0x2254, 0x2255), (0x2264, 0x2265), (0x2266, 0x2267), (0x2268, 0x2269), (0x226A, 0x226B),
+    (0x226E, 0x226F), (0x2270, 0x2271), (0x2272, 0x2273), (0x2274, 0x2275), (0x2276, 0x2277), (0x2278, 0x2279),
+    (0x227A, 0x227B), (0x227C, 0x227D), (0x227E, 0x227F), (0x2280, 0x2281), (0x2282, 0x2283), (0x2284, 0x2285),
+    (0x2286, 0x2287), (0x2288, 0x2289), (0x228A, 0x228B), (0x228F, 0x2290), (0x2291, 0x2292), (0x22A2, 0x22A3),
+    (0x22B0, 0x22B1), (0x22B2, 0x22B3), (0x22B4, 0x22B5), (0x22B6, 0x22B7), (0x22C9, 0x22CA), (0x22CB, 0x22CC),
+    (0x22D0, 0x22D1), (0x22D6, 0x22D7), (0x22D8, 0x22D9), (0x22DA, 0x22DB), (0x22DC, 0x22DD), (0x22DE, 0x22DF),
+    (0x22E0, 0x22E1), (0x22E2, 0x22E3), (0x22E4, 0x22E5), (0x22E6, 0x22E7), (0x22E8, 0x22E9), (0x22EA, 0x22EB),
+    (0x22EC, 0x22ED), (0x2308, 0x2309), (0x230A, 0x230B), (0x2329, 0x232A), (0x2768, 0x2769), (0x276A, 0x276B),
+    (0x276C, 0x276D), (0x276E, 0x276F), (0x2770, 0x2771), (0x2772, 0x2773), (0x2774, 0x2775), (0x27C3, 0x27C4),
+    (0x27C5, 0x27C6), (0x27C8, 0x27C9), (0x27D5, 0x27D6), (0x27DD, 0x27DE), (0x27E2, 0x27E3), (0x27E4, 0x27E5),
+    (0x27E6, 0x27E7), (0x27E8, 0x27E9), (0x27EA, 0x27EB), (0x27EC, 0x27ED), (0x27EE, 0x27EF), (0x2983, 0x2984),
+    (0x2985, 0x2986), (0x2987, 0x2988), (0x2989, 0x298A), (0x298B, 0x298C), (0x298D, 0x2990), (0x298E, 0x298F),
+    (0x2991, 0x2992), (0x2993, 0x2994), (0x2995, 0x2996), (0x2997, 0x2998), (0x29D8, 0x29D9), (0x29DA, 0x29DB),
+    (0x29FC, 0x29FD), (0x2E02, 0x2E03), (0x2E04, 0x2E05), (0x2E09, 0x2E0A), (0x2E0C, 0x2E0D), (0x2E1C, 0x2E1D),
+    (0x2E20, 0x2E21), (0x2E22, 0x2E23), (0x2E24, 0x2E25), (0x2E26, 0x2E27), (0x2E28, 0x2E29), (0x2E55, 0x2E56),
+    (0x2E57, 0x2E58), (0x2E59, 0x2E5A), (0x2E5B, 0x2E5C), (0x3008, 0x3009), (0x300A, 0x300B), (0x300C, 0x300D),
+    (0x300E, 0x300F), (0x3010, 0x3011), (0x3014, 0x3015), (0x3016, 0x3017), (0x3018, 0x3019), (0x301A, 0x301B),
+    (0xFE59, 0xFE5A), (0xFE5B, 0xFE5C), (0xFE5D, 0xFE5E), (0xFE64, 0xFE65), (0xFF08, 0xFF09), (0xFF1C, 0xFF1E),
+    (0xFF3B, 0xFF3D), (0xFF5B, 0xFF5D), (0xFF5F, 0xFF60), (0xFF62, 0xFF63),
+];
+
+// Paired brackets (Bidi_Paired_Bracket), opening then closing
+const BRACKET_PAIRS: &[(u32, u32)] = &[
+    (0x0028, 0x0029), (0x005B, 0x005D), (0x007B, 0x007D), (0x0F3A, 0x0F3B), (0x0F3C, 0x0F3D), (0x169B, 0x169C),
+    (0x2045, 0x2046), (0x207D, 0x207E), (0x208D, 0x208E), (0x2308, 0x2309), (0x230A, 0x230B), (0x2329, 0x232A),
+    (0x2768, 0x2769), (0x276A, 0x276B), (0x276C, 0x276D), (0x276E, 0x276F), (0x2770, 0x2771), (0x2772, 0x2773),
+    (0x2774, 0x2775), (0x27C5, 0x27C6), (0x27E6, 0x27E7), (0x27E8, 0x27E9), (0x27EA, 0x27EB), (0x27EC, 0x27ED),
+    (0x27EE, 0x27EF), (0x2983, 0x2984), (0x2985, 0x2986), (0x2987, 0x2988), (0x2989, 0x298A), (0x298B, 0x298C),
+    (0x298D, 0x2990), (0x298F, 0x298E), (0x2991, 0x2992), (0x2993, 0x2994), (0x2995, 0x2996), (0x2997, 0x2998),
+    (0x29D8, 0x29D9), (0x29DA, 0x29DB), (0x29FC, 0x29FD), (0x2E22, 0x2E23), (0x2E24, 0x2E25), (0x2E26, 0x2E27),
+    (0x2E28, 0x2E29), (0x2E55, 0x2E56), (0x2E57, 0x2E58), (0x2E59, 0x2E5A), (0x2E5B, 0x2E5C), (0x3008, 0x3009),
+    (0x300A, 0x300B), (0x300C, 0x300D), (0x300E, 0x300F), (0x3010, 0x3011), (0x3014, 0x3015), (0x3016, 0x3017),
+    (0x3018, 0x3019), (0x301A, 0x301B), (0xFE59, 0xFE5A), (0xFE5B, 0xFE5C), (0xFE5D, 0xFE5E), (0xFF08, 0xFF09),
+    (0xFF3B, 0xFF3D), (0xFF5B, 0xFF5D), (0xFF5F, 0xFF60), (0xFF62, 0xFF63),
+];
+
+pub fn bidi_class(ch: char) -> BidiClass {
+    let code_point = ch as u32;
+    let index = CLASS_RANGES.partition_point(|&(_, end, _)| end < code_point);
+    match CLASS_RANGES.get(index) {
+        Some(&(start, _, class)) if start <= code_point => class,
+        _ => L,
+    }
+}
+
+// The character an RTL run shows in place of this one, like ( for )
+pub fn mirrored(ch: char) -> Option<char> {
+    let code_point = ch as u32;
+    MIRROR_PAIRS.iter()
+        .find_map(|&(left, right)| if left == code_point { Some(right) } else if right == code_point { Some(left) } else { None })
+        .and_then(char::from_u32)
+}
+
+fn opening_bracket_for(ch: char) -> Option<char> {
+    BRACKET_PAIRS.iter().find(|&&(_, closing)| closing == ch as u32).and_then(|&(opening, _)| char::from_u32(opening))
+}
+
+fn is_opening_bracket(ch: char) -> bool {
+    BRACKET_PAIRS.iter().any(|&(opening, _)| opening == ch as u32)
+}
+
+fn is_isolate_initiator(class: BidiClass) -> bool {
+    matches!(class, LRI | RLI | FSI)
+}
+
+// Characters that rule X9 takes out of the later stages
+fn is_removed(class: BidiClass) -> bool {
+    matches!(class, LRE | RLE | LRO | RLO | PDF | BN)
+}
+
+fn is_neutral_or_isolate(class: BidiClass) -> bool {
+    matches!(class, B | S | WS | ON | LRI | RLI | FSI | PDI)
+}
+
+fn direction_of_level(level: u8) -> BidiClass {
+    if level.is_multiple_of(2) { L } else { R }
+}
+
+// Numbers count as R when deciding the direction of neutrals
+fn strong_direction(class: BidiClass) -> Option<BidiClass> {
+    match class {
+        L => Some(L),
+        R | AL | EN | AN => Some(R),
+        _ => None,
+    }
+}
+
+// A stretch of one embedding level, in logical order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BidiRun {
+    pub start: usize,
+    pub end: usize,
+    pub level: u8,
+}
+
+impl BidiRun {
+    pub fn direction(&self) -> Direction {
+        if self.level.is_multiple_of(2) { Direction::LeftToRight } else { Direction::RightToLeft }
+    }
+}
+
+// Resolved embedding levels for one paragraph of text, by character index
+#[derive(Debug, Clone)]
+pub struct BidiParagraph {
+    pub base_level: u8,
+    pub levels: Vec<u8>,
+    classes: Vec<BidiClass>,
+}
+
+impl BidiParagraph {
+    // Runs the algorithm over a paragraph. Without a direction, the first strong character
+    // outside any isolate decides it (rules P2 and P3), defaulting to left to right.
+    pub fn new(text: &[char], direction: Option<Direction>) -> Self {
+        let classes: Vec<BidiClass> = text.iter().map(|&ch| bidi_class(ch)).collect();
+        let matching_pdis = matching_pdis(&classes);
+        let base_level = match direction {
+            Some(Direction::LeftToRight) => 0,
+            Some(Direction::RightToLeft) => 1,
+            None => first_strong_level(&classes, &matching_pdis, 0..classes.len()).unwrap_or(0),
+        };
+
+        let (mut levels, mut resolved) = explicit_levels(&classes, &matching_pdis, base_level);
+        for sequence in isolating_run_sequences(&classes, &levels, &matching_pdis) {
+            let level = levels[sequence[0]];
+            let before = (0..sequence[0]).rev().find(|&i| !is_removed(classes[i])).map_or(base_level, |i| levels[i]);
+            let last = *sequence.last().unwrap_or(&0);
+            let after = if is_isolate_initiator(classes[last]) {
+                base_level
+            } else {
+                (last + 1..classes.len()).find(|&i| !is_removed(classes[i])).map_or(base_level, |i| levels[i])
+            };
+            let sos = direction_of_level(level.max(before));
+            let eos = direction_of_level(level.max(after));
+
+            let mut types: Vec<BidiClass> = sequence.iter().map(|&i| resolved[i]).collect();
+            resolve_weak_types(&mut types, sos);
+            resolve_brackets(&mut types, &sequence, text, &classes, level, sos);
+            resolve_neutral_types(&mut types, level, sos, eos);
+            for (&i, &class) in sequence.iter().zip(&types) {
+                resolved[i] = class;
+                // Rules I1 and I2
+                levels[i] += match (level % 2, class) {
+                    (0, R) => 1,
+                    (0, AN | EN) => 2,
+                    (1, L | EN | AN) => 1,
+                    _ => 0,
+                };
+            }
+        }
+
+        // Removed characters take the level of what comes before them, which keeps runs whole
+        for i in 0..classes.len() {
+            if is_removed(classes[i]) {
+                levels[i] = if i == 0 { base_level } else { levels[i - 1] };
+            }
+        }
+
+        BidiParagraph { base_level, levels, classes }
+    }
+
+    pub fn direction(&self) -> Direction {
+        if self.base_level.is_multiple_of(2) { Direction::LeftToRight } else { Direction::RightToLeft }
+    }
+
+    // Levels of one line after rule L1 puts separators and trailing whitespace back at the
+    // paragraph level
+    pub fn line_levels(&self, line: Range<usize>) -> Vec<u8> {
+        let mut levels = self.levels[line.clone()].to_vec();
+        let classes = &self.classes[line];
+        let is_whitespace = |class: BidiClass| matches!(class, WS | LRI | RLI | FSI | PDI) || is_removed(class);
+        let mut trailing_start = 0;
+        for i in 0..classes.len() {
+            if matches!(classes[i], S | B) {
+                for level in &mut levels[trailing_start..=i] {
+                    *level = self.base_level;
+                }
+            }
+            if !is_whitespace(classes[i]) {
+                trailing_start = i + 1;
+            }
+        }
+        for level in &mut levels[trailing_start..] {
+            *level = self.base_level;
+        }
+        levels
+    }
+
+    // The runs of one line in visual order, left to right. Rule L2 reverses the order of runs;
+    // the characters inside a right-to-left run stay in logical order for shaping to reverse.
+    pub fn visual_runs(&self, line: Range<usize>) -> Vec<BidiRun> {
+        let levels = self.line_levels(line.clone());
+        let mut runs: Vec<BidiRun> = Vec::new();
+        for (i, &level) in levels.iter().enumerate() {
+            match runs.last_mut() {
+                Some(run) if run.level == level => run.end = line.start + i + 1,
+                _ => runs.push(BidiRun { start: line.start + i, end: line.start + i + 1, level }),
+            }
+        }
+
+        let highest = runs.iter().map(|run| run.level).max().unwrap_or(0);
+        let lowest_odd = runs.iter().map(|run| run.level | 1).min().unwrap_or(1);
+        for level in (lowest_odd..=highest).rev() {
+            let mut start = 0;
+            while start < runs.len() {
+                if runs[start].level < level {
+                    start += 1;
+                    continue;
+                }
+                let end = (start..runs.len()).find(|&i| runs[i].level < level).unwrap_or(runs.len());
+                runs[start..end].reverse();
+                start = end;
+            }
+        }
+        runs
+    }
+}
+
+// For each isolate initiator, the index of the PDI that closes it (BD9)
+fn matching_pdis(classes: &[BidiClass]) -> Vec<Option<usize>> {
+    let mut matches = vec![None; classes.len()];
+    let mut open = Vec::new();
+    for (i, &class) in classes.iter().enumerate() {
+        if is_isolate_initiator(class) {
+            open.push(i);
+        } else if class == PDI {
+            if let Some(initiator) = open.pop() {
+                matches[initiator] = Some(i);
+            }
+        }
+    }
+    matches
+}
+
+// The level of the first strong character in a range, skipping over isolates
+fn first_strong_level(classes: &[BidiClass], matching_pdis: &[Option<usize>], range: Range<usize>) -> Option<u8> {
+    let mut i = range.start;
+    while i < range.end {
+        match classes[i] {
+            L => return Some(0),
+            R | AL => return Some(1),
+            class if is_isolate_initiator(class) => i = matching_pdis[i]?,
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+#[derive(Clone, Copy)]
+struct StatusEntry {
+    level: u8,
+    override_class: Option<BidiClass>,
+    isolate: bool,
+}
+
+// Rules X1 to X8: levels from explicit embeddings, overrides and isolates, along with the
+// classes overrides leave behind
+fn explicit_levels(classes: &[BidiClass], matching_pdis: &[Option<usize>], base_level: u8) -> (Vec<u8>, Vec<BidiClass>) {
+    let mut levels = vec![base_level; classes.len()];
+    let mut resolved = classes.to_vec();
+    let mut stack = vec![StatusEntry { level: base_level, override_class: None, isolate: false }];
+    let mut overflow_isolates = 0;
+    let mut overflow_embeddings = 0;
+    let mut valid_isolates = 0;
+
+    for i in 0..classes.len() {
+        let last = *stack.last().unwrap();
+        match classes[i] {
+            RLE | LRE | RLO | LRO => {
+                let rtl = matches!(classes[i], RLE | RLO);
+                let level = if rtl { (last.level + 1) | 1 } else { (last.level + 2) & !1 };
+                levels[i] = last.level;
+                if level <= MAX_DEPTH && overflow_isolates == 0 && overflow_embeddings == 0 {
+                    let override_class = match classes[i] {
+                        RLO => Some(R),
+                        LRO => Some(L),
+                        _ => None,
+                    };
+                    stack.push(StatusEntry { level, override_class, isolate: false });
+                } else if overflow_isolates == 0 {
+                    overflow_embeddings += 1;
+                }
+            }
+            RLI | LRI | FSI => {
+                levels[i] = last.level;
+                if let Some(class) = last.override_class {
+                    resolved[i] = class;
+                }
+                let rtl = match classes[i] {
+                    RLI => true,
+                    LRI => false,
+                    _ => first_strong_level(classes, matching_pdis, i + 1..matching_pdis[i].unwrap_or(classes.len())) == Some(1),
+                };
+                let level = if rtl { (last.level + 1) | 1 } else { (last.level + 2) & !1 };
+                if level <= MAX_DEPTH && overflow_isolates == 0 && overflow_embeddings == 0 {
+                    valid_isolates += 1;
+                    stack.push(StatusEntry { level, override_class: None, isolate: true });
+                } else {
+                    overflow_isolates += 1;
+                }
+            }
+            PDI => {
+                if overflow_isolates > 0 {
+                    overflow_isolates -= 1;
+                } else if valid_isolates > 0 {
+                    overflow_embeddings = 0;
+                    while stack.last().is_some_and(|entry| !entry.isolate) {
+                        stack.pop();
+                    }
+                    stack.pop();
+                    valid_isolates -= 1;
+                }
+                let last = *stack.last().unwrap();
+                levels[i] = last.level;
+                if let Some(class) = last.override_class {
+                    resolved[i] = class;
+                }
+            }
+            PDF => {
+                if overflow_isolates == 0 && overflow_embeddings > 0 {
+                    overflow_embeddings -= 1;
+                } else if overflow_isolates == 0 && !last.isolate && stack.len() >= 2 {
+                    stack.pop();
+                }
+                levels[i] = stack.last().unwrap().level;
+            }
+            B => levels[i] = base_level,
+            BN => levels[i] = last.level,
+            _ => {
+                levels[i] = last.level;
+                if let Some(class) = last.override_class {
+                    resolved[i] = class;
+                }
+            }
+        }
+    }
+    (levels, resolved)
+}
+
+// Rule X10: level runs chained across isolates into the sequences the later rules work on
+fn isolating_run_sequences(classes: &[BidiClass], levels: &[u8], matching_pdis: &[Option<usize>]) -> Vec<Vec<usize>> {
+    let mut runs: Vec<Vec<usize>> = Vec::new();
+    let mut previous_level = None;
+    for i in (0..classes.len()).filter(|&i| !is_removed(classes[i])) {
+        match runs.last_mut() {
+            Some(run) if previous_level == Some(levels[i]) => run.push(i),
+            _ => runs.push(vec![i]),
+        }
+        previous_level = Some(levels[i]);
+    }
+
+    let matched_pdis: Vec<usize> = matching_pdis.iter().flatten().copied().collect();
+    let mut sequences = Vec::new();
+    for run in &runs {
+        if matched_pdis.contains(&run[0]) {
+            continue;
+        }
+        let mut sequence = run.clone();
+        while let Some(pdi) = sequence.last().and_then(|&last| matching_pdis[last]) {
+            match runs.iter().find(|run| run[0] == pdi) {
+                Some(next) => sequence.extend_from_slice(next),
+                None => break,
+            }
+        }
+        sequences.push(sequence);
+    }
+    sequences
+}
+
+// Rules W1 to W7
+fn resolve_weak_types(types: &mut [BidiClass], sos: BidiClass) {
+    for i in 0..types.len() {
+        if types[i] == NSM {
+            types[i] = match i.checked_sub(1).map(|previous| types[previous]) {
+                None => sos,
+                Some(LRI | RLI | FSI | PDI) => ON,
+                Some(class) => class,
+            };
+        }
+    }
+
+    let mut last_strong = sos;
+    for class in types.iter_mut() {
+        match *class {
+            L | R | AL => last_strong = *class,
+            EN if last_strong == AL => *class = AN,
+            _ => {}
+        }
+    }
+
+    for class in types.iter_mut() {
+        if *class == AL {
+            *class = R;
+        }
+    }
+
+    for i in 1..types.len().saturating_sub(1) {
+        let (before, after) = (types[i - 1], types[i + 1]);
+        match types[i] {
+            ES if before == EN && after == EN => types[i] = EN,
+            CS if before == after && matches!(before, EN | AN) => types[i] = before,
+            _ => {}
+        }
+    }
+
+    let mut i = 0;
+    while i < types.len() {
+        if types[i] != ET {
+            i += 1;
+            continue;
+        }
+        let end = (i..types.len()).find(|&j| types[j] != ET).unwrap_or(types.len());
+        if (i > 0 && types[i - 1] == EN) || types.get(end) == Some(&EN) {
+            for class in &mut types[i..end] {
+                *class = EN;
+            }
+        }
+        i = end;
+    }
+
+    for class in types.iter_mut() {
+        if matches!(*class, ES | ET | CS) {
+            *class = ON;
+        }
+    }
+
+    let mut last_strong = sos;
+    for class in types.iter_mut() {
+        match *class {
+            L | R => last_strong = *class,
+            EN if last_strong == L => *class = L,
+            _ => {}
+        }
+    }
+}
+
+// Rule N0: paired brackets take the embedding direction when it appears between them, or the
+// opposite direction when that is both inside and before them
+fn resolve_brackets(types: &mut [BidiClass], sequence: &[usize], text: &[char], classes: &[BidiClass], level: u8, sos: BidiClass) {
+    let mut pairs = Vec::new();
+    let mut open: Vec<(char, usize)> = Vec::new();
+    for (position, &i) in sequence.iter().enumerate() {
+        if types[position] != ON {
+            continue;
+        }
+        let ch = text[i];
+        if is_opening_bracket(ch) {
+            if open.len() == 63 {
+                break;
+            }
+            open.push((ch, position));
+        } else if let Some(opening) = opening_bracket_for(ch) {
+            if let Some(depth) = open.iter().rposition(|&(bracket, _)| bracket == opening) {
+                pairs.push((open[depth].1, position));
+                open.truncate(depth);
+            }
+        }
+    }
+    pairs.sort_unstable();
+
+    let embedding = direction_of_level(level);
+    let opposite = if embedding == L { R } else { L };
+    for (opening, closing) in pairs {
+        let inside: Vec<BidiClass> = types[opening + 1..closing].iter().filter_map(|&class| strong_direction(class)).collect();
+        let direction = if inside.contains(&embedding) {
+            embedding
+        } else if inside.contains(&opposite) {
+            let before = types[..opening].iter().rev().find_map(|&class| strong_direction(class)).unwrap_or(sos);
+            if before == opposite { opposite } else { embedding }
+        } else {
+            continue;
+        };
+        for bracket in [opening, closing] {
+            types[bracket] = direction;
+            // Marks on a bracket follow it
+            for position in bracket + 1..types.len() {
+                if classes[sequence[position]] != NSM {
+                    break;
+                }
+                types[position] = direction;
+            }
+        }
+    }
+}
+
+// Rules N1 and N2: neutrals between two of the same direction take it, others the embedding's
+fn resolve_neutral_types(types: &mut [BidiClass], level: u8, sos: BidiClass, eos: BidiClass) {
+    let mut i = 0;
+    while i < types.len() {
+        if !is_neutral_or_isolate(types[i]) {
+            i += 1;
+            continue;
+        }
+        let end = (i..types.len()).find(|&j| !is_neutral_or_isolate(types[j])).unwrap_or(types.len());
+        let before = if i == 0 { sos } else { strong_direction(types[i - 1]).unwrap_or(sos) };
+        let after = if end == types.len() { eos } else { strong_direction(types[end]).unwrap_or(eos) };
+        let direction = if before == after { before } else { direction_of_level(level) };
+        for class in &mut types[i..end] {
+            *class = direction;
+        }
+        i = end;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Resolved levels and visual order of a one-line paragraph, as BidiCharacterTest.txt lists
+    // them. Nothing here uses explicit embeddings, so no character is removed from the order.
+    fn resolve(text: &str, direction: Option<Direction>) -> (Direction, Vec<u8>, Vec<usize>) {
+        let chars: Vec<char> = text.chars().collect();
+        let paragraph = BidiParagraph::new(&chars, direction);
+        let order = paragraph.visual_runs(0..chars.len()).iter()
+            .flat_map(|run| {
+                let indices: Vec<usize> = (run.start..run.end).collect();
+                match run.direction() {
+                    Direction::LeftToRight => indices,
+                    Direction::RightToLeft => indices.into_iter().rev().collect(),
+                }
+            })
+            .collect();
+        (paragraph.direction(), paragraph.line_levels(0..chars.len()), order)
+    }
+
+    #[test]
+    fn european_numbers_in_hebrew_keep_their_order() {
+        let (direction, levels, order) = resolve("אב 123 גד", None);
+        assert_eq!(direction, Direction::RightToLeft);
+        assert_eq!(levels, [1, 1, 1, 2, 2, 2, 1, 1, 1]);
+        assert_eq!(order, [8, 7, 6, 3, 4, 5, 2, 1, 0]);
+    }
+
+    #[test]
+    fn separator_between_digits_joins_the_number_after_hebrew() {
+        // W4 makes the full stop part of 1.5, and the number stays left to right inside the
+        // Hebrew run it follows
+        let (direction, levels, order) = resolve("ab אב 1.5 cd", None);
+        assert_eq!(direction, Direction::LeftToRight);
+        assert_eq!(levels, [0, 0, 0, 1, 1, 1, 2, 2, 2, 0, 0, 0]);
+        assert_eq!(order, [0, 1, 2, 6, 7, 8, 5, 4, 3, 9, 10, 11]);
+    }
+
+    #[test]
+    fn arabic_letters_make_digits_arabic_numbers() {
+        let (_, levels, order) = resolve("ا 12", None);
+        assert_eq!(levels, [1, 1, 2, 2]);
+        assert_eq!(order, [2, 3, 1, 0]);
+    }
+
+    #[test]
+    fn trailing_whitespace_takes_the_paragraph_level() {
+        let (_, levels, _) = resolve("ab אב ", None);
+        assert_eq!(levels, [0, 0, 0, 1, 1, 0]);
+    }
+
+    #[test]
+    fn brackets_around_opposite_text_follow_the_embedding_direction() {
+        // N0 with the context before the brackets matching the paragraph
+        let (_, levels, order) = resolve("א(b)ג", None);
+        assert_eq!(levels, [1, 1, 2, 1, 1]);
+        assert_eq!(order, [4, 3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn brackets_take_the_opposite_direction_from_the_context_before() {
+        // Without N0 the closing bracket would sit between R and the end of the paragraph and
+        // take the paragraph's level
+        let (_, levels, order) = resolve("a א(ב)", None);
+        assert_eq!(levels, [0, 0, 1, 1, 1, 1]);
+        assert_eq!(order, [0, 1, 5, 4, 3, 2]);
+    }
+
+    #[test]
+    fn paragraph_direction_skips_isolated_text() {
+        let (direction, levels, order) = resolve("\u{2067}א\u{2069} b", None);
+        assert_eq!(direction, Direction::LeftToRight);
+        assert_eq!(levels, [0, 1, 0, 0, 0]);
+        assert_eq!(order, [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn first_strong_isolate_takes_its_direction_from_its_content() {
+        let (_, levels, order) = resolve("a \u{2068}אb\u{2069}", None);
+        assert_eq!(levels, [0, 0, 0, 1, 2, 0]);
+        assert_eq!(order, [0, 1, 2, 4, 3, 5]);
+    }
+
+    #[test]
+    fn explicit_direction_overrides_the_first_strong_character() {
+        let (direction, levels, _) = resolve("ab", Some(Direction::RightToLeft));
+        assert_eq!(direction, Direction::RightToLeft);
+        assert_eq!(levels, [2, 2]);
+    }
+}
@@ -1,11 +1,38 @@
 use clap::{App, Arg};
+use ttf_parser::image::Color;
+use ttf_parser::lcd::SubpixelOrder;
+use ttf_parser::missing::MissingGlyphs;
+use ttf_parser::opentype::{parse_tag, FeatureSetting};
+use ttf_parser::paragraph::Alignment;
+use ttf_parser::shaping::Direction;
 
 pub struct Config {
     pub print_all_glyphs: bool,
+    #[cfg_attr(not(feature = "sdl2"), allow(dead_code))] // Viewer only
     pub debug: bool,
     pub input_string: String,
-    pub font_path: String,
+    pub font_paths: Vec<String>, // The primary font, then fallbacks in the order to try them
+    #[cfg_attr(not(feature = "sdl2"), allow(dead_code))] // Viewer only
     pub outline_thickness: i32,
+    pub hinting_ppem: Option<u16>,
+    pub png_path: Option<String>,
+    pub pgm_path: Option<String>,
+    pub svg_path: Option<String>,
+    pub specimen_path: Option<String>,
+    pub specimen_columns: usize,
+    pub specimen_rows: usize,
+    pub pixel_size: f32,
+    pub foreground: Color,
+    pub background: Color,
+    pub padding: u32,
+    pub subpixel_order: Option<SubpixelOrder>, // None renders greyscale
+    pub script: [u8; 4],
+    pub language: [u8; 4],
+    pub features: Vec<FeatureSetting>,
+    pub direction: Option<Direction>, // None takes it from the text
+    pub wrap_width: Option<f32>, // In pixels at pixel_size
+    pub alignment: Alignment,
+    pub missing_glyphs: MissingGlyphs,
 }
 
 impl Config {
@@ -32,9 +59,169 @@ impl Config {
                     .short('f')
                     .long("font")
                     .takes_value(true)
-                    .help("Path to the font file")
+                    .multiple_occurrences(true)
+                    .help("Path to the font file; repeat to fall back to later fonts for characters earlier ones lack")
                     .default_value("fonts/JetBrainsMono-Bold.ttf"),
             )
+            .arg(
+                Arg::new("hint")
+                    .long("hint")
+                    .takes_value(true)
+                    .value_name("PPEM")
+                    .value_parser(positive_ppem)
+                    .help("Grid-fit glyphs with the font's TrueType instructions at this pixels-per-em size; headless output is fitted at --size"),
+            )
+            .arg(
+                Arg::new("png")
+                    .long("png")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("Render the input headlessly to a PNG file instead of opening a window"),
+            )
+            .arg(
+                Arg::new("pgm")
+                    .long("pgm")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("Render the input headlessly to a greyscale PGM file instead of opening a window"),
+            )
+            .arg(
+                Arg::new("svg")
+                    .long("svg")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("Export the input as SVG paths instead of opening a window"),
+            )
+            .arg(
+                Arg::new("specimen")
+                    .long("specimen")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("Write a chart of every glyph with its id, name and code points (SVG if FILE ends in .svg, PNG otherwise)"),
+            )
+            .arg(
+                Arg::new("columns")
+                    .long("columns")
+                    .takes_value(true)
+                    .value_name("N")
+                    .value_parser(positive_count)
+                    .help("Glyphs per row in the specimen chart")
+                    .default_value("16"),
+            )
+            .arg(
+                Arg::new("rows")
+                    .long("rows")
+                    .takes_value(true)
+                    .value_name("N")
+                    .value_parser(positive_count)
+                    .help("Rows per specimen page; larger fonts are split into numbered files")
+                    .default_value("12"),
+            )
+            .arg(
+                Arg::new("size")
+                    .long("size")
+                    .takes_value(true)
+                    .value_name("PX")
+                    .value_parser(positive_pixel_size)
+                    .help("Pixel size of headless renders and SVG exports")
+                    .default_value("64"),
+            )
+            .arg(
+                Arg::new("fg")
+                    .long("fg")
+                    .takes_value(true)
+                    .value_name("RRGGBB")
+                    .value_parser(Color::from_hex)
+                    .help("Text colour of headless renders and SVG exports")
+                    .default_value("000000"),
+            )
+            .arg(
+                Arg::new("bg")
+                    .long("bg")
+                    .takes_value(true)
+                    .value_name("RRGGBB")
+                    .value_parser(Color::from_hex)
+                    .help("Background colour of headless renders and SVG exports")
+                    .default_value("ffffff"),
+            )
+            .arg(
+                Arg::new("padding")
+                    .long("padding")
+                    .takes_value(true)
+                    .value_name("PX")
+                    .value_parser(pixel_count)
+                    .help("Empty border around headless renders and SVG exports")
+                    .default_value("16"),
+            )
+            .arg(
+                Arg::new("lcd")
+                    .long("lcd")
+                    .takes_value(true)
+                    .value_name("ORDER")
+                    .possible_values(["rgb", "bgr", "vrgb", "vbgr"])
+                    .help("Subpixel-render headless output for an LCD with this stripe order"),
+            )
+            .arg(
+                Arg::new("script")
+                    .long("script")
+                    .takes_value(true)
+                    .value_name("TAG")
+                    .value_parser(opentype_tag)
+                    .help("OpenType script tag to shape for, e.g. latn or arab; DFLT detects it from the text")
+                    .default_value("DFLT"),
+            )
+            .arg(
+                Arg::new("language")
+                    .long("language")
+                    .takes_value(true)
+                    .value_name("TAG")
+                    .value_parser(opentype_tag)
+                    .help("OpenType language system tag, e.g. TRK; dflt uses the script's default")
+                    .default_value("dflt"),
+            )
+            .arg(
+                Arg::new("features")
+                    .long("features")
+                    .takes_value(true)
+                    .value_name("LIST")
+                    .value_parser(feature_list)
+                    .help("Comma-separated OpenType features on top of the defaults, e.g. ss01,smcp,-liga,salt=2"),
+            )
+            .arg(
+                Arg::new("direction")
+                    .long("direction")
+                    .takes_value(true)
+                    .value_name("DIR")
+                    .possible_values(["auto", "ltr", "rtl"])
+                    .help("Paragraph direction; auto takes it from the first strong character")
+                    .default_value("auto"),
+            )
+            .arg(
+                Arg::new("width")
+                    .long("width")
+                    .takes_value(true)
+                    .value_name("PX")
+                    .value_parser(positive_width)
+                    .help("Wrap lines that would be wider than this many pixels at --size"),
+            )
+            .arg(
+                Arg::new("align")
+                    .long("align")
+                    .takes_value(true)
+                    .value_name("ALIGN")
+                    .possible_values(["left", "right", "center", "justify"])
+                    .help("Alignment of the lines of text")
+                    .default_value("left"),
+            )
+            .arg(
+                Arg::new("missing")
+                    .long("missing")
+                    .takes_value(true)
+                    .value_name("STYLE")
+                    .possible_values(["skip", "notdef", "hex"])
+                    .help("How to draw characters no font has: left out, as the font's .notdef glyph, or as a box with the code point in hex")
+                    .default_value("notdef"),
+            )
             .arg(
                 Arg::new("input")
                     .help("The input string to render")
@@ -45,14 +232,102 @@ impl Config {
         let print_all_glyphs = matches.is_present("print-all-glyphs");
         let debug = matches.is_present("debug");
         let input_string = matches.value_of("input").unwrap_or("Hello, World!").to_string();
-        let font_path = matches.value_of("font").unwrap_or("fonts/JetBrainsMono-Bold.ttf").to_string();
+        let font_paths = matches.values_of("font").map(|paths| paths.map(|path| path.to_string()).collect()).unwrap_or_else(|| vec!["fonts/JetBrainsMono-Bold.ttf".to_string()]);
+        let hinting_ppem = matches.get_one::<u16>("hint").copied();
+        let png_path = matches.value_of("png").map(|path| path.to_string());
+        let pgm_path = matches.value_of("pgm").map(|path| path.to_string());
+        let svg_path = matches.value_of("svg").map(|path| path.to_string());
+        let specimen_path = matches.value_of("specimen").map(|path| path.to_string());
+        let specimen_columns = matches.get_one::<usize>("columns").copied().unwrap_or(16);
+        let specimen_rows = matches.get_one::<usize>("rows").copied().unwrap_or(12);
+        let pixel_size = matches.get_one::<f32>("size").copied().unwrap_or(64.0);
+        let foreground = matches.get_one::<Color>("fg").copied().unwrap_or(Color { r: 0, g: 0, b: 0 });
+        let background = matches.get_one::<Color>("bg").copied().unwrap_or(Color { r: 255, g: 255, b: 255 });
+        let padding = matches.get_one::<u32>("padding").copied().unwrap_or(16);
+        let subpixel_order = matches.value_of("lcd").map(|order| match order {
+            "bgr" => SubpixelOrder::Bgr,
+            "vrgb" => SubpixelOrder::VerticalRgb,
+            "vbgr" => SubpixelOrder::VerticalBgr,
+            _ => SubpixelOrder::Rgb,
+        });
+        let script = matches.get_one::<[u8; 4]>("script").copied().unwrap_or(*b"DFLT");
+        let features = matches.get_one::<Vec<FeatureSetting>>("features").cloned().unwrap_or_default();
+        let direction = match matches.value_of("direction").unwrap_or("auto") {
+            "ltr" => Some(Direction::LeftToRight),
+            "rtl" => Some(Direction::RightToLeft),
+            _ => None,
+        };
+        let wrap_width = matches.get_one::<f32>("width").copied();
+        let alignment = match matches.value_of("align").unwrap_or("left") {
+            "right" => Alignment::Right,
+            "center" => Alignment::Center,
+            "justify" => Alignment::Justify,
+            _ => Alignment::Left,
+        };
+        let missing_glyphs = match matches.value_of("missing").unwrap_or("notdef") {
+            "skip" => MissingGlyphs::Skip,
+            "hex" => MissingGlyphs::HexBox,
+            _ => MissingGlyphs::Notdef,
+        };
+        let language = matches.get_one::<[u8; 4]>("language").copied().unwrap_or(*b"dflt");
 
         Config {
             print_all_glyphs,
             debug,
             input_string,
-            font_path,
+            font_paths,
             outline_thickness: 2,
+            hinting_ppem,
+            png_path,
+            pgm_path,
+            svg_path,
+            specimen_path,
+            specimen_columns,
+            specimen_rows,
+            pixel_size,
+            foreground,
+            background,
+            padding,
+            subpixel_order,
+            script,
+            language,
+            features,
+            direction,
+            wrap_width,
+            alignment,
+            missing_glyphs,
         }
     }
 }
+
+// Bad values are reported by clap with the usage instead of panicking
+fn positive_ppem(ppem: &str) -> Result<u16, String> {
+    ppem.parse::<u16>().ok().filter(|&ppem| ppem > 0).ok_or_else(|| "expects a positive pixels-per-em size".to_string())
+}
+
+fn positive_pixel_size(size: &str) -> Result<f32, String> {
+    size.parse::<f32>().ok().filter(|&size| size.is_finite() && size > 0.0).ok_or_else(|| "expects a positive pixel size".to_string())
+}
+
+fn positive_width(width: &str) -> Result<f32, String> {
+    width.parse::<f32>().ok().filter(|&width| width.is_finite() && width > 0.0).ok_or_else(|| "expects a positive pixel width".to_string())
+}
+
+fn pixel_count(count: &str) -> Result<u32, String> {
+    count.parse::<u32>().map_err(|_| "expects a whole number of pixels".to_string())
+}
+
+fn positive_count(count: &str) -> Result<usize, String> {
+    count.parse::<usize>().ok().filter(|&count| count > 0).ok_or_else(|| "expects a positive number".to_string())
+}
+
+fn opentype_tag(tag: &str) -> Result<[u8; 4], String> {
+    parse_tag(tag).ok_or_else(|| "expects a tag of up to 4 characters".to_string())
+}
+
+fn feature_list(list: &str) -> Result<Vec<FeatureSetting>, String> {
+    list.split(',')
+        .filter(|setting| !setting.is_empty())
+        .map(|setting| FeatureSetting::parse(setting.trim()).map_err(|_| "expects tags like liga, -kern or salt=2".to_string()))
+        .collect()
+}
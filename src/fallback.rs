@@ -0,0 +1,108 @@
+use std::ops::Range;
+
+use crate::bidi::{bidi_class, BidiClass, BidiParagraph};
+use crate::opentype::FeatureSetting;
+use crate::shaping::{is_default_ignorable, Direction, PositionedGlyph, ShapingFont};
+
+// A font of a fallback chain with its units per em, to bring its positions to the primary font's
+pub struct FallbackFont<'a> {
+    pub font: ShapingFont<'a>,
+    pub units_per_em: u16,
+}
+
+// Fonts tried in order for each cluster of text, starting with the primary font. Positions come
+// out in the primary font's units whichever font a glyph is from, which its font_index names.
+pub struct FontFallback<'a> {
+    pub fonts: Vec<FallbackFont<'a>>,
+}
+
+impl FontFallback<'_> {
+    // Shapes a paragraph of mixed-direction text like ShapingFont::shape_paragraph, each run
+    // shaped with fallback
+    pub fn shape_paragraph(&self, text: &str, script_tag: &[u8; 4], language_tag: &[u8; 4], direction: Option<Direction>, features: &[FeatureSetting]) -> Vec<PositionedGlyph> {
+        let chars: Vec<char> = text.chars().collect();
+        let paragraph = BidiParagraph::new(&chars, direction);
+        paragraph.visual_runs(0..chars.len()).into_iter()
+            .flat_map(|run| {
+                let run_text: String = chars[run.start..run.end].iter().collect();
+                self.shape(&run_text, script_tag, language_tag, run.direction(), features).into_iter()
+                    .map(move |glyph| PositionedGlyph { cluster: glyph.cluster + run.start, ..glyph })
+            })
+            .collect()
+    }
+
+    // How much to scale a font's units by to reach the primary font's
+    pub fn scale(&self, font_index: usize) -> f64 {
+        match (self.fonts.first(), self.fonts.get(font_index)) {
+            (Some(primary), Some(fallback)) if fallback.units_per_em != 0 => primary.units_per_em as f64 / fallback.units_per_em as f64,
+            _ => 1.0,
+        }
+    }
+
+    // Shapes a run of one direction like ShapingFont::shape. Each cluster goes to the first font
+    // that has all of its characters, or failing that its first character, and consecutive
+    // clusters with the same font are shaped together so their substitutions still apply.
+    // Clusters no font covers stay with the primary font.
+    pub fn shape(&self, text: &str, script_tag: &[u8; 4], language_tag: &[u8; 4], direction: Direction, features: &[FeatureSetting]) -> Vec<PositionedGlyph> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut pieces: Vec<(usize, Range<usize>)> = Vec::new();
+        for cluster in clusters(&chars) {
+            let font_index = self.font_for(&chars[cluster.clone()]);
+            match pieces.last_mut() {
+                Some((index, piece)) if *index == font_index => piece.end = cluster.end,
+                _ => pieces.push((font_index, cluster)),
+            }
+        }
+        if direction == Direction::RightToLeft {
+            pieces.reverse();
+        }
+
+        let mut glyphs = Vec::new();
+        for (font_index, piece) in pieces {
+            let Some(fallback) = self.fonts.get(font_index) else {
+                continue;
+            };
+            let scale = self.scale(font_index);
+            let scale_value = |value: i32| (value as f64 * scale).round() as i32;
+            let piece_text: String = chars[piece.clone()].iter().collect();
+            glyphs.extend(fallback.font.shape(&piece_text, script_tag, language_tag, direction, features).into_iter()
+                .map(|glyph| PositionedGlyph {
+                    cluster: glyph.cluster + piece.start,
+                    font_index,
+                    x_advance: scale_value(glyph.x_advance),
+                    y_advance: scale_value(glyph.y_advance),
+                    x_offset: scale_value(glyph.x_offset),
+                    y_offset: scale_value(glyph.y_offset),
+                    ..glyph
+                }));
+        }
+        glyphs
+    }
+
+    // Whether any font of the chain has the character
+    pub fn covers(&self, ch: char) -> bool {
+        self.fonts.iter().any(|fallback| fallback.font.covers(ch))
+    }
+
+    fn font_for(&self, cluster: &[char]) -> usize {
+        self.fonts.iter()
+            .position(|fallback| cluster.iter().all(|&ch| is_default_ignorable(ch) || fallback.font.covers(ch)))
+            .or_else(|| self.fonts.iter().position(|fallback| cluster.first().is_some_and(|&ch| fallback.font.covers(ch))))
+            .unwrap_or(0)
+    }
+}
+
+// Splits text into clusters of a character with the marks, joiners and variation selectors
+// after it, and whatever a zero width joiner joins on
+fn clusters(chars: &[char]) -> Vec<Range<usize>> {
+    let mut clusters: Vec<Range<usize>> = Vec::new();
+    for (index, &ch) in chars.iter().enumerate() {
+        let continues = index > 0
+            && (matches!(bidi_class(ch), BidiClass::NSM | BidiClass::BN) || chars[index - 1] == '\u{200D}');
+        match clusters.last_mut() {
+            Some(cluster) if continues => cluster.end = index + 1,
+            _ => clusters.push(index..index + 1),
+        }
+    }
+    clusters
+}
@@ -0,0 +1,89 @@
+use ttf_parser::buffer::ByteBuffer;
+use ttf_parser::fallback::FallbackFont;
+use ttf_parser::gdef::GdefTable;
+use ttf_parser::gpos::GposTable;
+use ttf_parser::gsub::GsubTable;
+use ttf_parser::hinting::Hinter;
+use ttf_parser::reader::{read_table_directory, FontParser};
+use ttf_parser::shaping::ShapingFont;
+use ttf_parser::table::{CmapFormat4, HeadTable, HheaTable, HmtxTable, KernTable, MaxpTable, Os2Table};
+use ttf_parser::utils::read_file_to_byte_array;
+
+// The tables of one font the viewer reads up front. Its parser is kept apart so glyphs can be
+// read while shaping borrows the tables.
+pub struct FontFile {
+    pub head_table: HeadTable,
+    pub maxp_table: MaxpTable,
+    pub hhea_table: HheaTable,
+    pub hmtx_table: HmtxTable,
+    pub glyph_offsets: Vec<u32>,
+    pub cmap_subtable: CmapFormat4,
+    pub kern_table: Option<KernTable>,
+    pub gdef_table: Option<GdefTable>,
+    pub gpos_table: Option<GposTable>,
+    pub gsub_table: Option<GsubTable>,
+    pub os2_table: Option<Os2Table>,
+}
+
+impl FontFile {
+    pub fn load(path: &str) -> (FontParser, FontFile) {
+        let bytes = read_file_to_byte_array(path);
+        let mut byte_buffer = ByteBuffer::new(bytes);
+
+        let table_records = read_table_directory(&mut byte_buffer);
+        let mut parser = FontParser::new(byte_buffer, table_records);
+
+        let head_table = parser.read_head_table().unwrap_or_else(|| panic!("head table not found in {}", path));
+        let maxp_table = parser.read_maxp_table().unwrap_or_else(|| panic!("maxp table not found in {}", path));
+        let hhea_table = parser.read_hhea_table().unwrap_or_else(|| panic!("hhea table not found in {}", path));
+        let hmtx_table = parser.read_hmtx_table(maxp_table.num_glyphs, hhea_table.num_h_metrics).unwrap_or_else(|| panic!("hmtx table not found in {}", path));
+        let glyph_offsets = parser.read_glyph_offsets(maxp_table.num_glyphs, head_table.index_to_loc_format).unwrap_or_else(|| panic!("glyph offsets not found in {}", path));
+        let cmap_table = parser.read_cmap_table().unwrap_or_else(|| panic!("cmap table not found in {}", path));
+        let cmap_subtable = parser.read_cmap_subtable(&cmap_table).unwrap_or_else(|| panic!("cmap subtable not found in {}", path));
+        let kern_table = parser.read_kern_table();
+        let gdef_table = parser.read_gdef_table();
+        let gpos_table = parser.read_gpos_table();
+        let gsub_table = parser.read_gsub_table();
+        let os2_table = parser.read_os2_table();
+
+        let font = FontFile {
+            head_table,
+            maxp_table,
+            hhea_table,
+            hmtx_table,
+            glyph_offsets,
+            cmap_subtable,
+            kern_table,
+            gdef_table,
+            gpos_table,
+            gsub_table,
+            os2_table,
+        };
+        (parser, font)
+    }
+
+    pub fn hinter(&self, parser: &mut FontParser, ppem: u16) -> Result<Hinter, String> {
+        let fpgm = parser.read_fpgm_table().unwrap_or_default();
+        let prep = parser.read_prep_table().unwrap_or_default();
+        let cvt = parser.read_cvt_table().unwrap_or_default();
+        Hinter::new(&self.head_table, &self.maxp_table, &fpgm, &prep, &cvt, ppem)
+    }
+
+    pub fn shaping_font(&self) -> ShapingFont<'_> {
+        ShapingFont {
+            cmap: &self.cmap_subtable,
+            hmtx: &self.hmtx_table,
+            gdef: self.gdef_table.as_ref(),
+            gsub: self.gsub_table.as_ref(),
+            gpos: self.gpos_table.as_ref(),
+            kern: self.kern_table.as_ref(),
+        }
+    }
+
+    pub fn fallback_font(&self) -> FallbackFont<'_> {
+        FallbackFont {
+            font: self.shaping_font(),
+            units_per_em: self.head_table.units_per_em,
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use crate::buffer::ByteBuffer;
+use crate::opentype::{read_class_def, read_coverage, read_offset16, read_offset32, ClassDef, Coverage};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlyphClass {
+    Unclassified,
+    Base,
+    Ligature,
+    Mark,
+    Component,
+}
+
+// Position of a caret between the components of a ligature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaretValue {
+    // Formats 1 and 3, in font units along the baseline (format 3's device table is ignored)
+    Coordinate(i16),
+    // Format 2, a point of the hinted outline whose x coordinate is the caret
+    ContourPoint(u16),
+}
+
+// Glyph definitions shared by GSUB and GPOS. Every part is optional in the font and reads as
+// empty when missing.
+#[derive(Debug, Clone, Default)]
+pub struct GdefTable {
+    pub glyph_class_def: ClassDef,
+    pub mark_attach_class_def: ClassDef,
+    // Per covered glyph, the contour points other glyphs may attach to
+    pub attach_coverage: Option<Coverage>,
+    pub attach_points: Vec<Vec<u16>>,
+    // Per covered ligature, the carets between its components
+    pub lig_caret_coverage: Option<Coverage>,
+    pub lig_carets: Vec<Vec<CaretValue>>,
+    // Version 1.2 and later
+    pub mark_glyph_sets: Vec<Coverage>,
+}
+
+impl GdefTable {
+    pub fn glyph_class(&self, glyph_index: u16) -> GlyphClass {
+        match self.glyph_class_def.class(glyph_index) {
+            1 => GlyphClass::Base,
+            2 => GlyphClass::Ligature,
+            3 => GlyphClass::Mark,
+            4 => GlyphClass::Component,
+            _ => GlyphClass::Unclassified,
+        }
+    }
+
+    pub fn mark_attachment_class(&self, glyph_index: u16) -> u16 {
+        self.mark_attach_class_def.class(glyph_index)
+    }
+
+    pub fn is_in_mark_glyph_set(&self, set_index: u16, glyph_index: u16) -> bool {
+        self.mark_glyph_sets.get(set_index as usize).is_some_and(|set| set.contains(glyph_index))
+    }
+
+    pub fn attachment_points(&self, glyph_index: u16) -> &[u16] {
+        self.attach_coverage.as_ref()
+            .and_then(|coverage| coverage.index(glyph_index))
+            .and_then(|index| self.attach_points.get(index as usize))
+            .map_or(&[], |points| points.as_slice())
+    }
+
+    pub fn ligature_carets(&self, glyph_index: u16) -> &[CaretValue] {
+        self.lig_caret_coverage.as_ref()
+            .and_then(|coverage| coverage.index(glyph_index))
+            .and_then(|index| self.lig_carets.get(index as usize))
+            .map_or(&[], |carets| carets.as_slice())
+    }
+}
+
+// Reads GDEF with the buffer positioned at the start of the table
+pub fn read_gdef(buffer: &mut ByteBuffer) -> GdefTable {
+    let table_start = buffer.current_position();
+    let major_version = buffer.read_u16();
+    let minor_version = buffer.read_u16();
+    if major_version != 1 {
+        return GdefTable::default();
+    }
+    let glyph_class_def = read_offset16(buffer, table_start);
+    let attach_list = read_offset16(buffer, table_start);
+    let lig_caret_list = read_offset16(buffer, table_start);
+    let mark_attach_class_def = read_offset16(buffer, table_start);
+    let mark_glyph_sets_def = if minor_version >= 2 { read_offset16(buffer, table_start) } else { None };
+
+    let mut gdef = GdefTable {
+        glyph_class_def: glyph_class_def.map(|position| read_class_def(buffer, position)).unwrap_or_default(),
+        mark_attach_class_def: mark_attach_class_def.map(|position| read_class_def(buffer, position)).unwrap_or_default(),
+        ..GdefTable::default()
+    };
+
+    if let Some(list_start) = attach_list {
+        buffer.set_position(list_start);
+        let coverage = read_offset16(buffer, list_start);
+        let glyph_count = buffer.read_u16();
+        let offsets: Vec<usize> = (0..glyph_count).map(|_| list_start + buffer.read_u16() as usize).collect();
+        gdef.attach_points = offsets.iter()
+            .map(|&position| {
+                buffer.set_position(position);
+                let point_count = buffer.read_u16();
+                buffer.read_array::<u16>(point_count as usize)
+            })
+            .collect();
+        gdef.attach_coverage = coverage.map(|position| read_coverage(buffer, position));
+    }
+
+    if let Some(list_start) = lig_caret_list {
+        buffer.set_position(list_start);
+        let coverage = read_offset16(buffer, list_start);
+        let lig_glyph_count = buffer.read_u16();
+        let lig_glyphs: Vec<usize> = (0..lig_glyph_count).map(|_| list_start + buffer.read_u16() as usize).collect();
+        gdef.lig_carets = lig_glyphs.iter()
+            .map(|&lig_glyph_start| {
+                buffer.set_position(lig_glyph_start);
+                let caret_count = buffer.read_u16();
+                let carets: Vec<usize> = (0..caret_count).map(|_| lig_glyph_start + buffer.read_u16() as usize).collect();
+                carets.iter().map(|&position| read_caret_value(buffer, position)).collect()
+            })
+            .collect();
+        gdef.lig_caret_coverage = coverage.map(|position| read_coverage(buffer, position));
+    }
+
+    if let Some(sets_start) = mark_glyph_sets_def {
+        buffer.set_position(sets_start);
+        if buffer.read_u16() == 1 {
+            let set_count = buffer.read_u16();
+            let coverages: Vec<Option<usize>> = (0..set_count).map(|_| read_offset32(buffer, sets_start)).collect();
+            gdef.mark_glyph_sets = coverages.iter()
+                .map(|coverage| match coverage {
+                    Some(position) => read_coverage(buffer, *position),
+                    None => Coverage::Glyphs(Vec::new()),
+                })
+                .collect();
+        }
+    }
+
+    gdef
+}
+
+fn read_caret_value(buffer: &mut ByteBuffer, position: usize) -> CaretValue {
+    buffer.set_position(position);
+    match buffer.read_u16() {
+        2 => CaretValue::ContourPoint(buffer.read_u16()),
+        _ => CaretValue::Coordinate(buffer.read_i16()),
+    }
+}
@@ -1,5 +1,8 @@
 use std::collections::HashMap;
 
+use crate::metrics::{HorizontalMetrics, VerticalMetrics};
+use crate::outline::Outline;
+
 #[derive(Debug, Clone)]
 pub struct Glyph {
     pub glyph_index: u16,
@@ -13,7 +16,174 @@ pub struct Glyph {
     pub y_coordinates: Vec<i16>,
     pub flags: Vec<u8>,
     pub processed_points: Vec<(i16, i16)>, // Combines actual points and 'implied' bezier control points
+    pub raw_end_pts_of_contours: Vec<u16>, // Indexes x/y_coordinates, while end_pts_of_contours indexes processed_points
+    pub instructions: Vec<u8>,
     pub advance_width: f64,
+    pub left_side_bearing: i16, // From hmtx; the origin sits this far left of xmin
+}
+
+// Axis-aligned box in font units, y pointing up
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BoundingBox {
+    pub x_min: f32,
+    pub y_min: f32,
+    pub x_max: f32,
+    pub y_max: f32,
+}
+
+impl BoundingBox {
+    pub fn width(&self) -> f32 {
+        self.x_max - self.x_min
+    }
+
+    pub fn height(&self) -> f32 {
+        self.y_max - self.y_min
+    }
+
+    fn from_bounds(bounds: Option<(f32, f32, f32, f32)>) -> Self {
+        bounds.map_or_else(BoundingBox::default, |(x_min, y_min, x_max, y_max)| BoundingBox { x_min, y_min, x_max, y_max })
+    }
+}
+
+impl Glyph {
+    // X coordinate of the left phantom point, which is where the pen sits. Zero in most fonts,
+    // where the left side bearing matches xmin, but the font designer's bearing wins when not.
+    pub fn origin_x(&self) -> i32 {
+        self.xmin as i32 - self.left_side_bearing as i32
+    }
+
+    pub fn horizontal_metrics(&self) -> HorizontalMetrics {
+        let advance_width = self.advance_width as i32;
+        HorizontalMetrics {
+            advance_width,
+            left_side_bearing: self.left_side_bearing as i32,
+            right_side_bearing: advance_width - self.left_side_bearing as i32 - (self.xmax as i32 - self.xmin as i32),
+        }
+    }
+
+    // The four TrueType phantom points in font units: horizontal origin and advance, then
+    // vertical origin and advance. Without vertical metrics the vertical origin is the top of
+    // the glyph and the vertical advance is one em.
+    pub fn phantom_points(&self, vertical_metrics: Option<&VerticalMetrics>, units_per_em: u16) -> [(i32, i32); 4] {
+        let origin_x = self.origin_x();
+        let (top, advance_height) = match vertical_metrics {
+            Some(vertical) => (vertical.vertical_origin_y(self) as i32, vertical.advance_height(self.glyph_index) as i32),
+            None => (self.ymax as i32, units_per_em as i32),
+        };
+        [
+            (origin_x, 0),
+            (origin_x + self.advance_width as i32, 0),
+            (0, top),
+            (0, top - advance_height),
+        ]
+    }
+
+    // All three bounding boxes are in font units with x measured from the origin (the left
+    // phantom point), which only differs from the raw glyf coordinates when the left side
+    // bearing doesn't match xmin.
+
+    // The box stored in the glyf header. Fonts are not always accurate here, and it goes stale
+    // once the outline has been hinted or otherwise moved.
+    pub fn header_bounding_box(&self) -> BoundingBox {
+        let origin_x = self.origin_x() as f32;
+        BoundingBox {
+            x_min: self.xmin as f32 - origin_x,
+            y_min: self.ymin as f32,
+            x_max: self.xmax as f32 - origin_x,
+            y_max: self.ymax as f32,
+        }
+    }
+
+    // Bounds of every outline point, off-curve control points included. For composites the
+    // coordinates already have the component transforms applied.
+    pub fn control_box(&self) -> BoundingBox {
+        BoundingBox::from_bounds(Outline::from_glyph(self).control_bounds())
+    }
+
+    // Exact bounds of the rendered curves, using the extrema of each quadratic segment
+    pub fn tight_bounding_box(&self) -> BoundingBox {
+        BoundingBox::from_bounds(Outline::from_glyph(self).tight_bounds())
+    }
+
+    // The glyph with its outline and metrics multiplied by `scale`, such as to bring a glyph
+    // from another font to this one's units per em. Instructions no longer fit the outline, so
+    // scale after hinting.
+    pub fn scaled(&self, scale: f64) -> Glyph {
+        let scale_value = |value: i16| (value as f64 * scale).round() as i16;
+        Glyph {
+            xmin: scale_value(self.xmin),
+            ymin: scale_value(self.ymin),
+            xmax: scale_value(self.xmax),
+            ymax: scale_value(self.ymax),
+            x_coordinates: self.x_coordinates.iter().map(|&x| scale_value(x)).collect(),
+            y_coordinates: self.y_coordinates.iter().map(|&y| scale_value(y)).collect(),
+            processed_points: self.processed_points.iter().map(|&(x, y)| (scale_value(x), scale_value(y))).collect(),
+            advance_width: self.advance_width * scale,
+            left_side_bearing: scale_value(self.left_side_bearing),
+            ..self.clone()
+        }
+    }
+}
+
+// Combines actual points and 'implied' bezier control points, returning the processed points
+// together with end point indices adjusted to index into them
+pub fn process_points(x_coordinates: &[i16], y_coordinates: &[i16], flags: &[u8], end_pts_of_contours: &[u16]) -> (Vec<(i16, i16)>, Vec<u16>) {
+    let mut processed_points = Vec::new();
+    let mut adjusted_end_pts_of_contours = end_pts_of_contours.to_vec();
+    let mut i = 0;
+    let mut contour_index = 0;
+
+    while i < x_coordinates.len() {
+        let x = x_coordinates[i];
+        let y = y_coordinates[i];
+        processed_points.push((x, y));
+
+        if (flags[i] & 1) == 0 { // If this point is off-curve
+            let mut next_i = i + 1;
+            if next_i >= x_coordinates.len() || next_i > end_pts_of_contours[contour_index] as usize {
+                next_i = if contour_index > 0 { (end_pts_of_contours[contour_index - 1] + 1).into() } else { 0 };
+            }
+
+            if (flags[next_i] & 1) == 0 { // Next point is also off-curve
+                let mid_point = ((x + x_coordinates[next_i]) / 2, (y + y_coordinates[next_i]) / 2);
+                processed_points.push(mid_point);
+                // Increment the end point indices for the current and subsequent contours
+                for end_pt in adjusted_end_pts_of_contours.iter_mut().skip(contour_index) {
+                    *end_pt += 1;
+                }
+            }
+        } else {
+            // Check for consecutive on-curve points
+            let mut next_i = i + 1;
+            if next_i >= x_coordinates.len() || next_i > end_pts_of_contours[contour_index] as usize {
+                // Loop back to the first point of the current contour
+                next_i = if contour_index > 0 { (end_pts_of_contours[contour_index - 1] + 1).into() } else { 0 };
+            }
+
+            if (flags[next_i] & 1) != 0 { // Next point is also on-curve
+                let mid_point = ((x + x_coordinates[next_i]) / 2, (y + y_coordinates[next_i]) / 2);
+                processed_points.push(mid_point);
+                // Increment the end point indices for the current and subsequent contours
+                for end_pt in adjusted_end_pts_of_contours.iter_mut().skip(contour_index) {
+                    *end_pt += 1;
+                }
+            }
+        }
+
+        i += 1;
+
+        if contour_index < end_pts_of_contours.len() && i > end_pts_of_contours[contour_index] as usize {
+            contour_index += 1;
+        }
+    }
+
+    // Do not add the first point again to close the contour if it already exists
+    if !end_pts_of_contours.is_empty() && processed_points.last() == Some(&processed_points[0]) {
+        processed_points.pop();
+        adjusted_end_pts_of_contours[contour_index - 1] -= 1;
+    }
+
+    (processed_points, adjusted_end_pts_of_contours)
 }
 
 pub struct GlyphCache {
@@ -25,6 +195,12 @@ pub struct CachedGlyphData {
     pub bounding_box: (i16, i16, i16, i16),
 }
 
+impl Default for GlyphCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl GlyphCache {
     pub fn new() -> Self {
         Self {
@@ -36,15 +212,18 @@ impl GlyphCache {
         let scale = |x: i16| -> i16 { (x as f64 * zoom_level) as i16 };
         let flip_y = |y: i16| -> i16 { (y as f64 * zoom_level) as i16 };
 
+        // Round outwards so the box still covers the curves; the box is relative to the origin
+        let bounds = glyph.tight_bounding_box();
+        let origin_x = glyph.origin_x() as i16;
         let (min_x, max_x, min_y, max_y) = (
-            *glyph.x_coordinates.iter().min().unwrap_or(&0),
-            *glyph.x_coordinates.iter().max().unwrap_or(&0),
-            *glyph.y_coordinates.iter().min().unwrap_or(&0),
-            *glyph.y_coordinates.iter().max().unwrap_or(&0),
+            bounds.x_min.floor() as i16,
+            bounds.x_max.ceil() as i16,
+            bounds.y_min.floor() as i16,
+            bounds.y_max.ceil() as i16,
         );
 
         let scaled_points = glyph.processed_points.iter()
-            .map(|&(x, y)| (scale(x - min_x), flip_y(y - max_y)))
+            .map(|&(x, y)| (scale(x - origin_x), flip_y(y - max_y)))
             .collect();
 
         let bounding_box = (
@@ -65,3 +244,44 @@ impl GlyphCache {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 200 unit square at x = 100..300 whose hmtx bearing puts the origin at x = 60
+    fn offset_square() -> Glyph {
+        let x_coordinates = vec![100, 100, 300, 300];
+        let y_coordinates = vec![0, 200, 200, 0];
+        let flags = vec![1; 4];
+        let raw_end_pts_of_contours = vec![3];
+        let (processed_points, end_pts_of_contours) = process_points(&x_coordinates, &y_coordinates, &flags, &raw_end_pts_of_contours);
+        Glyph {
+            glyph_index: 1,
+            num_contours: 1,
+            xmin: 100,
+            ymin: 0,
+            xmax: 300,
+            ymax: 200,
+            end_pts_of_contours,
+            x_coordinates,
+            y_coordinates,
+            flags,
+            processed_points,
+            raw_end_pts_of_contours,
+            instructions: Vec::new(),
+            advance_width: 400.0,
+            left_side_bearing: 40,
+        }
+    }
+
+    #[test]
+    fn bounding_boxes_share_the_origin_frame_when_bearing_differs_from_xmin() {
+        let glyph = offset_square();
+        assert_eq!(glyph.origin_x(), 60);
+        let expected = BoundingBox { x_min: 40.0, y_min: 0.0, x_max: 240.0, y_max: 200.0 };
+        assert_eq!(glyph.header_bounding_box(), expected);
+        assert_eq!(glyph.control_box(), expected);
+        assert_eq!(glyph.tight_bounding_box(), expected);
+    }
+}
@@ -0,0 +1,623 @@
+use crate::buffer::ByteBuffer;
+use crate::gdef::{GdefTable, GlyphClass};
+use crate::opentype::{feature_lookup_indices, feature_lookups, read_class_def, read_coverage, read_feature_list, read_lookup_list, read_offset16, read_script_list, ClassDef, Coverage, Feature, FeatureSetting, Lookup, Script, RIGHT_TO_LEFT};
+
+// Lookup types, with extension subtables resolved to the type they wrap
+const SINGLE_ADJUSTMENT: u16 = 1;
+const PAIR_ADJUSTMENT: u16 = 2;
+const CURSIVE_ATTACHMENT: u16 = 3;
+const MARK_TO_BASE: u16 = 4;
+const MARK_TO_LIGATURE: u16 = 5;
+const MARK_TO_MARK: u16 = 6;
+const EXTENSION: u16 = 9;
+
+// Features applied to every run when positioning
+pub const DEFAULT_FEATURES: [[u8; 4]; 5] = [*b"kern", *b"mark", *b"mkmk", *b"curs", *b"dist"];
+
+// Adjustment to one glyph's placement and advance in font units. Device table corrections for
+// particular sizes are not applied.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ValueRecord {
+    pub x_placement: i16,
+    pub y_placement: i16,
+    pub x_advance: i16,
+    pub y_advance: i16,
+}
+
+// Where one glyph of a run ends up, in font units: the advance moves the pen for the next glyph
+// and the offset moves this glyph without affecting the pen
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GlyphPosition {
+    pub x_advance: i32,
+    pub y_advance: i32,
+    pub x_offset: i32,
+    pub y_offset: i32,
+}
+
+impl GlyphPosition {
+    pub fn adjust(&mut self, value: &ValueRecord) {
+        self.x_offset += value.x_placement as i32;
+        self.y_offset += value.y_placement as i32;
+        self.x_advance += value.x_advance as i32;
+        self.y_advance += value.y_advance as i32;
+    }
+}
+
+// Attachment point in font units. Format 2 anchors also name an outline point to use when hinted,
+// but the design coordinates are what's applied; format 3's device tables are ignored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Anchor {
+    pub x: i16,
+    pub y: i16,
+    pub contour_point: Option<u16>,
+}
+
+#[derive(Debug, Clone)]
+pub enum PairPos {
+    // Format 1: per covered first glyph, (second glyph, first value, second value) sorted by
+    // second glyph
+    Glyphs {
+        coverage: Coverage,
+        pair_sets: Vec<Vec<(u16, ValueRecord, ValueRecord)>>,
+        second_has_value: bool,
+    },
+    // Format 2: values for each (first class, second class) pair, row by row
+    Classes {
+        coverage: Coverage,
+        class_def1: ClassDef,
+        class_def2: ClassDef,
+        class2_count: u16,
+        records: Vec<(ValueRecord, ValueRecord)>,
+        second_has_value: bool,
+    },
+}
+
+impl PairPos {
+    fn values(&self, first: u16, second: u16) -> Option<(ValueRecord, ValueRecord)> {
+        match self {
+            PairPos::Glyphs { coverage, pair_sets, .. } => {
+                let pair_set = pair_sets.get(coverage.index(first)? as usize)?;
+                let found = pair_set.binary_search_by_key(&second, |&(glyph, _, _)| glyph).ok()?;
+                Some((pair_set[found].1, pair_set[found].2))
+            }
+            PairPos::Classes { coverage, class_def1, class_def2, class2_count, records, .. } => {
+                coverage.index(first)?;
+                let index = class_def1.class(first) as usize * *class2_count as usize + class_def2.class(second) as usize;
+                records.get(index).copied()
+            }
+        }
+    }
+
+    fn second_has_value(&self) -> bool {
+        match self {
+            PairPos::Glyphs { second_has_value, .. } | PairPos::Classes { second_has_value, .. } => *second_has_value,
+        }
+    }
+}
+
+// Mark-to-base and mark-to-mark: marks attach to the anchor of their class on the glyph before
+#[derive(Debug, Clone)]
+pub struct MarkAttachPos {
+    pub mark_coverage: Coverage,
+    pub base_coverage: Coverage,
+    // Per covered mark, its class and anchor
+    pub marks: Vec<(u16, Anchor)>,
+    // Per covered base, one anchor per mark class
+    pub base_anchors: Vec<Vec<Option<Anchor>>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MarkLigaturePos {
+    pub mark_coverage: Coverage,
+    pub ligature_coverage: Coverage,
+    pub marks: Vec<(u16, Anchor)>,
+    // Per covered ligature, per component, one anchor per mark class
+    pub ligature_anchors: Vec<Vec<Vec<Option<Anchor>>>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum PosSubtable {
+    Single { coverage: Coverage, values: Vec<ValueRecord> },
+    Pair(PairPos),
+    // Per covered glyph, its entry and exit anchors
+    Cursive { coverage: Coverage, entry_exits: Vec<(Option<Anchor>, Option<Anchor>)> },
+    MarkToBase(MarkAttachPos),
+    MarkToLigature(MarkLigaturePos),
+    MarkToMark(MarkAttachPos),
+    Unsupported(u16),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GposTable {
+    pub scripts: Vec<Script>,
+    pub features: Vec<Feature>,
+    pub lookups: Vec<Lookup<PosSubtable>>,
+}
+
+impl GposTable {
+    pub fn lookup_indices(&self, script_tag: &[u8; 4], language_tag: &[u8; 4], feature_tags: &[[u8; 4]]) -> Vec<u16> {
+        feature_lookup_indices(&self.scripts, &self.features, script_tag, language_tag, feature_tags)
+    }
+
+    // The lookups the settings enable for a script and language, each with its feature's value
+    pub fn lookups_for(&self, script_tag: &[u8; 4], language_tag: &[u8; 4], settings: &[FeatureSetting]) -> Vec<(u16, u16)> {
+        feature_lookups(&self.scripts, &self.features, script_tag, language_tag, settings)
+    }
+
+    // Applies lookups to a run of glyphs in logical order, adding to the positions the caller
+    // started from. Each lookup runs over the whole run before the next one starts, and attached
+    // glyphs are positioned relative to what they attach to once every lookup has run. Ligature
+    // components say which component of a ligature each mark goes on, where substitution knows.
+    pub fn apply_lookups(&self, glyphs: &[u16], ligature_components: &[Option<u16>], positions: &mut [GlyphPosition], gdef: Option<&GdefTable>, lookup_indices: &[u16], right_to_left: bool) {
+        let mut run = PositioningRun {
+            glyphs,
+            ligature_components,
+            positions,
+            attachments: vec![None; glyphs.len()],
+            gdef,
+            right_to_left,
+        };
+        for &lookup_index in lookup_indices {
+            if let Some(lookup) = self.lookups.get(lookup_index as usize) {
+                run.apply_lookup(lookup);
+            }
+        }
+        run.resolve_attachments();
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Attachment {
+    // Offset from the anchor on the glyph at this index, which comes earlier in the run
+    Mark(usize),
+    // Cross-stream offset from the glyph at this index
+    Cursive(usize),
+}
+
+struct PositioningRun<'a> {
+    glyphs: &'a [u16],
+    ligature_components: &'a [Option<u16>],
+    positions: &'a mut [GlyphPosition],
+    attachments: Vec<Option<Attachment>>,
+    gdef: Option<&'a GdefTable>,
+    right_to_left: bool,
+}
+
+impl PositioningRun<'_> {
+    fn apply_lookup(&mut self, lookup: &Lookup<PosSubtable>) {
+        let mut index = 0;
+        while index < self.glyphs.len() {
+            if lookup.skips(self.glyphs[index], self.gdef) {
+                index += 1;
+                continue;
+            }
+            // The first subtable that applies wins and says how far to move on
+            index += lookup.subtables.iter()
+                .find_map(|subtable| self.apply_subtable(lookup, subtable, index))
+                .unwrap_or(1);
+        }
+    }
+
+    fn is_mark(&self, glyph_index: u16, mark_coverage: &Coverage) -> bool {
+        match self.gdef {
+            Some(gdef) => gdef.glyph_class(glyph_index) == GlyphClass::Mark,
+            None => mark_coverage.contains(glyph_index),
+        }
+    }
+
+    // The nearest earlier glyph that isn't a mark, which is what marks attach to
+    fn preceding_base(&self, index: usize, mark_coverage: &Coverage) -> Option<usize> {
+        (0..index).rev().find(|&previous| !self.is_mark(self.glyphs[previous], mark_coverage))
+    }
+
+    fn attach_mark(&mut self, index: usize, parent: usize, mark_anchor: Anchor, parent_anchor: Anchor) {
+        let position = &mut self.positions[index];
+        position.x_offset = parent_anchor.x as i32 - mark_anchor.x as i32;
+        position.y_offset = parent_anchor.y as i32 - mark_anchor.y as i32;
+        self.attachments[index] = Some(Attachment::Mark(parent));
+    }
+
+    // Positions the glyph at `index` and returns how many glyphs were consumed, or None when
+    // the subtable doesn't apply there
+    fn apply_subtable(&mut self, lookup: &Lookup<PosSubtable>, subtable: &PosSubtable, index: usize) -> Option<usize> {
+        let glyph = self.glyphs[index];
+        match subtable {
+            PosSubtable::Single { coverage, values } => {
+                let coverage_index = coverage.index(glyph)? as usize;
+                // Format 1 shares one value between every covered glyph
+                let value = if values.len() == 1 { values[0] } else { *values.get(coverage_index)? };
+                self.positions[index].adjust(&value);
+                Some(1)
+            }
+            PosSubtable::Pair(pair_pos) => {
+                // The second glyph is the next one the lookup doesn't skip
+                let second = (index + 1..self.glyphs.len()).find(|&next| !lookup.skips(self.glyphs[next], self.gdef))?;
+                let (first_value, second_value) = pair_pos.values(glyph, self.glyphs[second])?;
+                self.positions[index].adjust(&first_value);
+                self.positions[second].adjust(&second_value);
+                // A second glyph that was positioned can't start the next pair
+                Some(if pair_pos.second_has_value() { second + 1 - index } else { second - index })
+            }
+            PosSubtable::Cursive { coverage, entry_exits } => {
+                // Joins this glyph's entry anchor to the exit anchor of the glyph before it
+                let entry = entry_exits.get(coverage.index(glyph)? as usize)?.0?;
+                let previous = (0..index).rev().find(|&previous| !lookup.skips(self.glyphs[previous], self.gdef))?;
+                let exit = entry_exits.get(coverage.index(self.glyphs[previous])? as usize)?.1?;
+                self.join_cursive(lookup, previous, exit, index, entry);
+                Some(1)
+            }
+            PosSubtable::MarkToBase(mark_pos) => {
+                let (mark_class, mark_anchor) = *mark_pos.marks.get(mark_pos.mark_coverage.index(glyph)? as usize)?;
+                let base = self.preceding_base(index, &mark_pos.mark_coverage)?;
+                let base_anchor = (*mark_pos.base_anchors
+                    .get(mark_pos.base_coverage.index(self.glyphs[base])? as usize)?
+                    .get(mark_class as usize)?)?;
+                self.attach_mark(index, base, mark_anchor, base_anchor);
+                Some(1)
+            }
+            PosSubtable::MarkToLigature(mark_pos) => {
+                let (mark_class, mark_anchor) = *mark_pos.marks.get(mark_pos.mark_coverage.index(glyph)? as usize)?;
+                let ligature = self.preceding_base(index, &mark_pos.mark_coverage)?;
+                let components = mark_pos.ligature_anchors.get(mark_pos.ligature_coverage.index(self.glyphs[ligature])? as usize)?;
+                // Marks that came after the whole ligature go on its last component
+                let component = self.ligature_components.get(index).copied().flatten()
+                    .and_then(|component| components.get(component as usize))
+                    .or(components.last())?;
+                let ligature_anchor = (*component.get(mark_class as usize)?)?;
+                self.attach_mark(index, ligature, mark_anchor, ligature_anchor);
+                Some(1)
+            }
+            PosSubtable::MarkToMark(mark_pos) => {
+                let (mark_class, mark_anchor) = *mark_pos.marks.get(mark_pos.mark_coverage.index(glyph)? as usize)?;
+                let previous = (0..index).rev().find(|&previous| !lookup.skips(self.glyphs[previous], self.gdef))?;
+                if !self.is_mark(self.glyphs[previous], &mark_pos.base_coverage) {
+                    return None;
+                }
+                let mark2_anchor = (*mark_pos.base_anchors
+                    .get(mark_pos.base_coverage.index(self.glyphs[previous])? as usize)?
+                    .get(mark_class as usize)?)?;
+                self.attach_mark(index, previous, mark_anchor, mark2_anchor);
+                Some(1)
+            }
+            PosSubtable::Unsupported(_) => None,
+        }
+    }
+
+    // Moves the glyphs along the writing direction so the exit anchor of the glyph at `first`
+    // meets the entry anchor of the one at `second`, then attaches one to the other across it.
+    // With the lookup's right-to-left flag the first glyph is the one that moves.
+    fn join_cursive(&mut self, lookup: &Lookup<PosSubtable>, first: usize, exit: Anchor, second: usize, entry: Anchor) {
+        if self.right_to_left {
+            let shift = exit.x as i32 + self.positions[first].x_offset;
+            self.positions[first].x_advance -= shift;
+            self.positions[first].x_offset -= shift;
+            self.positions[second].x_advance = entry.x as i32 + self.positions[second].x_offset;
+        } else {
+            self.positions[first].x_advance = exit.x as i32 + self.positions[first].x_offset;
+            let shift = entry.x as i32 + self.positions[second].x_offset;
+            self.positions[second].x_advance -= shift;
+            self.positions[second].x_offset -= shift;
+        }
+
+        let (child, parent, y_offset) = if lookup.lookup_flag & RIGHT_TO_LEFT != 0 {
+            (first, second, entry.y as i32 - exit.y as i32)
+        } else {
+            (second, first, exit.y as i32 - entry.y as i32)
+        };
+        // A glyph can only hang off one other, so break a chain that would loop back
+        if let Some(Attachment::Cursive(grandparent)) = self.attachments[parent] {
+            if grandparent == child {
+                self.attachments[parent] = None;
+                self.positions[parent].y_offset = 0;
+            }
+        }
+        self.positions[child].y_offset = y_offset;
+        self.attachments[child] = Some(Attachment::Cursive(parent));
+    }
+
+    fn resolve_attachments(&mut self) {
+        let mut resolved = vec![false; self.glyphs.len()];
+        for index in 0..self.glyphs.len() {
+            self.resolve_attachment(index, &mut resolved);
+        }
+    }
+
+    // Adds the position of what a glyph is attached to, resolving that first
+    fn resolve_attachment(&mut self, index: usize, resolved: &mut [bool]) {
+        if resolved[index] {
+            return;
+        }
+        resolved[index] = true;
+        match self.attachments[index] {
+            Some(Attachment::Cursive(parent)) => {
+                self.resolve_attachment(parent, resolved);
+                self.positions[index].y_offset += self.positions[parent].y_offset;
+            }
+            Some(Attachment::Mark(parent)) => {
+                self.resolve_attachment(parent, resolved);
+                let parent_position = self.positions[parent];
+                // The mark's pen position is past the advances between it and its parent
+                let advances: i32 = if self.right_to_left {
+                    -self.positions[parent + 1..=index].iter().map(|position| position.x_advance).sum::<i32>()
+                } else {
+                    self.positions[parent..index].iter().map(|position| position.x_advance).sum()
+                };
+                let position = &mut self.positions[index];
+                position.x_offset += parent_position.x_offset - advances;
+                position.y_offset += parent_position.y_offset;
+            }
+            None => {}
+        }
+    }
+}
+
+fn read_value_record(buffer: &mut ByteBuffer, value_format: u16) -> ValueRecord {
+    let mut value = ValueRecord::default();
+    if value_format & 0x0001 != 0 {
+        value.x_placement = buffer.read_i16();
+    }
+    if value_format & 0x0002 != 0 {
+        value.y_placement = buffer.read_i16();
+    }
+    if value_format & 0x0004 != 0 {
+        value.x_advance = buffer.read_i16();
+    }
+    if value_format & 0x0008 != 0 {
+        value.y_advance = buffer.read_i16();
+    }
+    // Skip the device table offsets
+    buffer.skip_bytes(2 * (value_format & 0x00F0).count_ones() as usize);
+    value
+}
+
+fn read_single_pos(buffer: &mut ByteBuffer, subtable_start: usize) -> PosSubtable {
+    buffer.set_position(subtable_start);
+    let format = buffer.read_u16();
+    let coverage = read_offset16(buffer, subtable_start);
+    let value_format = buffer.read_u16();
+    let values = match format {
+        1 => vec![read_value_record(buffer, value_format)],
+        2 => {
+            let value_count = buffer.read_u16();
+            (0..value_count).map(|_| read_value_record(buffer, value_format)).collect()
+        }
+        _ => return PosSubtable::Unsupported(SINGLE_ADJUSTMENT),
+    };
+    match coverage {
+        Some(position) => PosSubtable::Single { coverage: read_coverage(buffer, position), values },
+        None => PosSubtable::Unsupported(SINGLE_ADJUSTMENT),
+    }
+}
+
+fn read_pair_pos(buffer: &mut ByteBuffer, subtable_start: usize) -> PosSubtable {
+    buffer.set_position(subtable_start);
+    let format = buffer.read_u16();
+    let coverage = read_offset16(buffer, subtable_start);
+    let value_format1 = buffer.read_u16();
+    let value_format2 = buffer.read_u16();
+    let coverage = match coverage {
+        Some(position) => position,
+        None => return PosSubtable::Unsupported(PAIR_ADJUSTMENT),
+    };
+
+    let pair_pos = match format {
+        1 => {
+            let pair_set_count = buffer.read_u16();
+            let pair_set_starts: Vec<usize> = (0..pair_set_count).map(|_| subtable_start + buffer.read_u16() as usize).collect();
+            let pair_sets = pair_set_starts.into_iter()
+                .map(|pair_set_start| {
+                    buffer.set_position(pair_set_start);
+                    let pair_value_count = buffer.read_u16();
+                    let mut pair_set: Vec<(u16, ValueRecord, ValueRecord)> = (0..pair_value_count)
+                        .map(|_| (buffer.read_u16(), read_value_record(buffer, value_format1), read_value_record(buffer, value_format2)))
+                        .collect();
+                    pair_set.sort_by_key(|&(glyph, _, _)| glyph);
+                    pair_set
+                })
+                .collect();
+            PairPos::Glyphs {
+                coverage: read_coverage(buffer, coverage),
+                pair_sets,
+                second_has_value: value_format2 != 0,
+            }
+        }
+        2 => {
+            let class_def1 = read_offset16(buffer, subtable_start);
+            let class_def2 = read_offset16(buffer, subtable_start);
+            let class1_count = buffer.read_u16();
+            let class2_count = buffer.read_u16();
+            let records = (0..class1_count as usize * class2_count as usize)
+                .map(|_| (read_value_record(buffer, value_format1), read_value_record(buffer, value_format2)))
+                .collect();
+            PairPos::Classes {
+                coverage: read_coverage(buffer, coverage),
+                class_def1: class_def1.map(|position| read_class_def(buffer, position)).unwrap_or_default(),
+                class_def2: class_def2.map(|position| read_class_def(buffer, position)).unwrap_or_default(),
+                class2_count,
+                records,
+                second_has_value: value_format2 != 0,
+            }
+        }
+        _ => return PosSubtable::Unsupported(PAIR_ADJUSTMENT),
+    };
+    PosSubtable::Pair(pair_pos)
+}
+
+fn read_anchor(buffer: &mut ByteBuffer, position: usize) -> Anchor {
+    buffer.set_position(position);
+    let format = buffer.read_u16();
+    let x = buffer.read_i16();
+    let y = buffer.read_i16();
+    Anchor {
+        x,
+        y,
+        contour_point: if format == 2 { Some(buffer.read_u16()) } else { None },
+    }
+}
+
+// Reads `count` records of `anchor_count` anchor offsets relative to `base`; null offsets mean
+// the record has no anchor for that class
+fn read_anchor_records(buffer: &mut ByteBuffer, base: usize, count: usize, anchor_count: usize) -> Vec<Vec<Option<Anchor>>> {
+    let offsets: Vec<Vec<Option<usize>>> = (0..count)
+        .map(|_| (0..anchor_count).map(|_| read_offset16(buffer, base)).collect())
+        .collect();
+    offsets.into_iter()
+        .map(|record| record.into_iter().map(|offset| offset.map(|position| read_anchor(buffer, position))).collect())
+        .collect()
+}
+
+fn read_mark_array(buffer: &mut ByteBuffer, position: usize) -> Vec<(u16, Anchor)> {
+    buffer.set_position(position);
+    let mark_count = buffer.read_u16();
+    let records: Vec<(u16, usize)> = (0..mark_count)
+        .map(|_| (buffer.read_u16(), position + buffer.read_u16() as usize))
+        .collect();
+    records.into_iter()
+        .map(|(mark_class, anchor)| (mark_class, read_anchor(buffer, anchor)))
+        .collect()
+}
+
+fn read_cursive_pos(buffer: &mut ByteBuffer, subtable_start: usize) -> PosSubtable {
+    buffer.set_position(subtable_start);
+    let format = buffer.read_u16();
+    let coverage = read_offset16(buffer, subtable_start);
+    let (1, Some(coverage)) = (format, coverage) else {
+        return PosSubtable::Unsupported(CURSIVE_ATTACHMENT);
+    };
+    let entry_exit_count = buffer.read_u16();
+    let entry_exits = read_anchor_records(buffer, subtable_start, entry_exit_count as usize, 2);
+    PosSubtable::Cursive {
+        coverage: read_coverage(buffer, coverage),
+        entry_exits: entry_exits.into_iter().map(|record| (record[0], record[1])).collect(),
+    }
+}
+
+// Mark-to-base, mark-to-ligature and mark-to-mark share their layout up to the second array
+fn read_mark_attach_pos(buffer: &mut ByteBuffer, lookup_type: u16, subtable_start: usize) -> PosSubtable {
+    buffer.set_position(subtable_start);
+    let format = buffer.read_u16();
+    let mark_coverage = read_offset16(buffer, subtable_start);
+    let base_coverage = read_offset16(buffer, subtable_start);
+    let mark_class_count = buffer.read_u16() as usize;
+    let mark_array = read_offset16(buffer, subtable_start);
+    let base_array = read_offset16(buffer, subtable_start);
+    let (1, Some(mark_coverage), Some(base_coverage), Some(mark_array), Some(base_array)) = (format, mark_coverage, base_coverage, mark_array, base_array) else {
+        return PosSubtable::Unsupported(lookup_type);
+    };
+
+    let marks = read_mark_array(buffer, mark_array);
+    buffer.set_position(base_array);
+    let base_count = buffer.read_u16() as usize;
+    if lookup_type == MARK_TO_LIGATURE {
+        let ligature_attaches: Vec<usize> = (0..base_count).map(|_| base_array + buffer.read_u16() as usize).collect();
+        let ligature_anchors = ligature_attaches.into_iter()
+            .map(|ligature_attach| {
+                buffer.set_position(ligature_attach);
+                let component_count = buffer.read_u16() as usize;
+                read_anchor_records(buffer, ligature_attach, component_count, mark_class_count)
+            })
+            .collect();
+        return PosSubtable::MarkToLigature(MarkLigaturePos {
+            mark_coverage: read_coverage(buffer, mark_coverage),
+            ligature_coverage: read_coverage(buffer, base_coverage),
+            marks,
+            ligature_anchors,
+        });
+    }
+
+    let mark_pos = MarkAttachPos {
+        base_anchors: read_anchor_records(buffer, base_array, base_count, mark_class_count),
+        mark_coverage: read_coverage(buffer, mark_coverage),
+        base_coverage: read_coverage(buffer, base_coverage),
+        marks,
+    };
+    match lookup_type {
+        MARK_TO_BASE => PosSubtable::MarkToBase(mark_pos),
+        _ => PosSubtable::MarkToMark(mark_pos),
+    }
+}
+
+fn read_subtable(buffer: &mut ByteBuffer, lookup_type: u16, subtable_start: usize) -> PosSubtable {
+    match lookup_type {
+        SINGLE_ADJUSTMENT => read_single_pos(buffer, subtable_start),
+        PAIR_ADJUSTMENT => read_pair_pos(buffer, subtable_start),
+        CURSIVE_ATTACHMENT => read_cursive_pos(buffer, subtable_start),
+        MARK_TO_BASE | MARK_TO_LIGATURE | MARK_TO_MARK => read_mark_attach_pos(buffer, lookup_type, subtable_start),
+        EXTENSION => {
+            buffer.set_position(subtable_start);
+            buffer.skip_bytes(2); // Skip format
+            let extension_lookup_type = buffer.read_u16();
+            let extension_offset = buffer.read_u32() as usize;
+            match extension_lookup_type {
+                EXTENSION => PosSubtable::Unsupported(EXTENSION),
+                lookup_type => read_subtable(buffer, lookup_type, subtable_start + extension_offset),
+            }
+        }
+        lookup_type => PosSubtable::Unsupported(lookup_type),
+    }
+}
+
+// Reads GPOS with the buffer positioned at the start of the table
+pub fn read_gpos(buffer: &mut ByteBuffer) -> GposTable {
+    let table_start = buffer.current_position();
+    let major_version = buffer.read_u16();
+    buffer.skip_bytes(2); // Skip minor version; 1.1 only adds feature variations
+    if major_version != 1 {
+        return GposTable::default();
+    }
+    let script_list = read_offset16(buffer, table_start);
+    let feature_list = read_offset16(buffer, table_start);
+    let lookup_list = read_offset16(buffer, table_start);
+
+    GposTable {
+        scripts: script_list.map(|position| read_script_list(buffer, position)).unwrap_or_default(),
+        features: feature_list.map(|position| read_feature_list(buffer, position)).unwrap_or_default(),
+        lookups: lookup_list.map(|position| read_lookup_list(buffer, position, read_subtable)).unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opentype::ClassDef;
+
+    const MARK: u16 = 3;
+    const LIGATURE: u16 = 4;
+
+    fn lookup<T>(lookup_flag: u16, subtable: T) -> Lookup<T> {
+        Lookup { lookup_type: 0, lookup_flag, mark_filtering_set: None, subtables: vec![subtable] }
+    }
+
+    fn anchor(x: i16, y: i16) -> Anchor {
+        Anchor { x, y, contour_point: None }
+    }
+
+    #[test]
+    fn marks_go_on_the_ligature_component_they_belong_to() {
+        let gdef = GdefTable {
+            glyph_class_def: ClassDef::Array { start_glyph: MARK, classes: vec![3, 2] },
+            ..GdefTable::default()
+        };
+        let gpos = GposTable {
+            lookups: vec![lookup(0, PosSubtable::MarkToLigature(MarkLigaturePos {
+                mark_coverage: Coverage::Glyphs(vec![MARK]),
+                ligature_coverage: Coverage::Glyphs(vec![LIGATURE]),
+                marks: vec![(0, anchor(50, 0))],
+                ligature_anchors: vec![vec![vec![Some(anchor(250, 700))], vec![Some(anchor(750, 700))]]],
+            }))],
+            ..GposTable::default()
+        };
+
+        // The first mark was typed after the ligature's first component, the second after all of it
+        let glyphs = [LIGATURE, MARK, MARK];
+        let mut positions = vec![GlyphPosition::default(); glyphs.len()];
+        gpos.apply_lookups(&glyphs, &[None, Some(0), None], &mut positions, Some(&gdef), &[0], false);
+        assert_eq!((positions[1].x_offset, positions[1].y_offset), (200, 700));
+        assert_eq!((positions[2].x_offset, positions[2].y_offset), (700, 700));
+
+        // Without component information every mark goes on the last component
+        let mut positions = vec![GlyphPosition::default(); glyphs.len()];
+        gpos.apply_lookups(&glyphs, &[None; 3], &mut positions, Some(&gdef), &[0], false);
+        assert_eq!((positions[1].x_offset, positions[1].y_offset), (700, 700));
+    }
+}
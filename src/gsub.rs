@@ -0,0 +1,659 @@
+use crate::buffer::ByteBuffer;
+use crate::gdef::GdefTable;
+use crate::opentype::{feature_lookups, match_sequence, read_coverage, read_coverages, read_feature_list, read_lookup_list, read_offset16, read_script_list, read_sequence_context, Coverage, Feature, FeatureSetting, Lookup, Script, SequenceContext, SequenceLookup};
+
+// Lookup types, with extension subtables resolved to the type they wrap
+const SINGLE: u16 = 1;
+const MULTIPLE: u16 = 2;
+const ALTERNATE: u16 = 3;
+const LIGATURE: u16 = 4;
+const CONTEXT: u16 = 5;
+const CHAINED_CONTEXT: u16 = 6;
+const EXTENSION: u16 = 7;
+const REVERSE_CHAINED_SINGLE: u16 = 8;
+
+// How deep contextual lookups may call each other before the rest are ignored
+const MAX_NESTING_LEVEL: usize = 6;
+
+// Features applied to every run unless turned off
+pub const DEFAULT_FEATURES: [[u8; 4]; 6] = [*b"ccmp", *b"locl", *b"rlig", *b"liga", *b"clig", *b"calt"];
+
+// One glyph of a run being substituted. The cluster is the index of the first input character it
+// came from, shared by every glyph made from the same characters. A mark that was skipped between
+// the components of a ligature records which component it came after, counting from 0, so GPOS
+// can put it on that one. Properties are for script shapers to track glyphs by; a glyph a
+// substitution produces keeps those of the glyph (or first ligature component) it replaced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GlyphInfo {
+    pub glyph_index: u16,
+    pub cluster: usize,
+    pub ligature_component: Option<u16>,
+    pub properties: u32,
+}
+
+#[derive(Debug, Clone)]
+pub enum SubstSubtable {
+    // Single format 1: covered glyphs move by a fixed delta, modulo 65536
+    SingleDelta { coverage: Coverage, delta: i16 },
+    // Single format 2: one substitute per covered glyph
+    Single { coverage: Coverage, substitutes: Vec<u16> },
+    // Per covered glyph, the sequence that replaces it
+    Multiple { coverage: Coverage, sequences: Vec<Vec<u16>> },
+    // Per covered glyph, the alternates a feature value picks from, starting at 1
+    Alternate { coverage: Coverage, alternate_sets: Vec<Vec<u16>> },
+    // Per covered first glyph, (ligature, remaining components) in order of preference
+    Ligature { coverage: Coverage, ligature_sets: Vec<Vec<(u16, Vec<u16>)>> },
+    // Contextual and chained contextual, applying other lookups to the matched input
+    Context(SequenceContext),
+    // Covered glyphs in the right context get one substitute each, working from the end of the run
+    ReverseChainSingle { coverage: Coverage, backtrack: Vec<Coverage>, lookahead: Vec<Coverage>, substitutes: Vec<u16> },
+    Unsupported(u16),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GsubTable {
+    pub scripts: Vec<Script>,
+    pub features: Vec<Feature>,
+    pub lookups: Vec<Lookup<SubstSubtable>>,
+}
+
+impl GsubTable {
+    // The lookups the settings enable for a script and language, each with its feature's value
+    pub fn lookups_for(&self, script_tag: &[u8; 4], language_tag: &[u8; 4], settings: &[FeatureSetting]) -> Vec<(u16, u16)> {
+        feature_lookups(&self.scripts, &self.features, script_tag, language_tag, settings)
+    }
+
+    // Substitutes a run of glyphs in logical order with the features in `settings`
+    pub fn substitute(&self, glyphs: &mut Vec<GlyphInfo>, gdef: Option<&GdefTable>, script_tag: &[u8; 4], language_tag: &[u8; 4], settings: &[FeatureSetting]) {
+        let lookups = self.lookups_for(script_tag, language_tag, settings);
+        self.apply_lookups(glyphs, gdef, &lookups);
+    }
+
+    // Applies (lookup index, feature value) pairs in order, each over the whole run
+    pub fn apply_lookups(&self, glyphs: &mut Vec<GlyphInfo>, gdef: Option<&GdefTable>, lookups: &[(u16, u16)]) {
+        self.apply_lookups_to(glyphs, gdef, lookups, &|_| true);
+    }
+
+    // Applies lookups like apply_lookups, but only starting at glyphs `applies_to` accepts.
+    // Context and skipped glyphs are still seen, so a lookup can match across the others.
+    pub fn apply_lookups_to(&self, glyphs: &mut Vec<GlyphInfo>, gdef: Option<&GdefTable>, lookups: &[(u16, u16)], applies_to: &dyn Fn(&GlyphInfo) -> bool) {
+        let mut run = SubstitutionRun { glyphs, gdef, lookups: &self.lookups, nesting: 0 };
+        for &(lookup_index, value) in lookups {
+            if let Some(lookup) = self.lookups.get(lookup_index as usize) {
+                run.apply_lookup(lookup, value, applies_to);
+            }
+        }
+    }
+}
+
+// Substitution in stages for scripts whose features must apply in a set order, some only to
+// particular glyphs. Each stage runs its features' lookups to completion before the next, and
+// whatever is enabled but in no stage runs last.
+pub struct SubstitutionPlan<'a> {
+    gsub: &'a GsubTable,
+    gdef: Option<&'a GdefTable>,
+    script_tag: [u8; 4],
+    language_tag: [u8; 4],
+    settings: Vec<FeatureSetting>,
+    // Features already staged, and the required feature's lookups once they've run
+    staged_tags: Vec<[u8; 4]>,
+    required_lookups: Option<Vec<(u16, u16)>>,
+}
+
+impl<'a> SubstitutionPlan<'a> {
+    pub fn new(gsub: &'a GsubTable, gdef: Option<&'a GdefTable>, script_tag: &[u8; 4], language_tag: &[u8; 4], settings: &[FeatureSetting]) -> Self {
+        SubstitutionPlan {
+            gsub,
+            gdef,
+            script_tag: *script_tag,
+            language_tag: *language_tag,
+            settings: settings.to_vec(),
+            staged_tags: Vec::new(),
+            required_lookups: None,
+        }
+    }
+
+    pub fn is_enabled(&self, tag: &[u8; 4]) -> bool {
+        self.settings.iter().any(|setting| &setting.tag == tag)
+    }
+
+    // Whether the enabled feature `tag` on its own would change a sequence of glyphs
+    pub fn would_substitute(&self, tag: &[u8; 4], glyph_indices: &[u16]) -> bool {
+        let settings: Vec<FeatureSetting> = self.settings.iter().filter(|setting| &setting.tag == tag).copied().collect();
+        if settings.is_empty() {
+            return false;
+        }
+        let required = self.gsub.lookups_for(&self.script_tag, &self.language_tag, &[]);
+        let mut lookups = self.gsub.lookups_for(&self.script_tag, &self.language_tag, &settings);
+        lookups.retain(|lookup| !required.contains(lookup));
+
+        let mut glyphs: Vec<GlyphInfo> = glyph_indices.iter()
+            .enumerate()
+            .map(|(cluster, &glyph_index)| GlyphInfo { glyph_index, cluster, ..GlyphInfo::default() })
+            .collect();
+        self.gsub.apply_lookups(&mut glyphs, self.gdef, &lookups);
+        glyphs.len() != glyph_indices.len() || glyphs.iter().zip(glyph_indices).any(|(info, &glyph_index)| info.glyph_index != glyph_index)
+    }
+
+    // Applies the enabled features among `tags` to the whole run
+    pub fn apply_stage(&mut self, glyphs: &mut Vec<GlyphInfo>, tags: &[[u8; 4]]) {
+        self.apply_stage_to(glyphs, tags, &|_| true);
+    }
+
+    // Applies the enabled features among `tags`, starting only at glyphs `applies_to` accepts
+    pub fn apply_stage_to(&mut self, glyphs: &mut Vec<GlyphInfo>, tags: &[[u8; 4]], applies_to: &dyn Fn(&GlyphInfo) -> bool) {
+        let settings: Vec<FeatureSetting> = self.settings.iter().filter(|setting| tags.contains(&setting.tag)).copied().collect();
+        self.staged_tags.extend_from_slice(tags);
+        self.apply(glyphs, &settings, applies_to);
+    }
+
+    // Applies every enabled feature no stage has covered
+    pub fn apply_remaining(&mut self, glyphs: &mut Vec<GlyphInfo>) {
+        let settings: Vec<FeatureSetting> = self.settings.iter().filter(|setting| !self.staged_tags.contains(&setting.tag)).copied().collect();
+        self.apply(glyphs, &settings, &|_| true);
+    }
+
+    fn apply(&mut self, glyphs: &mut Vec<GlyphInfo>, settings: &[FeatureSetting], applies_to: &dyn Fn(&GlyphInfo) -> bool) {
+        let mut lookups = self.gsub.lookups_for(&self.script_tag, &self.language_tag, settings);
+        // The required feature comes with every set of lookups but only runs in the first stage
+        match &self.required_lookups {
+            Some(required) => lookups.retain(|lookup| !required.contains(lookup)),
+            None => self.required_lookups = Some(self.gsub.lookups_for(&self.script_tag, &self.language_tag, &[])),
+        }
+        self.gsub.apply_lookups_to(glyphs, self.gdef, &lookups, applies_to);
+    }
+}
+
+struct SubstitutionRun<'a> {
+    glyphs: &'a mut Vec<GlyphInfo>,
+    gdef: Option<&'a GdefTable>,
+    // The whole lookup list, for contextual lookups to call into
+    lookups: &'a [Lookup<SubstSubtable>],
+    nesting: usize,
+}
+
+impl SubstitutionRun<'_> {
+    fn apply_lookup(&mut self, lookup: &Lookup<SubstSubtable>, value: u16, applies_to: &dyn Fn(&GlyphInfo) -> bool) {
+        if lookup.subtables.iter().any(|subtable| matches!(subtable, SubstSubtable::ReverseChainSingle { .. })) {
+            for index in (0..self.glyphs.len()).rev() {
+                if applies_to(&self.glyphs[index]) && !lookup.skips(self.glyphs[index].glyph_index, self.gdef) {
+                    self.apply_lookup_at(lookup, value, index);
+                }
+            }
+            return;
+        }
+
+        let mut index = 0;
+        while index < self.glyphs.len() {
+            if !applies_to(&self.glyphs[index]) || lookup.skips(self.glyphs[index].glyph_index, self.gdef) {
+                index += 1;
+                continue;
+            }
+            index = self.apply_lookup_at(lookup, value, index).unwrap_or(index + 1);
+        }
+    }
+
+    // Tries each subtable at `index` until one applies, returning where to continue from
+    fn apply_lookup_at(&mut self, lookup: &Lookup<SubstSubtable>, value: u16, index: usize) -> Option<usize> {
+        lookup.subtables.iter().find_map(|subtable| self.apply_subtable(lookup, subtable, value, index))
+    }
+
+    // Positions of the next `count` glyphs after `index` that the lookup doesn't skip, provided
+    // each satisfies `matches` along with its place in the sequence
+    fn match_forward(&self, lookup: &Lookup<SubstSubtable>, index: usize, count: usize, matches: impl Fn(usize, u16) -> bool) -> Option<Vec<usize>> {
+        let mut positions = Vec::with_capacity(count);
+        let mut next = index + 1;
+        while positions.len() < count {
+            let position = (next..self.glyphs.len()).find(|&position| !lookup.skips(self.glyphs[position].glyph_index, self.gdef))?;
+            if !matches(positions.len(), self.glyphs[position].glyph_index) {
+                return None;
+            }
+            positions.push(position);
+            next = position + 1;
+        }
+        Some(positions)
+    }
+
+    // Applies a matched context's lookups at their input positions, in order, and returns the
+    // position after the input. Positions after one that grew or shrank the run move with it.
+    fn apply_nested_lookups(&mut self, mut positions: Vec<usize>, lookups: &[SequenceLookup], value: u16) -> usize {
+        let mut end = positions.last().map_or(0, |&last| last + 1);
+        if self.nesting >= MAX_NESTING_LEVEL {
+            return end;
+        }
+        self.nesting += 1;
+        let all_lookups = self.lookups;
+        for record in lookups {
+            let (Some(&position), Some(lookup)) = (positions.get(record.sequence_index as usize), all_lookups.get(record.lookup_index as usize)) else {
+                continue;
+            };
+            if position >= self.glyphs.len() || lookup.skips(self.glyphs[position].glyph_index, self.gdef) {
+                continue;
+            }
+            let len = self.glyphs.len();
+            if self.apply_lookup_at(lookup, value, position).is_none() {
+                continue;
+            }
+            let delta = self.glyphs.len() as isize - len as isize;
+            for later in positions.iter_mut().chain(std::iter::once(&mut end)).filter(|later| **later > position) {
+                *later = (*later as isize + delta).max(position as isize + 1) as usize;
+            }
+        }
+        self.nesting -= 1;
+        end.min(self.glyphs.len())
+    }
+
+    fn apply_subtable(&mut self, lookup: &Lookup<SubstSubtable>, subtable: &SubstSubtable, value: u16, index: usize) -> Option<usize> {
+        let glyph = self.glyphs[index].glyph_index;
+        match subtable {
+            SubstSubtable::SingleDelta { coverage, delta } => {
+                coverage.index(glyph)?;
+                self.glyphs[index].glyph_index = glyph.wrapping_add(*delta as u16);
+                Some(index + 1)
+            }
+            SubstSubtable::Single { coverage, substitutes } => {
+                self.glyphs[index].glyph_index = *substitutes.get(coverage.index(glyph)? as usize)?;
+                Some(index + 1)
+            }
+            SubstSubtable::Multiple { coverage, sequences } => {
+                let sequence = sequences.get(coverage.index(glyph)? as usize)?;
+                let replaced = self.glyphs[index];
+                self.glyphs.splice(index..=index, sequence.iter().map(|&glyph_index| GlyphInfo { glyph_index, ..replaced }));
+                Some(index + sequence.len())
+            }
+            SubstSubtable::Alternate { coverage, alternate_sets } => {
+                let alternates = alternate_sets.get(coverage.index(glyph)? as usize)?;
+                self.glyphs[index].glyph_index = *alternates.get(value.checked_sub(1)? as usize)?;
+                Some(index + 1)
+            }
+            SubstSubtable::Ligature { coverage, ligature_sets } => {
+                let ligature_set = ligature_sets.get(coverage.index(glyph)? as usize)?;
+                let (ligature, positions) = ligature_set.iter().find_map(|(ligature, components)| {
+                    let positions = self.match_forward(lookup, index, components.len(), |position, glyph_index| components[position] == glyph_index)?;
+                    Some((*ligature, positions))
+                })?;
+
+                // Everything from the first component to the last joins one cluster; skipped
+                // glyphs in between stay and end up after the ligature
+                let last = positions.last().copied().unwrap_or(index);
+                let cluster = self.glyphs[index..=last].iter().map(|info| info.cluster).min().unwrap_or(self.glyphs[index].cluster);
+                for info in &mut self.glyphs[index..=last] {
+                    info.cluster = cluster;
+                }
+                for skipped in index + 1..last {
+                    if !positions.contains(&skipped) {
+                        let component = positions.iter().filter(|&&position| position < skipped).count();
+                        self.glyphs[skipped].ligature_component = Some(component as u16);
+                    }
+                }
+                self.glyphs[index].glyph_index = ligature;
+                for &position in positions.iter().rev() {
+                    self.glyphs.remove(position);
+                }
+                Some(index + 1)
+            }
+            SubstSubtable::Context(context) => {
+                let glyphs = &*self.glyphs;
+                let gdef = self.gdef;
+                let (positions, lookups) = context.match_at(
+                    glyphs.len(),
+                    index,
+                    &|position| glyphs[position].glyph_index,
+                    &|position| lookup.skips(glyphs[position].glyph_index, gdef),
+                )?;
+                Some(self.apply_nested_lookups(positions, lookups, value).max(index + 1))
+            }
+            SubstSubtable::ReverseChainSingle { coverage, backtrack, lookahead, substitutes } => {
+                let substitute = *substitutes.get(coverage.index(glyph)? as usize)?;
+                let glyphs = &*self.glyphs;
+                let skips = |position: usize| lookup.skips(glyphs[position].glyph_index, self.gdef);
+                match_sequence(glyphs.len(), index, backtrack.len(), false, &skips, |i, position| backtrack[i].contains(glyphs[position].glyph_index))?;
+                match_sequence(glyphs.len(), index, lookahead.len(), true, &skips, |i, position| lookahead[i].contains(glyphs[position].glyph_index))?;
+                self.glyphs[index].glyph_index = substitute;
+                Some(index + 1)
+            }
+            SubstSubtable::Unsupported(_) => None,
+        }
+    }
+}
+
+// Reads `count` offsets relative to `base` followed by the glyph array at each
+fn read_glyph_arrays(buffer: &mut ByteBuffer, base: usize, count: u16) -> Vec<Vec<u16>> {
+    let starts: Vec<usize> = (0..count).map(|_| base + buffer.read_u16() as usize).collect();
+    starts.into_iter()
+        .map(|start| {
+            buffer.set_position(start);
+            let glyph_count = buffer.read_u16();
+            buffer.read_array::<u16>(glyph_count as usize)
+        })
+        .collect()
+}
+
+fn read_ligature_sets(buffer: &mut ByteBuffer, subtable_start: usize, count: u16) -> Vec<Vec<(u16, Vec<u16>)>> {
+    let set_starts: Vec<usize> = (0..count).map(|_| subtable_start + buffer.read_u16() as usize).collect();
+    set_starts.into_iter()
+        .map(|set_start| {
+            buffer.set_position(set_start);
+            let ligature_count = buffer.read_u16();
+            let ligature_starts: Vec<usize> = (0..ligature_count).map(|_| set_start + buffer.read_u16() as usize).collect();
+            ligature_starts.into_iter()
+                .map(|ligature_start| {
+                    buffer.set_position(ligature_start);
+                    let ligature = buffer.read_u16();
+                    let component_count = buffer.read_u16();
+                    // The first component is the covered glyph itself
+                    (ligature, buffer.read_array::<u16>(component_count.saturating_sub(1) as usize))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn read_subtable(buffer: &mut ByteBuffer, lookup_type: u16, subtable_start: usize) -> SubstSubtable {
+    buffer.set_position(subtable_start);
+    let format = buffer.read_u16();
+    if lookup_type == EXTENSION {
+        let extension_lookup_type = buffer.read_u16();
+        let extension_offset = buffer.read_u32() as usize;
+        return match extension_lookup_type {
+            EXTENSION => SubstSubtable::Unsupported(EXTENSION),
+            lookup_type => read_subtable(buffer, lookup_type, subtable_start + extension_offset),
+        };
+    }
+
+    if lookup_type == CONTEXT || lookup_type == CHAINED_CONTEXT {
+        return read_sequence_context(buffer, subtable_start, lookup_type == CHAINED_CONTEXT)
+            .map_or(SubstSubtable::Unsupported(lookup_type), SubstSubtable::Context);
+    }
+
+    let coverage = match read_offset16(buffer, subtable_start) {
+        Some(position) => position,
+        None => return SubstSubtable::Unsupported(lookup_type),
+    };
+    match (lookup_type, format) {
+        (SINGLE, 1) => SubstSubtable::SingleDelta {
+            delta: buffer.read_i16(),
+            coverage: read_coverage(buffer, coverage),
+        },
+        (SINGLE, 2) => {
+            let glyph_count = buffer.read_u16();
+            SubstSubtable::Single {
+                substitutes: buffer.read_array::<u16>(glyph_count as usize),
+                coverage: read_coverage(buffer, coverage),
+            }
+        }
+        (MULTIPLE, 1) => {
+            let sequence_count = buffer.read_u16();
+            SubstSubtable::Multiple {
+                sequences: read_glyph_arrays(buffer, subtable_start, sequence_count),
+                coverage: read_coverage(buffer, coverage),
+            }
+        }
+        (ALTERNATE, 1) => {
+            let alternate_set_count = buffer.read_u16();
+            SubstSubtable::Alternate {
+                alternate_sets: read_glyph_arrays(buffer, subtable_start, alternate_set_count),
+                coverage: read_coverage(buffer, coverage),
+            }
+        }
+        (LIGATURE, 1) => {
+            let ligature_set_count = buffer.read_u16();
+            SubstSubtable::Ligature {
+                ligature_sets: read_ligature_sets(buffer, subtable_start, ligature_set_count),
+                coverage: read_coverage(buffer, coverage),
+            }
+        }
+        (REVERSE_CHAINED_SINGLE, 1) => {
+            let backtrack_count = buffer.read_u16();
+            let backtrack_offsets = buffer.read_array::<u16>(backtrack_count as usize);
+            let lookahead_count = buffer.read_u16();
+            let lookahead_offsets = buffer.read_array::<u16>(lookahead_count as usize);
+            let glyph_count = buffer.read_u16();
+            let substitutes = buffer.read_array::<u16>(glyph_count as usize);
+            SubstSubtable::ReverseChainSingle {
+                coverage: read_coverage(buffer, coverage),
+                backtrack: read_coverages(buffer, subtable_start, backtrack_offsets),
+                lookahead: read_coverages(buffer, subtable_start, lookahead_offsets),
+                substitutes,
+            }
+        }
+        (lookup_type, _) => SubstSubtable::Unsupported(lookup_type),
+    }
+}
+
+// Reads GSUB with the buffer positioned at the start of the table
+pub fn read_gsub(buffer: &mut ByteBuffer) -> GsubTable {
+    let table_start = buffer.current_position();
+    let major_version = buffer.read_u16();
+    buffer.skip_bytes(2); // Skip minor version; 1.1 only adds feature variations
+    if major_version != 1 {
+        return GsubTable::default();
+    }
+    let script_list = read_offset16(buffer, table_start);
+    let feature_list = read_offset16(buffer, table_start);
+    let lookup_list = read_offset16(buffer, table_start);
+
+    GsubTable {
+        scripts: script_list.map(|position| read_script_list(buffer, position)).unwrap_or_default(),
+        features: feature_list.map(|position| read_feature_list(buffer, position)).unwrap_or_default(),
+        lookups: lookup_list.map(|position| read_lookup_list(buffer, position, read_subtable)).unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opentype::{ClassDef, ContextRule, SequenceContext, SequenceLookup, IGNORE_MARKS};
+
+    const MARK: u16 = 9;
+
+    fn gdef() -> GdefTable {
+        GdefTable {
+            glyph_class_def: ClassDef::Array { start_glyph: MARK, classes: vec![3] },
+            ..GdefTable::default()
+        }
+    }
+
+    fn lookup(lookup_flag: u16, subtable: SubstSubtable) -> Lookup<SubstSubtable> {
+        Lookup { lookup_type: 0, lookup_flag, mark_filtering_set: None, subtables: vec![subtable] }
+    }
+
+    fn infos(glyph_indices: &[u16]) -> Vec<GlyphInfo> {
+        glyph_indices.iter().enumerate()
+            .map(|(cluster, &glyph_index)| GlyphInfo { glyph_index, cluster, ..GlyphInfo::default() })
+            .collect()
+    }
+
+    // Runs one lookup with a feature value over the glyphs, returning the glyphs and clusters
+    fn apply(lookup: Lookup<SubstSubtable>, value: u16, glyph_indices: &[u16]) -> Vec<GlyphInfo> {
+        let gsub = GsubTable { lookups: vec![lookup], ..GsubTable::default() };
+        let mut glyphs = infos(glyph_indices);
+        gsub.apply_lookups(&mut glyphs, Some(&gdef()), &[(0, value)]);
+        glyphs
+    }
+
+    // Runs the first of several lookups, which contextual lookups can call the others from
+    fn apply_first(lookups: Vec<Lookup<SubstSubtable>>, glyph_indices: &[u16]) -> Vec<GlyphInfo> {
+        let gsub = GsubTable { lookups, ..GsubTable::default() };
+        let mut glyphs = infos(glyph_indices);
+        gsub.apply_lookups(&mut glyphs, Some(&gdef()), &[(0, 1)]);
+        glyphs
+    }
+
+    fn nested(sequence_index: u16, lookup_index: u16) -> SequenceLookup {
+        SequenceLookup { sequence_index, lookup_index }
+    }
+
+    fn single(from: &[u16], to: &[u16]) -> Lookup<SubstSubtable> {
+        lookup(0, SubstSubtable::Single { coverage: Coverage::Glyphs(from.to_vec()), substitutes: to.to_vec() })
+    }
+
+    fn glyph_indices(glyphs: &[GlyphInfo]) -> Vec<u16> {
+        glyphs.iter().map(|info| info.glyph_index).collect()
+    }
+
+    fn clusters(glyphs: &[GlyphInfo]) -> Vec<usize> {
+        glyphs.iter().map(|info| info.cluster).collect()
+    }
+
+    #[test]
+    fn single_delta_wraps_around_the_glyph_ids() {
+        let glyphs = apply(lookup(0, SubstSubtable::SingleDelta { coverage: Coverage::Glyphs(vec![1, 5]), delta: -2 }), 1, &[1, 3, 5]);
+        assert_eq!(glyph_indices(&glyphs), [65535, 3, 3]);
+    }
+
+    #[test]
+    fn single_substitutes_by_coverage_index() {
+        let subtable = SubstSubtable::Single { coverage: Coverage::Ranges(vec![(10, 12, 0)]), substitutes: vec![20, 21, 22] };
+        let glyphs = apply(lookup(0, subtable), 1, &[12, 13, 10]);
+        assert_eq!(glyph_indices(&glyphs), [22, 13, 20]);
+    }
+
+    #[test]
+    fn multiple_expands_and_deletes_within_the_cluster() {
+        let subtable = SubstSubtable::Multiple { coverage: Coverage::Glyphs(vec![1, 2]), sequences: vec![vec![10, 11, 12], vec![]] };
+        let glyphs = apply(lookup(0, subtable), 1, &[3, 1, 2, 4]);
+        assert_eq!(glyph_indices(&glyphs), [3, 10, 11, 12, 4]);
+        assert_eq!(clusters(&glyphs), [0, 1, 1, 1, 3]);
+    }
+
+    #[test]
+    fn alternate_is_picked_by_the_feature_value() {
+        let alternates = || SubstSubtable::Alternate { coverage: Coverage::Glyphs(vec![1]), alternate_sets: vec![vec![10, 11, 12]] };
+        assert_eq!(glyph_indices(&apply(lookup(0, alternates()), 1, &[1])), [10]);
+        assert_eq!(glyph_indices(&apply(lookup(0, alternates()), 3, &[1])), [12]);
+        // Values past the set leave the glyph alone
+        assert_eq!(glyph_indices(&apply(lookup(0, alternates()), 4, &[1])), [1]);
+    }
+
+    #[test]
+    fn ligature_takes_the_first_whose_components_all_match() {
+        // f f i, then f i, as fonts list longer ligatures first
+        let ligatures = || SubstSubtable::Ligature {
+            coverage: Coverage::Glyphs(vec![1]),
+            ligature_sets: vec![vec![(20, vec![1, 2]), (21, vec![2])]],
+        };
+        let glyphs = apply(lookup(0, ligatures()), 1, &[1, 1, 2, 3]);
+        assert_eq!(glyph_indices(&glyphs), [20, 3]);
+        assert_eq!(clusters(&glyphs), [0, 3]);
+
+        let glyphs = apply(lookup(0, ligatures()), 1, &[1, 1, 3, 1, 2]);
+        assert_eq!(glyph_indices(&glyphs), [1, 1, 3, 21]);
+        // A component that doesn't match stops the ligature
+        assert_eq!(glyph_indices(&apply(lookup(0, ligatures()), 1, &[1, 3, 2])), [1, 3, 2]);
+    }
+
+    #[test]
+    fn ligature_skips_marks_and_records_their_component() {
+        let ligatures = || SubstSubtable::Ligature { coverage: Coverage::Glyphs(vec![1]), ligature_sets: vec![vec![(20, vec![2, 3])]] };
+
+        // Marks between components stay behind the ligature and remember which component they followed
+        let glyphs = apply(lookup(IGNORE_MARKS, ligatures()), 1, &[1, MARK, 2, MARK, 3, MARK]);
+        assert_eq!(glyph_indices(&glyphs), [20, MARK, MARK, MARK]);
+        assert_eq!(clusters(&glyphs), [0, 0, 0, 5]);
+        let components: Vec<Option<u16>> = glyphs.iter().map(|info| info.ligature_component).collect();
+        assert_eq!(components, [None, Some(0), Some(1), None]);
+
+        // Without IGNORE_MARKS a mark is just a glyph that doesn't match
+        assert_eq!(glyph_indices(&apply(lookup(0, ligatures()), 1, &[1, MARK, 2, 3])), [1, MARK, 2, 3]);
+    }
+
+    #[test]
+    fn context_of_glyphs_applies_its_lookup_at_the_sequence_index() {
+        let rule = ContextRule { input: vec![2], lookups: vec![nested(1, 1)], ..ContextRule::default() };
+        let context = || lookup(0, SubstSubtable::Context(SequenceContext::Glyphs { coverage: Coverage::Glyphs(vec![1]), rule_sets: vec![vec![rule.clone()]] }));
+        assert_eq!(glyph_indices(&apply_first(vec![context(), single(&[2], &[20])], &[1, 2, 2])), [1, 20, 2]);
+        assert_eq!(glyph_indices(&apply_first(vec![context(), single(&[2], &[20])], &[1, 3, 2])), [1, 3, 2]);
+    }
+
+    #[test]
+    fn chained_context_of_glyphs_needs_its_backtrack_and_lookahead() {
+        let rule = ContextRule { backtrack: vec![5], input: vec![], lookahead: vec![6], lookups: vec![nested(0, 1)] };
+        let context = || lookup(0, SubstSubtable::Context(SequenceContext::Glyphs { coverage: Coverage::Glyphs(vec![1]), rule_sets: vec![vec![rule.clone()]] }));
+        assert_eq!(glyph_indices(&apply_first(vec![context(), single(&[1], &[10])], &[5, 1, 6])), [5, 10, 6]);
+        assert_eq!(glyph_indices(&apply_first(vec![context(), single(&[1], &[10])], &[4, 1, 6])), [4, 1, 6]);
+        assert_eq!(glyph_indices(&apply_first(vec![context(), single(&[1], &[10])], &[5, 1])), [5, 1]);
+    }
+
+    #[test]
+    fn chained_context_of_classes_matches_each_sequence_with_its_own_class_def() {
+        // Glyphs 1 and 2 are input class 1 and glyph 3 is input class 2, while only glyph 7
+        // is backtrack class 1
+        let rule = ContextRule { backtrack: vec![1], input: vec![2], lookahead: vec![], lookups: vec![nested(0, 1)] };
+        let context = || lookup(0, SubstSubtable::Context(SequenceContext::Classes {
+            coverage: Coverage::Glyphs(vec![1, 2]),
+            backtrack_class_def: ClassDef::Ranges(vec![(7, 7, 1)]),
+            input_class_def: ClassDef::Array { start_glyph: 1, classes: vec![1, 1, 2] },
+            lookahead_class_def: ClassDef::Ranges(vec![]),
+            rule_sets: vec![vec![], vec![rule.clone()]],
+        }));
+        let substitute = || single(&[1, 2], &[11, 12]);
+        assert_eq!(glyph_indices(&apply_first(vec![context(), substitute()], &[7, 2, 3])), [7, 12, 3]);
+        assert_eq!(glyph_indices(&apply_first(vec![context(), substitute()], &[7, 1, 3])), [7, 11, 3]);
+        assert_eq!(glyph_indices(&apply_first(vec![context(), substitute()], &[6, 2, 3])), [6, 2, 3]);
+        assert_eq!(glyph_indices(&apply_first(vec![context(), substitute()], &[7, 2, 1])), [7, 2, 1]);
+    }
+
+    #[test]
+    fn context_of_coverages_skips_marks_like_its_lookup() {
+        let context = SubstSubtable::Context(SequenceContext::Coverages {
+            backtrack: vec![],
+            input: vec![Coverage::Glyphs(vec![1]), Coverage::Glyphs(vec![2])],
+            lookahead: vec![Coverage::Glyphs(vec![3])],
+            lookups: vec![nested(1, 1)],
+        });
+        let glyphs = apply_first(vec![lookup(IGNORE_MARKS, context), single(&[2], &[20])], &[1, MARK, 2, MARK, 3]);
+        assert_eq!(glyph_indices(&glyphs), [1, MARK, 20, MARK, 3]);
+    }
+
+    #[test]
+    fn nested_lookups_follow_the_input_as_the_run_grows_and_shrinks() {
+        let context = |lookups| lookup(0, SubstSubtable::Context(SequenceContext::Coverages {
+            backtrack: vec![],
+            input: vec![Coverage::Glyphs(vec![1]), Coverage::Glyphs(vec![2]), Coverage::Glyphs(vec![3])],
+            lookahead: vec![],
+            lookups,
+        }));
+
+        // Expanding the first glyph moves the third one along
+        let expand = lookup(0, SubstSubtable::Multiple { coverage: Coverage::Glyphs(vec![1]), sequences: vec![vec![10, 11]] });
+        let glyphs = apply_first(vec![context(vec![nested(0, 1), nested(2, 2)]), expand, single(&[3], &[30])], &[1, 2, 3, 4]);
+        assert_eq!(glyph_indices(&glyphs), [10, 11, 2, 30, 4]);
+
+        // Joining the first two brings it back
+        let join = lookup(0, SubstSubtable::Ligature { coverage: Coverage::Glyphs(vec![1]), ligature_sets: vec![vec![(12, vec![2])]] });
+        let glyphs = apply_first(vec![context(vec![nested(0, 1), nested(2, 2)]), join, single(&[3], &[30])], &[1, 2, 3, 4]);
+        assert_eq!(glyph_indices(&glyphs), [12, 30, 4]);
+    }
+
+    #[test]
+    fn context_calling_itself_stops_at_the_nesting_limit() {
+        let context = SubstSubtable::Context(SequenceContext::Coverages {
+            backtrack: vec![],
+            input: vec![Coverage::Glyphs(vec![1])],
+            lookahead: vec![],
+            lookups: vec![nested(0, 0)],
+        });
+        assert_eq!(glyph_indices(&apply_first(vec![lookup(0, context)], &[1, 1])), [1, 1]);
+    }
+
+    #[test]
+    fn reverse_chaining_works_from_the_end_of_the_run() {
+        // Each 1 after a 1 becomes 2, seeing the glyphs before it as they were
+        let reverse = SubstSubtable::ReverseChainSingle {
+            coverage: Coverage::Glyphs(vec![1]),
+            backtrack: vec![Coverage::Glyphs(vec![1])],
+            lookahead: vec![],
+            substitutes: vec![2],
+        };
+        assert_eq!(glyph_indices(&apply(lookup(0, reverse), 1, &[2, 1, 1, 1])), [2, 1, 2, 2]);
+
+        let reverse = SubstSubtable::ReverseChainSingle {
+            coverage: Coverage::Glyphs(vec![1]),
+            backtrack: vec![],
+            lookahead: vec![Coverage::Glyphs(vec![3])],
+            substitutes: vec![4],
+        };
+        assert_eq!(glyph_indices(&apply(lookup(0, reverse), 1, &[1, 3, 1])), [4, 3, 1]);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::glyph::Glyph;
+use crate::gpos::GlyphPosition;
+use crate::hinting::HintedGlyph;
+use crate::image::{Color, RgbImage};
+use crate::lcd::{rasterize_outline_lcd, LcdFilter, SubpixelOrder};
+use crate::outline::Outline;
+use crate::raster::{rasterize_outline, FillRule};
+use crate::metrics::LineMetrics;
+
+#[derive(Debug, Clone, Copy)]
+pub struct RenderOptions {
+    pub pixel_size: f32,
+    pub foreground: Color,
+    pub background: Color,
+    pub padding: u32,
+    // Subpixel rendering for an LCD with this stripe order, greyscale anti-aliasing when None
+    pub subpixel_order: Option<SubpixelOrder>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            pixel_size: 64.0,
+            foreground: Color::BLACK,
+            background: Color::WHITE,
+            padding: 16,
+            subpixel_order: None,
+        }
+    }
+}
+
+// Pen positions for lines of glyphs, in pixels with y pointing down
+#[derive(Debug, Clone, Default)]
+pub struct TextLayout {
+    pub width: f32,
+    pub height: f32,
+    // One (x, baseline y) origin per glyph, line by line
+    pub origins: Vec<Vec<(f32, f32)>>,
+}
+
+// Lays out lines of glyphs at their positions, one per glyph as from shaping, each line starting
+// its offset in font units from the left (none if there are fewer offsets than lines). Lines
+// are spaced by the font's line height and the padding surrounds the text.
+pub fn layout_lines(lines: &[Vec<Glyph>], positions: &[Vec<GlyphPosition>], line_offsets: &[i32], units_per_em: u16, metrics: &LineMetrics, options: &RenderOptions) -> TextLayout {
+    let scale = options.pixel_size / units_per_em as f32;
+    let ascender = metrics.ascender as f32 * scale;
+    let descender = metrics.descender as f32 * scale;
+    let line_height = metrics.line_height() as f32 * scale;
+    let padding = options.padding as f32;
+
+    let mut text_width: f32 = 0.0;
+    let mut origins = Vec::with_capacity(lines.len());
+    for line_index in 0..lines.len() {
+        let baseline = (padding + ascender + line_index as f32 * line_height).round();
+        let line_offset = line_offsets.get(line_index).copied().unwrap_or(0) as f32 * scale;
+        let (mut pen_x, mut pen_y) = (padding + line_offset, baseline);
+        let mut line_origins = Vec::new();
+        for position in positions.get(line_index).map_or(&[][..], |positions| positions.as_slice()) {
+            line_origins.push((pen_x + position.x_offset as f32 * scale, pen_y - position.y_offset as f32 * scale));
+            pen_x += position.x_advance as f32 * scale;
+            pen_y -= position.y_advance as f32 * scale;
+        }
+        text_width = text_width.max(pen_x - padding);
+        origins.push(line_origins);
+    }
+
+    let text_height = match lines.len() {
+        0 => 0.0,
+        count => (count - 1) as f32 * line_height + ascender - descender,
+    };
+    TextLayout {
+        width: (text_width + 2.0 * padding).ceil(),
+        height: (text_height + 2.0 * padding).ceil(),
+        origins,
+    }
+}
+
+// Renders laid out lines into an image sized to fit, per colour channel when the options name a
+// subpixel order. Pen positions keep their fractional part by rasterizing each glyph at its
+// subpixel offset. A glyph with a hinted outline in `hinted`
+// (laid out like `lines`) is drawn from that instead, at the nearest whole pixel so it stays on
+// the grid it was fitted to.
+pub fn render_lines(lines: &[Vec<Glyph>], positions: &[Vec<GlyphPosition>], line_offsets: &[i32], hinted: &[Vec<Option<HintedGlyph>>], units_per_em: u16, metrics: &LineMetrics, options: &RenderOptions) -> RgbImage {
+    let layout = layout_lines(lines, positions, line_offsets, units_per_em, metrics, options);
+    let mut image = RgbImage::new(layout.width as usize, layout.height as usize, options.background);
+
+    let scale = options.pixel_size / units_per_em as f32;
+    for (line_index, (line, origins)) in lines.iter().zip(&layout.origins).enumerate() {
+        for (glyph_index, (glyph, &(pen_x, baseline))) in line.iter().zip(origins).enumerate() {
+            let hinted_glyph = hinted.get(line_index).and_then(|line| line.get(glyph_index)).and_then(Option::as_ref);
+            let (outline, outline_scale, origin_x, origin_y, subpixel_offset) = match hinted_glyph {
+                Some(hinted_glyph) => (Outline::from_hinted_glyph(hinted_glyph), 1.0, pen_x.round(), baseline.round(), (0.0, 0.0)),
+                None => {
+                    let origin_x = pen_x.floor();
+                    let origin_y = baseline.ceil();
+                    (Outline::from_glyph(glyph), scale, origin_x, origin_y, (pen_x - origin_x, origin_y - baseline))
+                }
+            };
+            match options.subpixel_order {
+                Some(order) => {
+                    let bitmap = rasterize_outline_lcd(&outline, outline_scale, subpixel_offset, FillRule::NonZero, order, LcdFilter::DEFAULT);
+                    image.draw_lcd_bitmap(&bitmap, origin_x as i32, origin_y as i32, options.foreground);
+                }
+                None => {
+                    let bitmap = rasterize_outline(&outline, outline_scale, subpixel_offset, FillRule::NonZero);
+                    image.draw_bitmap(&bitmap, origin_x as i32, origin_y as i32, options.foreground);
+                }
+            }
+        }
+    }
+
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::glyph::process_points;
+
+    const METRICS: LineMetrics = LineMetrics { ascender: 1000, descender: 0, line_gap: 0 };
+
+    // A stem from 130 to 470 units across and 700 tall, with edges between pixels at most sizes
+    fn stem() -> Glyph {
+        let x_coordinates = vec![130, 130, 470, 470];
+        let y_coordinates = vec![0, 700, 700, 0];
+        let flags = vec![1; 4];
+        let (processed_points, end_pts_of_contours) = process_points(&x_coordinates, &y_coordinates, &flags, &[3]);
+        Glyph {
+            glyph_index: 1,
+            num_contours: 1,
+            xmin: 130,
+            ymin: 0,
+            xmax: 470,
+            ymax: 700,
+            end_pts_of_contours,
+            x_coordinates,
+            y_coordinates,
+            flags,
+            processed_points,
+            raw_end_pts_of_contours: vec![3],
+            instructions: vec![],
+            advance_width: 600.0,
+            left_side_bearing: 130,
+        }
+    }
+
+    fn render(hinted: &[Vec<Option<HintedGlyph>>], options: &RenderOptions) -> RgbImage {
+        let positions = vec![vec![GlyphPosition { x_advance: 600, ..GlyphPosition::default() }]];
+        render_lines(&[vec![stem()]], &positions, &[], hinted, 1000, &METRICS, options)
+    }
+
+    #[test]
+    fn lcd_output_swaps_red_and_blue_between_stripe_orders() {
+        let options = |order| RenderOptions { pixel_size: 20.0, padding: 2, subpixel_order: Some(order), ..RenderOptions::default() };
+        let rgb = render(&[], &options(SubpixelOrder::Rgb));
+        let bgr = render(&[], &options(SubpixelOrder::Bgr));
+
+        assert_eq!((rgb.width, rgb.height), (bgr.width, bgr.height));
+        for (rgb_pixel, bgr_pixel) in rgb.pixels.chunks(3).zip(bgr.pixels.chunks(3)) {
+            assert_eq!(rgb_pixel, [bgr_pixel[2], bgr_pixel[1], bgr_pixel[0]]);
+        }
+        // The stem's edges fall inside pixels, which come out coloured rather than grey
+        assert!(rgb.pixels.chunks(3).any(|pixel| pixel[0] != pixel[2]));
+    }
+
+    #[test]
+    fn hinted_glyphs_are_drawn_from_their_fitted_outline() {
+        // At 10 pixels the stem runs from 1.3 to 4.7, so unhinted it has grey edges
+        let options = RenderOptions { pixel_size: 10.0, padding: 0, ..RenderOptions::default() };
+        assert!(render(&[], &options).pixels.iter().any(|&value| value != 0 && value != 255));
+
+        let hinted = HintedGlyph {
+            glyph_index: 1,
+            ppem: 10,
+            points: vec![(1.0, 0.0), (1.0, 7.0), (5.0, 7.0), (5.0, 0.0)],
+            on_curve: vec![true; 4],
+            end_pts_of_contours: vec![3],
+            advance_width: 6.0,
+        };
+        let image = render(&[vec![Some(hinted)]], &options);
+        assert!(image.pixels.iter().all(|&value| value == 0 || value == 255));
+        let black = image.pixels.chunks(3).filter(|pixel| pixel[0] == 0).count();
+        assert_eq!(black, 4 * 7);
+    }
+}
@@ -0,0 +1,1600 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::glyph::{Glyph, process_points};
+use crate::table::{HeadTable, MaxpTable};
+
+// Guards against runaway programs (recursive CALLs, backwards jumps that never terminate)
+const MAX_CALL_DEPTH: usize = 64;
+const MAX_INSTRUCTIONS: usize = 1_000_000;
+
+// Unit vectors are stored as 2.14 fixed point, distances and coordinates as 26.6 fixed point
+const ONE_2DOT14: i32 = 0x4000;
+const TOUCHED_X: u8 = 1;
+const TOUCHED_Y: u8 = 2;
+const PHANTOM_POINTS: usize = 4;
+
+const TWILIGHT_ZONE: usize = 0;
+const GLYPH_ZONE: usize = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RoundState {
+    ToGrid,
+    ToHalfGrid,
+    ToDoubleGrid,
+    DownToGrid,
+    UpToGrid,
+    Off,
+    Super,
+    Super45,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct GraphicsState {
+    rp0: usize,
+    rp1: usize,
+    rp2: usize,
+    zp0: usize,
+    zp1: usize,
+    zp2: usize,
+    projection_vector: (i32, i32),
+    dual_projection_vector: (i32, i32),
+    freedom_vector: (i32, i32),
+    loop_count: i32,
+    minimum_distance: i32,
+    round_state: RoundState,
+    period: i32,
+    phase: i32,
+    threshold: i32,
+    auto_flip: bool,
+    control_value_cutin: i32,
+    single_width_cutin: i32,
+    single_width_value: i32,
+    delta_base: i32,
+    delta_shift: i32,
+    instruct_control: i32,
+}
+
+impl Default for GraphicsState {
+    fn default() -> Self {
+        GraphicsState {
+            rp0: 0,
+            rp1: 0,
+            rp2: 0,
+            zp0: GLYPH_ZONE,
+            zp1: GLYPH_ZONE,
+            zp2: GLYPH_ZONE,
+            projection_vector: (ONE_2DOT14, 0),
+            dual_projection_vector: (ONE_2DOT14, 0),
+            freedom_vector: (ONE_2DOT14, 0),
+            loop_count: 1,
+            minimum_distance: 64,
+            round_state: RoundState::ToGrid,
+            period: 64,
+            phase: 0,
+            threshold: 32,
+            auto_flip: true,
+            control_value_cutin: 68, // 17/16 pixel
+            single_width_cutin: 0,
+            single_width_value: 0,
+            delta_base: 9,
+            delta_shift: 3,
+            instruct_control: 0,
+        }
+    }
+}
+
+impl GraphicsState {
+    // Every program starts in the glyph zone with x-axis vectors, grid rounding and a loop of 1,
+    // while the remaining state carries over from the control value program
+    fn reset_for_program(&mut self) {
+        self.zp0 = GLYPH_ZONE;
+        self.zp1 = GLYPH_ZONE;
+        self.zp2 = GLYPH_ZONE;
+        self.projection_vector = (ONE_2DOT14, 0);
+        self.dual_projection_vector = (ONE_2DOT14, 0);
+        self.freedom_vector = (ONE_2DOT14, 0);
+        self.round_state = RoundState::ToGrid;
+        self.loop_count = 1;
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Zone {
+    original: Vec<(i32, i32)>,
+    current: Vec<(i32, i32)>,
+    touched: Vec<u8>,
+    on_curve: Vec<bool>,
+    end_pts_of_contours: Vec<usize>,
+}
+
+impl Zone {
+    fn with_points(num_points: usize) -> Self {
+        Zone {
+            original: vec![(0, 0); num_points],
+            current: vec![(0, 0); num_points],
+            touched: vec![0; num_points],
+            on_curve: vec![true; num_points],
+            end_pts_of_contours: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Definition {
+    code: Rc<[u8]>,
+    start: usize,
+    end: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ProgramKind {
+    Font,
+    ControlValue,
+    Glyph,
+}
+
+// Grid-fitted outline in pixels, y pointing up, with the origin at the hinted left phantom point
+#[derive(Debug, Clone)]
+pub struct HintedGlyph {
+    pub glyph_index: u16,
+    pub ppem: u16,
+    pub points: Vec<(f32, f32)>,
+    pub on_curve: Vec<bool>,
+    pub end_pts_of_contours: Vec<u16>,
+    pub advance_width: f32,
+}
+
+impl HintedGlyph {
+    // Maps the grid-fitted points back into font units so they can replace the original outline
+    pub fn to_glyph(&self, glyph: &Glyph, units_per_em: u16) -> Glyph {
+        let to_font_units = |v: f32| -> i16 { (v * units_per_em as f32 / self.ppem as f32).round() as i16 };
+
+        let x_coordinates: Vec<i16> = self.points.iter().map(|&(x, _)| to_font_units(x)).collect();
+        let y_coordinates: Vec<i16> = self.points.iter().map(|&(_, y)| to_font_units(y)).collect();
+        // The hinted points are relative to the hinted origin, so the header box and bearing are
+        // recomputed to keep the origin at x = 0
+        let (xmin, xmax) = (x_coordinates.iter().copied().min().unwrap_or(0), x_coordinates.iter().copied().max().unwrap_or(0));
+        let (ymin, ymax) = (y_coordinates.iter().copied().min().unwrap_or(0), y_coordinates.iter().copied().max().unwrap_or(0));
+        let flags: Vec<u8> = self.on_curve.iter().map(|&on_curve| on_curve as u8).collect();
+        let (processed_points, end_pts_of_contours) =
+            process_points(&x_coordinates, &y_coordinates, &flags, &self.end_pts_of_contours);
+
+        Glyph {
+            end_pts_of_contours,
+            raw_end_pts_of_contours: self.end_pts_of_contours.clone(),
+            x_coordinates,
+            y_coordinates,
+            flags,
+            processed_points,
+            advance_width: (self.advance_width * units_per_em as f32 / self.ppem as f32) as f64,
+            xmin,
+            ymin,
+            xmax,
+            ymax,
+            left_side_bearing: xmin,
+            ..glyph.clone()
+        }
+    }
+}
+
+// TrueType bytecode interpreter. The font program runs once on creation, the control value
+// program runs once per pixel size, and each glyph program runs against the state it leaves behind.
+pub struct Hinter {
+    ppem: u16,
+    units_per_em: u16,
+    scale: i64, // 16.16 factor from font units to 26.6 pixels
+    max_stack_elements: usize,
+    functions: Vec<Option<Definition>>,
+    instruction_defs: HashMap<u8, Definition>,
+    cvt: Vec<i32>,
+    storage: Vec<i32>,
+    zones: [Zone; 2],
+    stack: Vec<i32>,
+    state: GraphicsState,
+    program: ProgramKind,
+    instruction_count: usize,
+    // Snapshots taken after the control value program, restored before every glyph program
+    default_state: GraphicsState,
+    default_cvt: Vec<i32>,
+    default_storage: Vec<i32>,
+    default_twilight: Zone,
+}
+
+impl Hinter {
+    pub fn new(head_table: &HeadTable, maxp_table: &MaxpTable, fpgm: &[u8], prep: &[u8], cvt: &[i16], ppem: u16) -> Result<Self, String> {
+        if head_table.units_per_em == 0 {
+            return Err("unitsPerEm must not be zero".into());
+        }
+        if ppem == 0 {
+            return Err("ppem must not be zero".into());
+        }
+        let scale = ((ppem as i64) << 22) / head_table.units_per_em as i64;
+
+        let mut hinter = Hinter {
+            ppem,
+            units_per_em: head_table.units_per_em,
+            scale,
+            // Plenty of fonts understate their limits, so leave some headroom
+            max_stack_elements: maxp_table.max_stack_elements as usize + 32,
+            functions: vec![None; maxp_table.max_function_defs as usize],
+            instruction_defs: HashMap::new(),
+            cvt: vec![],
+            storage: vec![0; maxp_table.max_storage as usize],
+            zones: [Zone::with_points(maxp_table.max_twilight_points as usize), Zone::default()],
+            stack: Vec::new(),
+            state: GraphicsState::default(),
+            program: ProgramKind::Font,
+            instruction_count: 0,
+            default_state: GraphicsState::default(),
+            default_cvt: vec![],
+            default_storage: vec![],
+            default_twilight: Zone::default(),
+        };
+        hinter.cvt = cvt.iter().map(|&value| hinter.scale_font_units(value as i32)).collect();
+
+        if !fpgm.is_empty() {
+            hinter.run_program(ProgramKind::Font, Rc::from(fpgm))?;
+        }
+
+        hinter.state = GraphicsState::default();
+        if !prep.is_empty() {
+            hinter.run_program(ProgramKind::ControlValue, Rc::from(prep))?;
+        }
+
+        hinter.default_state = hinter.state;
+        hinter.default_cvt = hinter.cvt.clone();
+        hinter.default_storage = hinter.storage.clone();
+        hinter.default_twilight = hinter.zones[TWILIGHT_ZONE].clone();
+        Ok(hinter)
+    }
+
+    pub fn hint_glyph(&mut self, glyph: &Glyph) -> Result<HintedGlyph, String> {
+        let num_points = glyph.x_coordinates.len();
+        if glyph.flags.len() != num_points {
+            return Err(format!("Glyph {} has no point flags to hint", glyph.glyph_index));
+        }
+
+        // Glyph points followed by the four phantom points (left/right origin, top/bottom origin)
+        let mut zone = Zone::with_points(num_points + PHANTOM_POINTS);
+        for i in 0..num_points {
+            let point = (self.scale_font_units(glyph.x_coordinates[i] as i32), self.scale_font_units(glyph.y_coordinates[i] as i32));
+            zone.original[i] = point;
+            zone.on_curve[i] = (glyph.flags[i] & 1) != 0;
+        }
+        let phantom = glyph.phantom_points(None, self.units_per_em);
+        for (i, &(x, y)) in phantom.iter().enumerate() {
+            zone.original[num_points + i] = (self.scale_font_units(x), self.scale_font_units(y));
+        }
+        zone.current = zone.original.clone();
+        for i in 0..2 {
+            zone.current[num_points + i].0 = round_pixel(zone.current[num_points + i].0);
+            zone.current[num_points + 2 + i].1 = round_pixel(zone.current[num_points + 2 + i].1);
+        }
+        zone.end_pts_of_contours = glyph.raw_end_pts_of_contours.iter().map(|&end| end as usize).collect();
+
+        self.zones[GLYPH_ZONE] = zone;
+        self.zones[TWILIGHT_ZONE] = self.default_twilight.clone();
+        self.cvt = self.default_cvt.clone();
+        self.storage = self.default_storage.clone();
+        self.state = self.default_state;
+        // Bit 2 of INSTCTRL asks glyph programs to ignore graphics state set by the control value program
+        if (self.state.instruct_control & 2) != 0 {
+            self.state = GraphicsState::default();
+        }
+
+        // Bit 1 of INSTCTRL turns off glyph programs entirely
+        if !glyph.instructions.is_empty() && (self.state.instruct_control & 1) == 0 {
+            self.run_program(ProgramKind::Glyph, Rc::from(glyph.instructions.as_slice()))?;
+        }
+
+        let zone = &self.zones[GLYPH_ZONE];
+        let origin_x = zone.current[num_points].0;
+        let advance = zone.current[num_points + 1].0.wrapping_sub(origin_x);
+        Ok(HintedGlyph {
+            glyph_index: glyph.glyph_index,
+            ppem: self.ppem,
+            points: zone.current[..num_points].iter()
+                .map(|&(x, y)| (x.wrapping_sub(origin_x) as f32 / 64.0, y as f32 / 64.0))
+                .collect(),
+            on_curve: zone.on_curve[..num_points].to_vec(),
+            end_pts_of_contours: glyph.raw_end_pts_of_contours.clone(),
+            advance_width: advance as f32 / 64.0,
+        })
+    }
+
+    fn scale_font_units(&self, value: i32) -> i32 {
+        ((value as i64 * self.scale + 0x8000) >> 16) as i32
+    }
+
+    fn run_program(&mut self, program: ProgramKind, code: Rc<[u8]>) -> Result<(), String> {
+        self.program = program;
+        self.instruction_count = 0;
+        self.stack.clear();
+        self.state.reset_for_program();
+        let end = code.len();
+        self.execute(&code, 0, end, 0)
+            .map_err(|error| format!("{:?} program: {}", program, error))
+    }
+
+    fn execute(&mut self, code: &Rc<[u8]>, start: usize, end: usize, depth: usize) -> Result<(), String> {
+        if depth > MAX_CALL_DEPTH {
+            return Err("call depth exceeded".into());
+        }
+
+        let mut ip = start;
+        while ip < end {
+            self.instruction_count += 1;
+            if self.instruction_count > MAX_INSTRUCTIONS {
+                return Err("instruction limit exceeded".into());
+            }
+
+            let opcode_position = ip;
+            let opcode = code[ip];
+            ip += 1;
+
+            match opcode {
+                // SVTCA, SPVTCA, SFVTCA: set vectors to coordinate axis
+                0x00..=0x05 => {
+                    let axis = if (opcode & 1) != 0 { (ONE_2DOT14, 0) } else { (0, ONE_2DOT14) };
+                    if opcode <= 0x03 {
+                        self.state.projection_vector = axis;
+                        self.state.dual_projection_vector = axis;
+                    }
+                    if opcode <= 0x01 || opcode >= 0x04 {
+                        self.state.freedom_vector = axis;
+                    }
+                }
+                // SPVTL, SFVTL: set vector to line
+                0x06..=0x09 => {
+                    let p2 = self.pop_point(self.state.zp2)?;
+                    let p1 = self.pop_point(self.state.zp1)?;
+                    let a = self.zones[self.state.zp1].current[p1];
+                    let b = self.zones[self.state.zp2].current[p2];
+                    let vector = line_vector(a, b, (opcode & 1) != 0);
+                    if opcode <= 0x07 {
+                        self.state.projection_vector = vector;
+                        self.state.dual_projection_vector = vector;
+                    } else {
+                        self.state.freedom_vector = vector;
+                    }
+                }
+                // SPVFS, SFVFS: set vector from stack
+                0x0A | 0x0B => {
+                    let y = self.pop()?;
+                    let x = self.pop()?;
+                    let vector = normalize(x, y);
+                    if opcode == 0x0A {
+                        self.state.projection_vector = vector;
+                        self.state.dual_projection_vector = vector;
+                    } else {
+                        self.state.freedom_vector = vector;
+                    }
+                }
+                // GPV, GFV
+                0x0C | 0x0D => {
+                    let (x, y) = if opcode == 0x0C { self.state.projection_vector } else { self.state.freedom_vector };
+                    self.push(x)?;
+                    self.push(y)?;
+                }
+                // SFVTPV
+                0x0E => self.state.freedom_vector = self.state.projection_vector,
+                // ISECT
+                0x0F => {
+                    let b1 = self.pop_point(self.state.zp0)?;
+                    let b0 = self.pop_point(self.state.zp0)?;
+                    let a1 = self.pop_point(self.state.zp1)?;
+                    let a0 = self.pop_point(self.state.zp1)?;
+                    let point = self.pop_point(self.state.zp2)?;
+                    self.intersect(point, a0, a1, b0, b1);
+                }
+                // SRP0, SRP1, SRP2
+                0x10..=0x12 => {
+                    let value = self.pop()?;
+                    let point = usize::try_from(value).map_err(|_| format!("invalid reference point {}", value))?;
+                    match opcode {
+                        0x10 => self.state.rp0 = point,
+                        0x11 => self.state.rp1 = point,
+                        _ => self.state.rp2 = point,
+                    }
+                }
+                // SZP0, SZP1, SZP2, SZPS
+                0x13..=0x16 => {
+                    let zone = self.pop_zone()?;
+                    match opcode {
+                        0x13 => self.state.zp0 = zone,
+                        0x14 => self.state.zp1 = zone,
+                        0x15 => self.state.zp2 = zone,
+                        _ => {
+                            self.state.zp0 = zone;
+                            self.state.zp1 = zone;
+                            self.state.zp2 = zone;
+                        }
+                    }
+                }
+                // SLOOP
+                0x17 => {
+                    let count = self.pop()?;
+                    if count < 0 {
+                        return Err(format!("invalid loop count {}", count));
+                    }
+                    self.state.loop_count = count;
+                }
+                // RTG, RTHG
+                0x18 => self.state.round_state = RoundState::ToGrid,
+                0x19 => self.state.round_state = RoundState::ToHalfGrid,
+                // SMD
+                0x1A => self.state.minimum_distance = self.pop()?,
+                // ELSE reached while executing means the IF branch ran, so skip to the matching EIF
+                0x1B => ip = skip_conditional(code, ip, end, false)?,
+                // JMPR
+                0x1C => {
+                    let offset = self.pop()?;
+                    ip = jump_target(opcode_position, offset, end)?;
+                }
+                // SCVTCI, SSWCI, SSW
+                0x1D => self.state.control_value_cutin = self.pop()?,
+                0x1E => self.state.single_width_cutin = self.pop()?,
+                0x1F => {
+                    let value = self.pop()?;
+                    self.state.single_width_value = self.scale_font_units(value);
+                }
+                // DUP
+                0x20 => {
+                    let value = self.peek(1)?;
+                    self.push(value)?;
+                }
+                // POP
+                0x21 => {
+                    self.pop()?;
+                }
+                // CLEAR
+                0x22 => self.stack.clear(),
+                // SWAP
+                0x23 => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    self.push(b)?;
+                    self.push(a)?;
+                }
+                // DEPTH
+                0x24 => self.push(self.stack.len() as i32)?,
+                // CINDEX
+                0x25 => {
+                    let index = self.pop()?;
+                    let value = self.peek(index)?;
+                    self.push(value)?;
+                }
+                // MINDEX
+                0x26 => {
+                    let index = self.pop()?;
+                    self.peek(index)?;
+                    let value = self.stack.remove(self.stack.len() - index as usize);
+                    self.push(value)?;
+                }
+                // ALIGNPTS
+                0x27 => {
+                    let p2 = self.pop_point(self.state.zp0)?;
+                    let p1 = self.pop_point(self.state.zp1)?;
+                    let distance = self.project(self.zones[self.state.zp0].current[p2], self.zones[self.state.zp1].current[p1]) / 2;
+                    self.move_point(self.state.zp1, p1, distance, true);
+                    self.move_point(self.state.zp0, p2, -distance, true);
+                }
+                // UTP
+                0x29 => {
+                    let point = self.pop_point(self.state.zp0)?;
+                    let (fx, fy) = self.state.freedom_vector;
+                    let zone = &mut self.zones[self.state.zp0];
+                    if fx != 0 {
+                        zone.touched[point] &= !TOUCHED_X;
+                    }
+                    if fy != 0 {
+                        zone.touched[point] &= !TOUCHED_Y;
+                    }
+                }
+                // LOOPCALL, CALL
+                0x2A | 0x2B => {
+                    let function = self.pop()?;
+                    let count = if opcode == 0x2A { self.pop()? } else { 1 };
+                    let definition = usize::try_from(function).ok()
+                        .and_then(|index| self.functions.get(index).cloned().flatten())
+                        .ok_or_else(|| format!("call to undefined function {}", function))?;
+                    for _ in 0..count.max(0) {
+                        self.execute(&definition.code, definition.start, definition.end, depth + 1)?;
+                    }
+                }
+                // FDEF
+                0x2C => {
+                    if self.program == ProgramKind::Glyph {
+                        return Err("FDEF is not allowed in glyph programs".into());
+                    }
+                    let function = self.pop()?;
+                    let body_end = skip_definition(code, ip, end)?;
+                    let slot = usize::try_from(function).ok()
+                        .and_then(|index| self.functions.get_mut(index))
+                        .ok_or_else(|| format!("function number {} exceeds maxFunctionDefs", function))?;
+                    *slot = Some(Definition { code: code.clone(), start: ip, end: body_end });
+                    ip = body_end + 1;
+                }
+                // ENDF
+                0x2D => return Ok(()),
+                // MDAP
+                0x2E | 0x2F => {
+                    let point = self.pop_point(self.state.zp0)?;
+                    let distance = if (opcode & 1) != 0 {
+                        let current = self.project(self.zones[self.state.zp0].current[point], (0, 0));
+                        self.round(current).wrapping_sub(current)
+                    } else {
+                        0
+                    };
+                    self.move_point(self.state.zp0, point, distance, true);
+                    self.state.rp0 = point;
+                    self.state.rp1 = point;
+                }
+                // IUP[y], IUP[x]
+                0x30 | 0x31 => self.interpolate_untouched((opcode & 1) != 0),
+                // SHP
+                0x32 | 0x33 => {
+                    let (dx, dy) = self.reference_displacement((opcode & 1) != 0)?;
+                    for _ in 0..self.take_loop() {
+                        let point = self.pop_point(self.state.zp2)?;
+                        self.shift_point(self.state.zp2, point, dx, dy, true);
+                    }
+                }
+                // SHC
+                0x34 | 0x35 => {
+                    let (dx, dy) = self.reference_displacement((opcode & 1) != 0)?;
+                    let contour = self.pop()?;
+                    let zone = self.state.zp2;
+                    let ends = &self.zones[zone].end_pts_of_contours;
+                    let contour = usize::try_from(contour).ok().filter(|&c| c < ends.len())
+                        .ok_or_else(|| format!("invalid contour {}", contour))?;
+                    let first = if contour == 0 { 0 } else { ends[contour - 1] + 1 };
+                    let last = ends[contour];
+                    let (reference_zone, reference_point) = self.reference_point((opcode & 1) != 0);
+                    for point in first..=last {
+                        if zone != reference_zone || point != reference_point {
+                            self.shift_point(zone, point, dx, dy, true);
+                        }
+                    }
+                }
+                // SHZ
+                0x36 | 0x37 => {
+                    let (dx, dy) = self.reference_displacement((opcode & 1) != 0)?;
+                    let zone = self.pop_zone()?;
+                    let limit = match self.zones[zone].end_pts_of_contours.last() {
+                        Some(&last) => last + 1,
+                        None => self.zones[zone].current.len(),
+                    };
+                    let (reference_zone, reference_point) = self.reference_point((opcode & 1) != 0);
+                    for point in 0..limit {
+                        if zone != reference_zone || point != reference_point {
+                            self.shift_point(zone, point, dx, dy, false);
+                        }
+                    }
+                }
+                // SHPIX
+                0x38 => {
+                    let amount = self.pop()?;
+                    let dx = mul_2dot14(amount, self.state.freedom_vector.0);
+                    let dy = mul_2dot14(amount, self.state.freedom_vector.1);
+                    for _ in 0..self.take_loop() {
+                        let point = self.pop_point(self.state.zp2)?;
+                        self.shift_point(self.state.zp2, point, dx, dy, true);
+                    }
+                }
+                // IP
+                0x39 => self.interpolate_points()?,
+                // MSIRP
+                0x3A | 0x3B => {
+                    let distance = self.pop()?;
+                    let point = self.pop_point(self.state.zp1)?;
+                    let rp0 = self.reference(self.state.zp0, self.state.rp0)?;
+                    if self.state.zp1 == TWILIGHT_ZONE {
+                        let original = self.zones[self.state.zp0].original[rp0];
+                        let zone = &mut self.zones[TWILIGHT_ZONE];
+                        zone.original[point] = original;
+                        zone.current[point] = original;
+                    }
+                    let current = self.project(self.zones[self.state.zp1].current[point], self.zones[self.state.zp0].current[rp0]);
+                    self.move_point(self.state.zp1, point, distance.wrapping_sub(current), true);
+                    self.state.rp1 = self.state.rp0;
+                    self.state.rp2 = point;
+                    if (opcode & 1) != 0 {
+                        self.state.rp0 = point;
+                    }
+                }
+                // ALIGNRP
+                0x3C => {
+                    let rp0 = self.reference(self.state.zp0, self.state.rp0)?;
+                    for _ in 0..self.take_loop() {
+                        let point = self.pop_point(self.state.zp1)?;
+                        let distance = self.project(self.zones[self.state.zp1].current[point], self.zones[self.state.zp0].current[rp0]);
+                        self.move_point(self.state.zp1, point, -distance, true);
+                    }
+                }
+                // RTDG
+                0x3D => self.state.round_state = RoundState::ToDoubleGrid,
+                // MIAP
+                0x3E | 0x3F => {
+                    let cvt_index = self.pop()?;
+                    let point = self.pop_point(self.state.zp0)?;
+                    let mut distance = self.read_cvt(cvt_index)?;
+                    if self.state.zp0 == TWILIGHT_ZONE {
+                        let (fx, fy) = self.state.freedom_vector;
+                        let position = (mul_2dot14(distance, fx), mul_2dot14(distance, fy));
+                        let zone = &mut self.zones[TWILIGHT_ZONE];
+                        zone.original[point] = position;
+                        zone.current[point] = position;
+                    }
+                    let current = self.project(self.zones[self.state.zp0].current[point], (0, 0));
+                    if (opcode & 1) != 0 {
+                        if distance.wrapping_sub(current).wrapping_abs() > self.state.control_value_cutin {
+                            distance = current;
+                        }
+                        distance = self.round(distance);
+                    }
+                    self.move_point(self.state.zp0, point, distance.wrapping_sub(current), true);
+                    self.state.rp0 = point;
+                    self.state.rp1 = point;
+                }
+                // NPUSHB, NPUSHW
+                0x40 | 0x41 => {
+                    let count = *code.get(ip).ok_or("truncated push instruction")? as usize;
+                    ip = self.push_inline(code, ip + 1, count, opcode == 0x41)?;
+                }
+                // WS
+                0x42 => {
+                    let value = self.pop()?;
+                    let index = self.pop()?;
+                    let slot = usize::try_from(index).ok()
+                        .and_then(|index| self.storage.get_mut(index))
+                        .ok_or_else(|| format!("storage index {} out of range", index))?;
+                    *slot = value;
+                }
+                // RS
+                0x43 => {
+                    let index = self.pop()?;
+                    let value = usize::try_from(index).ok()
+                        .and_then(|index| self.storage.get(index).copied())
+                        .ok_or_else(|| format!("storage index {} out of range", index))?;
+                    self.push(value)?;
+                }
+                // WCVTP, WCVTF
+                0x44 | 0x70 => {
+                    let value = self.pop()?;
+                    let index = self.pop()?;
+                    let value = if opcode == 0x70 { self.scale_font_units(value) } else { value };
+                    self.write_cvt(index, value)?;
+                }
+                // RCVT
+                0x45 => {
+                    let index = self.pop()?;
+                    let value = self.read_cvt(index)?;
+                    self.push(value)?;
+                }
+                // GC
+                0x46 | 0x47 => {
+                    let point = self.pop_point(self.state.zp2)?;
+                    let value = if (opcode & 1) != 0 {
+                        self.dual_project(self.zones[self.state.zp2].original[point], (0, 0))
+                    } else {
+                        self.project(self.zones[self.state.zp2].current[point], (0, 0))
+                    };
+                    self.push(value)?;
+                }
+                // SCFS
+                0x48 => {
+                    let value = self.pop()?;
+                    let point = self.pop_point(self.state.zp2)?;
+                    let current = self.project(self.zones[self.state.zp2].current[point], (0, 0));
+                    self.move_point(self.state.zp2, point, value.wrapping_sub(current), true);
+                    if self.state.zp2 == TWILIGHT_ZONE {
+                        let zone = &mut self.zones[TWILIGHT_ZONE];
+                        zone.original[point] = zone.current[point];
+                    }
+                }
+                // MD
+                0x49 | 0x4A => {
+                    let p2 = self.pop_point(self.state.zp1)?;
+                    let p1 = self.pop_point(self.state.zp0)?;
+                    let distance = if (opcode & 1) != 0 {
+                        self.project(self.zones[self.state.zp0].current[p1], self.zones[self.state.zp1].current[p2])
+                    } else {
+                        self.dual_project(self.zones[self.state.zp0].original[p1], self.zones[self.state.zp1].original[p2])
+                    };
+                    self.push(distance)?;
+                }
+                // MPPEM, MPS
+                0x4B | 0x4C => self.push(self.ppem as i32)?,
+                // FLIPON, FLIPOFF
+                0x4D => self.state.auto_flip = true,
+                0x4E => self.state.auto_flip = false,
+                // DEBUG
+                0x4F => {
+                    self.pop()?;
+                }
+                // LT, LTEQ, GT, GTEQ, EQ, NEQ
+                0x50..=0x55 => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    let result = match opcode {
+                        0x50 => a < b,
+                        0x51 => a <= b,
+                        0x52 => a > b,
+                        0x53 => a >= b,
+                        0x54 => a == b,
+                        _ => a != b,
+                    };
+                    self.push(result as i32)?;
+                }
+                // ODD, EVEN
+                0x56 | 0x57 => {
+                    let value = self.pop()?;
+                    let odd = (self.round(value) & 127) == 64;
+                    self.push((odd == (opcode == 0x56)) as i32)?;
+                }
+                // IF
+                0x58 => {
+                    if self.pop()? == 0 {
+                        ip = skip_conditional(code, ip, end, true)?;
+                    }
+                }
+                // EIF
+                0x59 => {}
+                // AND, OR
+                0x5A | 0x5B => {
+                    let b = self.pop()? != 0;
+                    let a = self.pop()? != 0;
+                    let result = if opcode == 0x5A { a && b } else { a || b };
+                    self.push(result as i32)?;
+                }
+                // NOT
+                0x5C => {
+                    let value = self.pop()?;
+                    self.push((value == 0) as i32)?;
+                }
+                // DELTAP1, DELTAP2, DELTAP3
+                0x5D | 0x71 | 0x72 => {
+                    let range = match opcode { 0x5D => 0, 0x71 => 16, _ => 32 };
+                    let count = self.pop()?;
+                    for _ in 0..count.max(0) {
+                        let point = self.pop()?;
+                        let argument = self.pop()?;
+                        // Out of range points are skipped rather than failing the whole program
+                        let point = match self.reference(self.state.zp0, point as usize) {
+                            Ok(point) => point,
+                            Err(_) => continue,
+                        };
+                        if let Some(amount) = self.delta_amount(argument, range) {
+                            self.move_point(self.state.zp0, point, amount, true);
+                        }
+                    }
+                }
+                // SDB, SDS
+                0x5E => self.state.delta_base = self.pop()?,
+                0x5F => self.state.delta_shift = self.pop()?.clamp(0, 6),
+                // ADD, SUB, DIV, MUL
+                0x60..=0x63 => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    let result = match opcode {
+                        0x60 => a.wrapping_add(b),
+                        0x61 => a.wrapping_sub(b),
+                        0x62 => {
+                            if b == 0 {
+                                return Err("division by zero".into());
+                            }
+                            mul_div(a, 64, b)
+                        }
+                        _ => mul_div(a, b, 64),
+                    };
+                    self.push(result)?;
+                }
+                // ABS, NEG, FLOOR, CEILING
+                0x64..=0x67 => {
+                    let value = self.pop()?;
+                    let result = match opcode {
+                        0x64 => value.wrapping_abs(),
+                        0x65 => value.wrapping_neg(),
+                        0x66 => value & !63,
+                        _ => value.wrapping_add(63) & !63,
+                    };
+                    self.push(result)?;
+                }
+                // ROUND
+                0x68..=0x6B => {
+                    let value = self.pop()?;
+                    let rounded = self.round(value);
+                    self.push(rounded)?;
+                }
+                // NROUND applies engine compensation only, which is zero for every distance type here
+                0x6C..=0x6F => {}
+                // DELTAC1, DELTAC2, DELTAC3
+                0x73..=0x75 => {
+                    let range = (opcode as i32 - 0x73) * 16;
+                    let count = self.pop()?;
+                    for _ in 0..count.max(0) {
+                        let cvt_index = self.pop()?;
+                        let argument = self.pop()?;
+                        if let Some(amount) = self.delta_amount(argument, range) {
+                            let value = self.read_cvt(cvt_index)?;
+                            self.write_cvt(cvt_index, value + amount)?;
+                        }
+                    }
+                }
+                // SROUND, S45ROUND
+                0x76 | 0x77 => {
+                    let selector = self.pop()?;
+                    self.set_super_round(selector, opcode == 0x77);
+                }
+                // JROT, JROF
+                0x78 | 0x79 => {
+                    let condition = self.pop()? != 0;
+                    let offset = self.pop()?;
+                    if condition == (opcode == 0x78) {
+                        ip = jump_target(opcode_position, offset, end)?;
+                    }
+                }
+                // ROFF, RUTG, RDTG
+                0x7A => self.state.round_state = RoundState::Off,
+                0x7C => self.state.round_state = RoundState::UpToGrid,
+                0x7D => self.state.round_state = RoundState::DownToGrid,
+                // SANGW, AA are obsolete and only consume their argument
+                0x7E | 0x7F => {
+                    self.pop()?;
+                }
+                // FLIPPT
+                0x80 => {
+                    for _ in 0..self.take_loop() {
+                        let point = self.pop_point(GLYPH_ZONE)?;
+                        let on_curve = &mut self.zones[GLYPH_ZONE].on_curve[point];
+                        *on_curve = !*on_curve;
+                    }
+                }
+                // FLIPRGON, FLIPRGOFF
+                0x81 | 0x82 => {
+                    let high = self.pop_point(GLYPH_ZONE)?;
+                    let low = self.pop_point(GLYPH_ZONE)?;
+                    for point in low..=high {
+                        self.zones[GLYPH_ZONE].on_curve[point] = opcode == 0x81;
+                    }
+                }
+                // SCANCTRL, SCANTYPE only affect dropout control, which the rasterizer does not do
+                0x85 | 0x8D => {
+                    self.pop()?;
+                }
+                // SDPVTL
+                0x86 | 0x87 => {
+                    let p2 = self.pop_point(self.state.zp2)?;
+                    let p1 = self.pop_point(self.state.zp1)?;
+                    let rotate = (opcode & 1) != 0;
+                    self.state.dual_projection_vector = line_vector(
+                        self.zones[self.state.zp1].original[p1],
+                        self.zones[self.state.zp2].original[p2],
+                        rotate,
+                    );
+                    self.state.projection_vector = line_vector(
+                        self.zones[self.state.zp1].current[p1],
+                        self.zones[self.state.zp2].current[p2],
+                        rotate,
+                    );
+                }
+                // GETINFO
+                0x88 => {
+                    let selector = self.pop()?;
+                    let mut result = 0;
+                    if (selector & 1) != 0 {
+                        result |= 35; // Interpreter version, matching classic Windows grayscale hinting
+                    }
+                    if (selector & 32) != 0 {
+                        result |= 1 << 12; // Rendering in grayscale
+                    }
+                    self.push(result)?;
+                }
+                // IDEF
+                0x89 => {
+                    if self.program == ProgramKind::Glyph {
+                        return Err("IDEF is not allowed in glyph programs".into());
+                    }
+                    let defined_opcode = self.pop()?;
+                    let body_end = skip_definition(code, ip, end)?;
+                    self.instruction_defs.insert(defined_opcode as u8, Definition { code: code.clone(), start: ip, end: body_end });
+                    ip = body_end + 1;
+                }
+                // ROLL
+                0x8A => {
+                    let c = self.pop()?;
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    self.push(b)?;
+                    self.push(c)?;
+                    self.push(a)?;
+                }
+                // MAX, MIN
+                0x8B | 0x8C => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    self.push(if opcode == 0x8B { a.max(b) } else { a.min(b) })?;
+                }
+                // INSTCTRL
+                0x8E => {
+                    let selector = self.pop()?;
+                    let value = self.pop()?;
+                    if self.program == ProgramKind::ControlValue && (1..=2).contains(&selector) {
+                        let bit = 1 << (selector - 1);
+                        self.state.instruct_control = if value != 0 {
+                            self.state.instruct_control | bit
+                        } else {
+                            self.state.instruct_control & !bit
+                        };
+                    }
+                }
+                // PUSHB[abc], PUSHW[abc]
+                0xB0..=0xB7 => ip = self.push_inline(code, ip, (opcode - 0xAF) as usize, false)?,
+                0xB8..=0xBF => ip = self.push_inline(code, ip, (opcode - 0xB7) as usize, true)?,
+                // MDRP[abcde]
+                0xC0..=0xDF => self.move_direct_relative(opcode)?,
+                // MIRP[abcde]
+                0xE0..=0xFF => self.move_indirect_relative(opcode)?,
+                _ => {
+                    let definition = self.instruction_defs.get(&opcode).cloned()
+                        .ok_or_else(|| format!("unknown opcode 0x{:02X}", opcode))?;
+                    self.execute(&definition.code, definition.start, definition.end, depth + 1)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn push(&mut self, value: i32) -> Result<(), String> {
+        if self.stack.len() >= self.max_stack_elements {
+            return Err("stack overflow".into());
+        }
+        self.stack.push(value);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<i32, String> {
+        self.stack.pop().ok_or_else(|| "stack underflow".to_string())
+    }
+
+    // Reads the element `index` places from the top, where 1 is the top of the stack
+    fn peek(&self, index: i32) -> Result<i32, String> {
+        usize::try_from(index).ok()
+            .filter(|&index| index >= 1 && index <= self.stack.len())
+            .map(|index| self.stack[self.stack.len() - index])
+            .ok_or_else(|| format!("stack index {} out of range", index))
+    }
+
+    fn push_inline(&mut self, code: &[u8], start: usize, count: usize, words: bool) -> Result<usize, String> {
+        let size = if words { 2 } else { 1 };
+        let end = start + count * size;
+        if end > code.len() {
+            return Err("truncated push instruction".into());
+        }
+        for i in 0..count {
+            let value = if words {
+                i16::from_be_bytes([code[start + i * 2], code[start + i * 2 + 1]]) as i32
+            } else {
+                code[start + i] as i32
+            };
+            self.push(value)?;
+        }
+        Ok(end)
+    }
+
+    fn pop_zone(&mut self) -> Result<usize, String> {
+        match self.pop()? {
+            0 => Ok(TWILIGHT_ZONE),
+            1 => Ok(GLYPH_ZONE),
+            zone => Err(format!("invalid zone {}", zone)),
+        }
+    }
+
+    fn pop_point(&mut self, zone: usize) -> Result<usize, String> {
+        let value = self.pop()?;
+        self.reference(zone, value as usize)
+            .map_err(|_| format!("point {} out of range in zone {}", value, zone))
+    }
+
+    fn reference(&self, zone: usize, point: usize) -> Result<usize, String> {
+        if point < self.zones[zone].current.len() {
+            Ok(point)
+        } else {
+            Err(format!("point {} out of range in zone {}", point, zone))
+        }
+    }
+
+    // Returns how many times a looping instruction should run and resets the loop counter
+    fn take_loop(&mut self) -> i32 {
+        let count = self.state.loop_count;
+        self.state.loop_count = 1;
+        count
+    }
+
+    fn read_cvt(&self, index: i32) -> Result<i32, String> {
+        usize::try_from(index).ok()
+            .and_then(|index| self.cvt.get(index).copied())
+            .ok_or_else(|| format!("cvt index {} out of range", index))
+    }
+
+    fn write_cvt(&mut self, index: i32, value: i32) -> Result<(), String> {
+        let slot = usize::try_from(index).ok()
+            .and_then(|index| self.cvt.get_mut(index))
+            .ok_or_else(|| format!("cvt index {} out of range", index))?;
+        *slot = value;
+        Ok(())
+    }
+
+    // Decodes a DELTA argument, returning the 26.6 amount if it applies at the current size
+    fn delta_amount(&self, argument: i32, range: i32) -> Option<i32> {
+        let target_ppem = self.state.delta_base.wrapping_add(range + ((argument & 0xF0) >> 4));
+        if target_ppem != self.ppem as i32 {
+            return None;
+        }
+        let mut steps = (argument & 0x0F) - 8;
+        if steps >= 0 {
+            steps += 1;
+        }
+        Some(steps * (1 << (6 - self.state.delta_shift)))
+    }
+
+    fn project(&self, a: (i32, i32), b: (i32, i32)) -> i32 {
+        dot_2dot14((a.0.wrapping_sub(b.0), a.1.wrapping_sub(b.1)), self.state.projection_vector)
+    }
+
+    fn dual_project(&self, a: (i32, i32), b: (i32, i32)) -> i32 {
+        dot_2dot14((a.0.wrapping_sub(b.0), a.1.wrapping_sub(b.1)), self.state.dual_projection_vector)
+    }
+
+    fn freedom_dot_projection(&self) -> i32 {
+        let (px, py) = self.state.projection_vector;
+        let (fx, fy) = self.state.freedom_vector;
+        let dot = ((px as i64 * fx as i64 + py as i64 * fy as i64) >> 14) as i32;
+        // Nearly perpendicular vectors would make moves explode, so treat them as parallel
+        if dot.abs() < 0x400 { ONE_2DOT14 } else { dot }
+    }
+
+    // Moves a point along the freedom vector so its projection changes by `distance`. Distances
+    // come from the font's bytecode, so coordinates wrap like the stack arithmetic does rather
+    // than overflow.
+    fn move_point(&mut self, zone: usize, point: usize, distance: i32, touch: bool) {
+        let (fx, fy) = self.state.freedom_vector;
+        let dot = self.freedom_dot_projection();
+        let zone = &mut self.zones[zone];
+        if fx != 0 {
+            zone.current[point].0 = zone.current[point].0.wrapping_add(mul_div(distance, fx, dot));
+            if touch {
+                zone.touched[point] |= TOUCHED_X;
+            }
+        }
+        if fy != 0 {
+            zone.current[point].1 = zone.current[point].1.wrapping_add(mul_div(distance, fy, dot));
+            if touch {
+                zone.touched[point] |= TOUCHED_Y;
+            }
+        }
+    }
+
+    fn shift_point(&mut self, zone: usize, point: usize, dx: i32, dy: i32, touch: bool) {
+        let (fx, fy) = self.state.freedom_vector;
+        let zone = &mut self.zones[zone];
+        if fx != 0 {
+            zone.current[point].0 = zone.current[point].0.wrapping_add(dx);
+            if touch {
+                zone.touched[point] |= TOUCHED_X;
+            }
+        }
+        if fy != 0 {
+            zone.current[point].1 = zone.current[point].1.wrapping_add(dy);
+            if touch {
+                zone.touched[point] |= TOUCHED_Y;
+            }
+        }
+    }
+
+    // SHP, SHC and SHZ use rp1 in zp0 when the opcode's low bit is set, rp2 in zp1 otherwise
+    fn reference_point(&self, use_rp1: bool) -> (usize, usize) {
+        if use_rp1 {
+            (self.state.zp0, self.state.rp1)
+        } else {
+            (self.state.zp1, self.state.rp2)
+        }
+    }
+
+    fn reference_displacement(&self, use_rp1: bool) -> Result<(i32, i32), String> {
+        let (zone, point) = self.reference_point(use_rp1);
+        let point = self.reference(zone, point)?;
+        let zone = &self.zones[zone];
+        let distance = self.project(zone.current[point], zone.original[point]);
+        let dot = self.freedom_dot_projection();
+        let (fx, fy) = self.state.freedom_vector;
+        Ok((mul_div(distance, fx, dot), mul_div(distance, fy, dot)))
+    }
+
+    fn round(&self, distance: i32) -> i32 {
+        let state = &self.state;
+        let round_with = |distance: i32, f: &dyn Fn(i32) -> i32| -> i32 {
+            if distance >= 0 {
+                f(distance).max(0)
+            } else {
+                f(distance.wrapping_neg()).wrapping_neg().min(0)
+            }
+        };
+        match state.round_state {
+            RoundState::ToGrid => round_with(distance, &|d| d.wrapping_add(32) & !63),
+            RoundState::ToHalfGrid => round_with(distance, &|d| (d & !63).wrapping_add(32)),
+            RoundState::ToDoubleGrid => round_with(distance, &|d| d.wrapping_add(16) & !31),
+            RoundState::DownToGrid => round_with(distance, &|d| d & !63),
+            RoundState::UpToGrid => round_with(distance, &|d| d.wrapping_add(63) & !63),
+            RoundState::Off => distance,
+            RoundState::Super => {
+                if distance >= 0 {
+                    let value = (distance.wrapping_sub(state.phase).wrapping_add(state.threshold) & -state.period).wrapping_add(state.phase);
+                    if value < 0 { state.phase } else { value }
+                } else {
+                    let value = (state.threshold.wrapping_sub(state.phase).wrapping_sub(distance) & -state.period).wrapping_neg().wrapping_sub(state.phase);
+                    if value > 0 { -state.phase } else { value }
+                }
+            }
+            RoundState::Super45 => {
+                if distance >= 0 {
+                    let value = (distance.wrapping_sub(state.phase).wrapping_add(state.threshold) / state.period).wrapping_mul(state.period).wrapping_add(state.phase);
+                    if value < 0 { state.phase } else { value }
+                } else {
+                    let value = (state.threshold.wrapping_sub(state.phase).wrapping_sub(distance) / state.period).wrapping_mul(state.period).wrapping_neg().wrapping_sub(state.phase);
+                    if value > 0 { -state.phase } else { value }
+                }
+            }
+        }
+    }
+
+    fn set_super_round(&mut self, selector: i32, is_45: bool) {
+        let grid_period = if is_45 { 45 } else { 64 };
+        let period = match selector & 0xC0 {
+            0x00 => grid_period / 2,
+            0x40 => grid_period,
+            0x80 => grid_period * 2,
+            _ => grid_period,
+        };
+        let phase = match selector & 0x30 {
+            0x00 => 0,
+            0x10 => period / 4,
+            0x20 => period / 2,
+            _ => period * 3 / 4,
+        };
+        let threshold = if (selector & 0x0F) == 0 {
+            period - 1
+        } else {
+            ((selector & 0x0F) - 4) * period / 8
+        };
+        self.state.period = period;
+        self.state.phase = phase;
+        self.state.threshold = threshold;
+        self.state.round_state = if is_45 { RoundState::Super45 } else { RoundState::Super };
+    }
+
+    fn apply_single_width(&self, distance: i32) -> i32 {
+        let single_width = self.state.single_width_value;
+        let signed_width = if distance >= 0 { single_width } else { -single_width };
+        if distance.wrapping_sub(signed_width).wrapping_abs() < self.state.single_width_cutin {
+            signed_width
+        } else {
+            distance
+        }
+    }
+
+    fn apply_minimum_distance(&self, original_distance: i32, distance: i32) -> i32 {
+        let minimum = self.state.minimum_distance;
+        if original_distance >= 0 {
+            distance.max(minimum)
+        } else {
+            distance.min(-minimum)
+        }
+    }
+
+    fn move_direct_relative(&mut self, opcode: u8) -> Result<(), String> {
+        let point = self.pop_point(self.state.zp1)?;
+        let rp0 = self.reference(self.state.zp0, self.state.rp0)?;
+
+        let original_distance = self.apply_single_width(self.dual_project(
+            self.zones[self.state.zp1].original[point],
+            self.zones[self.state.zp0].original[rp0],
+        ));
+        let mut distance = if (opcode & 0x04) != 0 { self.round(original_distance) } else { original_distance };
+        if (opcode & 0x08) != 0 {
+            distance = self.apply_minimum_distance(original_distance, distance);
+        }
+
+        let current = self.project(self.zones[self.state.zp1].current[point], self.zones[self.state.zp0].current[rp0]);
+        self.move_point(self.state.zp1, point, distance.wrapping_sub(current), true);
+
+        self.state.rp1 = self.state.rp0;
+        self.state.rp2 = point;
+        if (opcode & 0x10) != 0 {
+            self.state.rp0 = point;
+        }
+        Ok(())
+    }
+
+    fn move_indirect_relative(&mut self, opcode: u8) -> Result<(), String> {
+        let cvt_index = self.pop()?;
+        let point = self.pop_point(self.state.zp1)?;
+        let rp0 = self.reference(self.state.zp0, self.state.rp0)?;
+
+        let mut cvt_distance = if cvt_index == -1 { 0 } else { self.apply_single_width(self.read_cvt(cvt_index)?) };
+
+        if self.state.zp1 == TWILIGHT_ZONE {
+            let (fx, fy) = self.state.freedom_vector;
+            let base = self.zones[self.state.zp0].original[rp0];
+            let position = (base.0.wrapping_add(mul_2dot14(cvt_distance, fx)), base.1.wrapping_add(mul_2dot14(cvt_distance, fy)));
+            let zone = &mut self.zones[TWILIGHT_ZONE];
+            zone.original[point] = position;
+            zone.current[point] = position;
+        }
+
+        let original_distance = self.dual_project(
+            self.zones[self.state.zp1].original[point],
+            self.zones[self.state.zp0].original[rp0],
+        );
+        let current = self.project(self.zones[self.state.zp1].current[point], self.zones[self.state.zp0].current[rp0]);
+
+        if self.state.auto_flip && (original_distance ^ cvt_distance) < 0 {
+            cvt_distance = cvt_distance.wrapping_neg();
+        }
+
+        let mut distance = if (opcode & 0x04) != 0 {
+            if self.state.zp0 == self.state.zp1 && cvt_distance.wrapping_sub(original_distance).wrapping_abs() > self.state.control_value_cutin {
+                cvt_distance = original_distance;
+            }
+            self.round(cvt_distance)
+        } else {
+            cvt_distance
+        };
+        if (opcode & 0x08) != 0 {
+            distance = self.apply_minimum_distance(original_distance, distance);
+        }
+
+        self.move_point(self.state.zp1, point, distance.wrapping_sub(current), true);
+
+        self.state.rp1 = self.state.rp0;
+        self.state.rp2 = point;
+        if (opcode & 0x10) != 0 {
+            self.state.rp0 = point;
+        }
+        Ok(())
+    }
+
+    fn interpolate_points(&mut self) -> Result<(), String> {
+        let rp1 = self.reference(self.state.zp0, self.state.rp1)?;
+        let rp2 = self.reference(self.state.zp1, self.state.rp2)?;
+        let original_base = self.zones[self.state.zp0].original[rp1];
+        let current_base = self.zones[self.state.zp0].current[rp1];
+        let original_range = self.dual_project(self.zones[self.state.zp1].original[rp2], original_base);
+        let current_range = self.project(self.zones[self.state.zp1].current[rp2], current_base);
+
+        for _ in 0..self.take_loop() {
+            let point = self.pop_point(self.state.zp2)?;
+            let original_distance = self.dual_project(self.zones[self.state.zp2].original[point], original_base);
+            let current_distance = self.project(self.zones[self.state.zp2].current[point], current_base);
+            let new_distance = if original_distance == 0 {
+                0
+            } else if original_range != 0 {
+                mul_div(original_distance, current_range, original_range)
+            } else {
+                original_distance
+            };
+            self.move_point(self.state.zp2, point, new_distance.wrapping_sub(current_distance), true);
+        }
+        Ok(())
+    }
+
+    fn intersect(&mut self, point: usize, a0: usize, a1: usize, b0: usize, b1: usize) {
+        let (a0, a1) = (self.zones[self.state.zp1].current[a0], self.zones[self.state.zp1].current[a1]);
+        let (b0, b1) = (self.zones[self.state.zp0].current[b0], self.zones[self.state.zp0].current[b1]);
+
+        let (dax, day) = (a1.0.wrapping_sub(a0.0), a1.1.wrapping_sub(a0.1));
+        let (dbx, dby) = (b1.0.wrapping_sub(b0.0), b1.1.wrapping_sub(b0.1));
+        let (dx, dy) = (b0.0.wrapping_sub(a0.0), b0.1.wrapping_sub(a0.1));
+
+        let discriminant = mul_div(dax, dby.wrapping_neg(), 64).wrapping_add(mul_div(day, dbx, 64));
+        let dot_product = mul_div(dax, dbx, 64).wrapping_add(mul_div(day, dby, 64));
+
+        // Only intersect lines that are not close to parallel, otherwise use the middle of the points
+        let position = if 19 * (discriminant as i64).abs() > (dot_product as i64).abs() {
+            let value = mul_div(dx, dby.wrapping_neg(), 64).wrapping_add(mul_div(dy, dbx, 64));
+            (a0.0.wrapping_add(mul_div(value, dax, discriminant)), a0.1.wrapping_add(mul_div(value, day, discriminant)))
+        } else {
+            let average = |values: [i32; 4]| (values.iter().map(|&value| value as i64).sum::<i64>() / 4) as i32;
+            (average([a0.0, a1.0, b0.0, b1.0]), average([a0.1, a1.1, b0.1, b1.1]))
+        };
+
+        let zone = &mut self.zones[self.state.zp2];
+        zone.current[point] = position;
+        zone.touched[point] |= TOUCHED_X | TOUCHED_Y;
+    }
+
+    // IUP: moves untouched points of each contour in proportion to the touched points around them
+    fn interpolate_untouched(&mut self, x_axis: bool) {
+        let touched_flag = if x_axis { TOUCHED_X } else { TOUCHED_Y };
+        let coordinate = |point: (i32, i32)| if x_axis { point.0 } else { point.1 };
+        let zone = &mut self.zones[GLYPH_ZONE];
+
+        let mut first = 0;
+        for contour in 0..zone.end_pts_of_contours.len() {
+            let last = zone.end_pts_of_contours[contour];
+            if last < first || last >= zone.current.len() {
+                break;
+            }
+
+            let touched: Vec<usize> = (first..=last).filter(|&p| (zone.touched[p] & touched_flag) != 0).collect();
+            if touched.len() == 1 {
+                let p = touched[0];
+                let delta = coordinate(zone.current[p]).wrapping_sub(coordinate(zone.original[p]));
+                for q in first..=last {
+                    if q != p {
+                        let value = coordinate(zone.original[q]).wrapping_add(delta);
+                        set_coordinate(&mut zone.current[q], x_axis, value);
+                    }
+                }
+            } else if touched.len() > 1 {
+                for (i, &start) in touched.iter().enumerate() {
+                    let stop = touched[(i + 1) % touched.len()];
+                    // Walk the untouched points between consecutive touched points, wrapping around the contour
+                    let mut q = if start == last { first } else { start + 1 };
+                    while q != stop {
+                        let value = interpolate_coordinate(
+                            coordinate(zone.original[q]),
+                            (coordinate(zone.original[start]), coordinate(zone.current[start])),
+                            (coordinate(zone.original[stop]), coordinate(zone.current[stop])),
+                        );
+                        set_coordinate(&mut zone.current[q], x_axis, value);
+                        q = if q == last { first } else { q + 1 };
+                    }
+                }
+            }
+
+            first = last + 1;
+        }
+    }
+}
+
+fn set_coordinate(point: &mut (i32, i32), x_axis: bool, value: i32) {
+    if x_axis {
+        point.0 = value;
+    } else {
+        point.1 = value;
+    }
+}
+
+// Interpolates between two touched reference points given as (original, current) pairs
+fn interpolate_coordinate(original: i32, a: (i32, i32), b: (i32, i32)) -> i32 {
+    let ((original1, current1), (original2, current2)) = if a.0 <= b.0 { (a, b) } else { (b, a) };
+    if original <= original1 {
+        original.wrapping_add(current1.wrapping_sub(original1))
+    } else if original >= original2 {
+        original.wrapping_add(current2.wrapping_sub(original2))
+    } else {
+        current1.wrapping_add(mul_div(original.wrapping_sub(original1), current2.wrapping_sub(current1), original2.wrapping_sub(original1)))
+    }
+}
+
+fn round_pixel(value: i32) -> i32 {
+    (value + 32) & !63
+}
+
+fn mul_div(a: i32, b: i32, c: i32) -> i32 {
+    if c == 0 {
+        return if (a < 0) != (b < 0) { -0x7FFF_FFFF } else { 0x7FFF_FFFF };
+    }
+    let numerator = a as i64 * b as i64;
+    let denominator = c as i64;
+    let sign = numerator.signum() * denominator.signum();
+    let value = (numerator.abs() + denominator.abs() / 2) / denominator.abs();
+    (sign * value).clamp(i32::MIN as i64, i32::MAX as i64) as i32
+}
+
+fn mul_2dot14(value: i32, factor: i32) -> i32 {
+    ((value as i64 * factor as i64 + 0x2000) >> 14) as i32
+}
+
+fn dot_2dot14(vector: (i32, i32), unit: (i32, i32)) -> i32 {
+    ((vector.0 as i64 * unit.0 as i64 + vector.1 as i64 * unit.1 as i64 + 0x2000) >> 14) as i32
+}
+
+fn normalize(x: i32, y: i32) -> (i32, i32) {
+    let length = ((x as f64).powi(2) + (y as f64).powi(2)).sqrt();
+    if length == 0.0 {
+        return (ONE_2DOT14, 0);
+    }
+    (
+        (x as f64 * ONE_2DOT14 as f64 / length).round() as i32,
+        (y as f64 * ONE_2DOT14 as f64 / length).round() as i32,
+    )
+}
+
+// Unit vector pointing from `b` to `a`, rotated counter-clockwise when `perpendicular` is set
+fn line_vector(a: (i32, i32), b: (i32, i32), perpendicular: bool) -> (i32, i32) {
+    let (dx, dy) = (a.0.wrapping_sub(b.0), a.1.wrapping_sub(b.1));
+    if dx == 0 && dy == 0 {
+        return (ONE_2DOT14, 0);
+    }
+    if perpendicular {
+        normalize(dy.wrapping_neg(), dx)
+    } else {
+        normalize(dx, dy)
+    }
+}
+
+fn jump_target(opcode_position: usize, offset: i32, end: usize) -> Result<usize, String> {
+    let target = opcode_position as i64 + offset as i64;
+    if offset == 0 || target < 0 || target as usize > end {
+        return Err(format!("invalid jump offset {}", offset));
+    }
+    Ok(target as usize)
+}
+
+// Size of the instruction starting at `ip`, including inline push data
+fn instruction_length(code: &[u8], ip: usize) -> Result<usize, String> {
+    let opcode = code[ip];
+    let length = match opcode {
+        0x40 => 2 + *code.get(ip + 1).ok_or("truncated push instruction")? as usize,
+        0x41 => 2 + 2 * *code.get(ip + 1).ok_or("truncated push instruction")? as usize,
+        0xB0..=0xB7 => 1 + (opcode - 0xAF) as usize,
+        0xB8..=0xBF => 1 + 2 * (opcode - 0xB7) as usize,
+        _ => 1,
+    };
+    Ok(length)
+}
+
+// Skips to just past the ELSE (when `stop_at_else`) or EIF matching the current IF level
+fn skip_conditional(code: &[u8], mut ip: usize, end: usize, stop_at_else: bool) -> Result<usize, String> {
+    let mut nesting = 0;
+    while ip < end {
+        let opcode = code[ip];
+        let length = instruction_length(code, ip)?;
+        match opcode {
+            0x58 => nesting += 1,
+            0x1B if nesting == 0 && stop_at_else => return Ok(ip + length),
+            0x59 => {
+                if nesting == 0 {
+                    return Ok(ip + length);
+                }
+                nesting -= 1;
+            }
+            _ => {}
+        }
+        ip += length;
+    }
+    Err("missing EIF".into())
+}
+
+// Returns the position of the ENDF closing the definition whose body starts at `ip`
+fn skip_definition(code: &[u8], mut ip: usize, end: usize) -> Result<usize, String> {
+    while ip < end {
+        match code[ip] {
+            0x2D => return Ok(ip),
+            0x2C | 0x89 => return Err("nested function definition".into()),
+            _ => ip += instruction_length(code, ip)?,
+        }
+    }
+    Err("missing ENDF".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 1000 units per em at 10 ppem, so a font unit is 0.64/64 of a pixel
+    const PPEM: u16 = 10;
+
+    const SVTCA_Y: u8 = 0x00;
+    const SVTCA_X: u8 = 0x01;
+    const PUSHB_1: u8 = 0xB0;
+    const PUSHB_2: u8 = 0xB1;
+    const PUSHB_3: u8 = 0xB2;
+    const PUSHW_1: u8 = 0xB8;
+    const PUSHW_2: u8 = 0xB9;
+
+    fn maxp_table() -> MaxpTable {
+        MaxpTable {
+            num_glyphs: 1,
+            max_points: 4,
+            max_contours: 1,
+            max_composite_points: 0,
+            max_composite_contours: 0,
+            max_zones: 1,
+            max_twilight_points: 0,
+            max_storage: 0,
+            max_function_defs: 1,
+            max_instruction_defs: 0,
+            max_stack_elements: 16,
+            max_size_of_instructions: 64,
+            max_component_elements: 0,
+            max_component_depth: 0,
+        }
+    }
+
+    fn hinter(fpgm: &[u8], prep: &[u8], cvt: &[i16]) -> Hinter {
+        let head_table = HeadTable { units_per_em: 1000, index_to_loc_format: 0 };
+        Hinter::new(&head_table, &maxp_table(), fpgm, prep, cvt, PPEM).unwrap()
+    }
+
+    // A stem 380 units wide and 710 tall, which lands between pixels at 10 ppem
+    fn rectangle(instructions: &[u8]) -> Glyph {
+        let x_coordinates = vec![100, 100, 480, 480];
+        let y_coordinates = vec![0, 710, 710, 0];
+        let flags = vec![1; 4];
+        let raw_end_pts_of_contours = vec![3];
+        let (processed_points, end_pts_of_contours) = process_points(&x_coordinates, &y_coordinates, &flags, &raw_end_pts_of_contours);
+        Glyph {
+            glyph_index: 0,
+            num_contours: 1,
+            xmin: 100,
+            ymin: 0,
+            xmax: 480,
+            ymax: 710,
+            end_pts_of_contours,
+            x_coordinates,
+            y_coordinates,
+            flags,
+            processed_points,
+            raw_end_pts_of_contours,
+            instructions: instructions.to_vec(),
+            advance_width: 600.0,
+            left_side_bearing: 100,
+        }
+    }
+
+    fn hint(hinter: &mut Hinter, instructions: &[u8]) -> HintedGlyph {
+        hinter.hint_glyph(&rectangle(instructions)).unwrap()
+    }
+
+    #[test]
+    fn unhinted_points_are_only_scaled() {
+        let hinted = hint(&mut hinter(&[], &[], &[]), &[]);
+        assert_eq!(hinted.points, [(1.0, 0.0), (1.0, 7.09375), (4.796875, 7.09375), (4.796875, 0.0)]);
+        // The phantom points are rounded to the grid, so the advance is too
+        assert_eq!(hinted.advance_width, 6.0);
+    }
+
+    #[test]
+    fn stem_edges_snap_to_the_grid() {
+        let hinted = hint(&mut hinter(&[], &[], &[710]), &[
+            // Baseline rounded in place, the top to the rounded cvt height, then the rest follows
+            SVTCA_Y, PUSHB_1, 0, 0x2F, PUSHB_2, 1, 0, 0x3F, 0x30,
+            // Left edge rounded, the right edge a rounded distance from it
+            SVTCA_X, PUSHB_1, 0, 0x2F, PUSHB_1, 3, 0xC4, 0x31,
+        ]);
+        assert_eq!(hinted.points, [(1.0, 0.0), (1.0, 7.0), (5.0, 7.0), (5.0, 0.0)]);
+    }
+
+    #[test]
+    fn glyph_programs_call_font_functions_and_see_control_value_changes() {
+        // fpgm: function 0 is MIAP[rnd]. prep: cvt 0 becomes 8 pixels.
+        let fpgm = [PUSHB_1, 0, 0x2C, 0x3F, 0x2D];
+        let prep = [PUSHB_1, 0, PUSHW_1, 0x02, 0x00, 0x44];
+        let hinted = hint(&mut hinter(&fpgm, &prep, &[710]), &[SVTCA_Y, PUSHB_3, 1, 0, 0, 0x2B]);
+        assert_eq!(hinted.points[1], (1.0, 8.0));
+        // Without IUP the other points stay where they were
+        assert_eq!(hinted.points[2], (4.796875, 7.09375));
+    }
+
+    #[test]
+    fn huge_shifts_from_bytecode_wrap_instead_of_overflowing() {
+        // Two SHPIX moves of 2^30 pixels/64 each on the same point
+        let program = [
+            SVTCA_X, PUSHB_2, 0, 0, PUSHW_2, 0x40, 0x00, 0x40, 0x00, 0x63, PUSHW_1, 0x40, 0x00, 0x63,
+            PUSHB_1, 2, 0x17, 0x38,
+        ];
+        assert!(hinter(&[], &[], &[]).hint_glyph(&rectangle(&program)).is_ok());
+    }
+
+    #[test]
+    fn zero_ppem_is_rejected() {
+        let head_table = HeadTable { units_per_em: 1000, index_to_loc_format: 0 };
+        assert!(Hinter::new(&head_table, &maxp_table(), &[], &[], &[], 0).is_err());
+    }
+}
@@ -0,0 +1,182 @@
+use crate::lcd::LcdBitmap;
+use crate::raster::GlyphBitmap;
+
+// PNG stores uncompressed deflate blocks of at most this many bytes
+const MAX_STORED_BLOCK: usize = 65535;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const BLACK: Color = Color { r: 0, g: 0, b: 0 };
+    pub const WHITE: Color = Color { r: 255, g: 255, b: 255 };
+
+    // Parses "rrggbb", with or without a leading '#'
+    pub fn from_hex(hex: &str) -> Result<Color, String> {
+        let digits = hex.trim_start_matches('#');
+        if digits.len() != 6 || !digits.is_ascii() {
+            return Err(format!("Expected a colour like #rrggbb, got '{}'", hex));
+        }
+        let channel = |range: std::ops::Range<usize>| {
+            u8::from_str_radix(&digits[range], 16).map_err(|_| format!("Invalid hex colour '{}'", hex))
+        };
+        Ok(Color {
+            r: channel(0..2)?,
+            g: channel(2..4)?,
+            b: channel(4..6)?,
+        })
+    }
+
+    pub fn to_hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+
+    // Rec. 601 luma, used when writing greyscale output
+    pub fn luminance(&self) -> u8 {
+        ((self.r as u32 * 299 + self.g as u32 * 587 + self.b as u32 * 114 + 500) / 1000) as u8
+    }
+}
+
+// 8-bit RGB image, rows top to bottom
+#[derive(Debug, Clone)]
+pub struct RgbImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl RgbImage {
+    pub fn new(width: usize, height: usize, background: Color) -> Self {
+        let mut pixels = Vec::with_capacity(width * height * 3);
+        for _ in 0..width * height {
+            pixels.extend([background.r, background.g, background.b]);
+        }
+        RgbImage { width, height, pixels }
+    }
+
+    // Mixes `color` into the pixel in proportion to `coverage`; pixels outside the image are ignored
+    pub fn blend(&mut self, x: i32, y: i32, coverage: u8, color: Color) {
+        if coverage == 0 || x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return;
+        }
+        let index = (y as usize * self.width + x as usize) * 3;
+        let alpha = coverage as u32;
+        for (channel, target) in [color.r, color.g, color.b].iter().zip(&mut self.pixels[index..index + 3]) {
+            *target = ((*channel as u32 * alpha + *target as u32 * (255 - alpha) + 127) / 255) as u8;
+        }
+    }
+
+    // Like blend, with a separate coverage for each of red, green and blue
+    pub fn blend_channels(&mut self, x: i32, y: i32, coverage: [u8; 3], color: Color) {
+        if coverage == [0; 3] || x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return;
+        }
+        let index = (y as usize * self.width + x as usize) * 3;
+        for ((channel, alpha), target) in [color.r, color.g, color.b].iter().zip(coverage).zip(&mut self.pixels[index..index + 3]) {
+            let alpha = alpha as u32;
+            *target = ((*channel as u32 * alpha + *target as u32 * (255 - alpha) + 127) / 255) as u8;
+        }
+    }
+
+    // Draws a subpixel-rendered glyph bitmap, placed like draw_bitmap
+    pub fn draw_lcd_bitmap(&mut self, bitmap: &LcdBitmap, origin_x: i32, origin_y: i32, color: Color) {
+        for row in 0..bitmap.height {
+            for column in 0..bitmap.width {
+                let index = (row * bitmap.width + column) * 3;
+                let coverage = [bitmap.coverage[index], bitmap.coverage[index + 1], bitmap.coverage[index + 2]];
+                self.blend_channels(origin_x + bitmap.left + column as i32, origin_y - bitmap.top + row as i32, coverage, color);
+            }
+        }
+    }
+
+    // Draws a glyph bitmap with its origin at (`origin_x`, `origin_y`), y pointing down
+    pub fn draw_bitmap(&mut self, bitmap: &GlyphBitmap, origin_x: i32, origin_y: i32, color: Color) {
+        for row in 0..bitmap.height {
+            for column in 0..bitmap.width {
+                let coverage = bitmap.coverage[row * bitmap.width + column];
+                self.blend(origin_x + bitmap.left + column as i32, origin_y - bitmap.top + row as i32, coverage, color);
+            }
+        }
+    }
+
+    // Binary greyscale PGM (P5) from the luminance of each pixel
+    pub fn to_pgm(&self) -> Vec<u8> {
+        let mut data = format!("P5\n{} {}\n255\n", self.width, self.height).into_bytes();
+        data.extend(self.pixels.chunks(3).map(|pixel| Color { r: pixel[0], g: pixel[1], b: pixel[2] }.luminance()));
+        data
+    }
+
+    // 8-bit truecolour PNG. The image data goes into stored (uncompressed) deflate blocks, which
+    // keeps the encoder dependency free at the cost of file size.
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut data = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+        let mut header = Vec::with_capacity(13);
+        header.extend((self.width as u32).to_be_bytes());
+        header.extend((self.height as u32).to_be_bytes());
+        header.extend([8, 2, 0, 0, 0]); // Bit depth, colour type RGB, deflate, adaptive filter, no interlace
+        write_chunk(&mut data, b"IHDR", &header);
+
+        // Every scanline starts with filter type 0 (none)
+        let stride = self.width * 3;
+        let mut scanlines = Vec::with_capacity((stride + 1) * self.height);
+        for row in self.pixels.chunks(stride.max(1)).take(self.height) {
+            scanlines.push(0);
+            scanlines.extend_from_slice(row);
+        }
+        write_chunk(&mut data, b"IDAT", &zlib_stored(&scanlines));
+        write_chunk(&mut data, b"IEND", &[]);
+        data
+    }
+}
+
+fn write_chunk(data: &mut Vec<u8>, kind: &[u8; 4], contents: &[u8]) {
+    data.extend((contents.len() as u32).to_be_bytes());
+    let start = data.len();
+    data.extend_from_slice(kind);
+    data.extend_from_slice(contents);
+    let crc = crc32(&data[start..]);
+    data.extend(crc.to_be_bytes());
+}
+
+fn zlib_stored(bytes: &[u8]) -> Vec<u8> {
+    let mut data = vec![0x78, 0x01]; // Deflate with a 32K window, no preset dictionary
+    let mut blocks = bytes.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        data.extend([1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none();
+        data.push(is_final as u8);
+        let length = block.len() as u16;
+        data.extend(length.to_le_bytes());
+        data.extend((!length).to_le_bytes());
+        data.extend_from_slice(block);
+    }
+    data.extend(adler32(bytes).to_be_bytes());
+    data
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
@@ -0,0 +1,482 @@
+use crate::gsub::{GlyphInfo, SubstitutionPlan};
+
+// Features that form conjuncts and other consonant shapes, each applied on its own in this order
+pub const BASIC_FEATURES: [[u8; 4]; 11] = [
+    *b"nukt", *b"akhn", *b"rphf", *b"rkrf", *b"pref", *b"blwf", *b"abvf", *b"half", *b"pstf", *b"vatu", *b"cjct",
+];
+
+// Features applied together once the syllables are in their final order
+pub const PRESENTATION_FEATURES: [[u8; 4]; 6] = [*b"init", *b"pres", *b"abvs", *b"blws", *b"psts", *b"haln"];
+
+const DOTTED_CIRCLE: char = '\u{25CC}';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Category {
+    Other,
+    Consonant,
+    Ra,
+    Vowel,
+    Matra,
+    Nukta,
+    Halant,
+    // Anusvara, visarga, candrabindu and Vedic signs, which follow everything else
+    Modifier,
+    Zwj,
+    Zwnj,
+    Placeholder,
+}
+
+impl Category {
+    const ALL: [Category; 11] = [
+        Category::Other, Category::Consonant, Category::Ra, Category::Vowel, Category::Matra, Category::Nukta,
+        Category::Halant, Category::Modifier, Category::Zwj, Category::Zwnj, Category::Placeholder,
+    ];
+
+    // Characters a syllable can be built around
+    fn is_base(self) -> bool {
+        matches!(self, Category::Consonant | Category::Ra | Category::Vowel | Category::Placeholder)
+    }
+
+    fn is_joiner(self) -> bool {
+        matches!(self, Category::Zwj | Category::Zwnj)
+    }
+}
+
+// Where a character ends up within its syllable, in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Position {
+    Start,
+    RaToBecomeReph,
+    PreMatra,
+    PreConsonant,
+    BaseConsonant,
+    AfterMain,
+    AboveConsonant,
+    BeforeSub,
+    BelowConsonant,
+    AfterSub,
+    BeforePost,
+    PostConsonant,
+    AfterPost,
+    Smvd,
+    End,
+}
+
+impl Position {
+    const ALL: [Position; 15] = [
+        Position::Start, Position::RaToBecomeReph, Position::PreMatra, Position::PreConsonant, Position::BaseConsonant,
+        Position::AfterMain, Position::AboveConsonant, Position::BeforeSub, Position::BelowConsonant, Position::AfterSub,
+        Position::BeforePost, Position::PostConsonant, Position::AfterPost, Position::Smvd, Position::End,
+    ];
+}
+
+// Indic properties packed into GlyphInfo::properties: category in the low byte, position in the
+// next four bits, a flag for syllables starting a word, and the syllable's number in the top half
+const WORD_INITIAL: u32 = 1 << 12;
+
+fn pack(category: Category, position: Position, word_initial: bool, syllable: usize) -> u32 {
+    category as u32 | (position as u32) << 8 | if word_initial { WORD_INITIAL } else { 0 } | (syllable as u32 & 0xFFFF) << 16
+}
+
+fn category_of(info: &GlyphInfo) -> Category {
+    Category::ALL.get((info.properties & 0xFF) as usize).copied().unwrap_or(Category::Other)
+}
+
+fn position_of(info: &GlyphInfo) -> Position {
+    Position::ALL.get((info.properties >> 8 & 0xF) as usize).copied().unwrap_or(Position::End)
+}
+
+fn syllable_of(info: &GlyphInfo) -> u32 {
+    info.properties >> 16
+}
+
+fn set_position(info: &mut GlyphInfo, position: Position) {
+    info.properties = info.properties & !0xF00 | (position as u32) << 8;
+}
+
+// Devanagari and Bengali share the ISCII-derived layout of their blocks
+fn category(ch: char) -> Category {
+    let code_point = ch as u32;
+    match code_point {
+        0x00A0 | 0x25CC => return Category::Placeholder,
+        0x200C => return Category::Zwnj,
+        0x200D => return Category::Zwj,
+        // Bengali khanda ta is a consonant that never takes a vowel; Assamese ra and wa
+        0x09CE => return Category::Consonant,
+        0x09F0 => return Category::Ra,
+        0x09F1 => return Category::Consonant,
+        0x0900..=0x09FF => {}
+        _ => return Category::Other,
+    }
+    let devanagari = code_point < 0x0980;
+    match code_point & 0x7F {
+        0x00..=0x03 | 0x51..=0x54 => Category::Modifier,
+        0x04..=0x14 | 0x60 | 0x61 => Category::Vowel,
+        0x72..=0x77 if devanagari => Category::Vowel,
+        0x30 => Category::Ra,
+        0x15..=0x39 | 0x58..=0x5F => Category::Consonant,
+        0x78..=0x7F if devanagari => Category::Consonant,
+        0x3C => Category::Nukta,
+        0x4D => Category::Halant,
+        0x3A | 0x3B | 0x3E..=0x4C | 0x4E | 0x4F | 0x55..=0x57 | 0x62 | 0x63 => Category::Matra,
+        _ => Category::Other,
+    }
+}
+
+fn matra_position(ch: char) -> Position {
+    match ch as u32 {
+        0x093F | 0x094E | 0x09BF | 0x09C7 | 0x09C8 => Position::PreMatra,
+        // Devanagari matras above, below and after the base all go after below-base consonants,
+        // which puts them before the reph
+        0x0900..=0x097F => Position::AfterSub,
+        0x09C1..=0x09C4 | 0x09E2 | 0x09E3 => Position::BelowConsonant,
+        _ => Position::PostConsonant,
+    }
+}
+
+// Bengali o and au are written with a part before the consonant and a part after
+fn split_matra(ch: char) -> Option<[char; 2]> {
+    match ch {
+        '\u{09CB}' => Some(['\u{09C7}', '\u{09BE}']),
+        '\u{09CC}' => Some(['\u{09C7}', '\u{09D7}']),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Devanagari,
+    Bengali,
+}
+
+impl Script {
+    fn virama(self) -> char {
+        match self {
+            Script::Devanagari => '\u{094D}',
+            Script::Bengali => '\u{09CD}',
+        }
+    }
+
+    // Reph moves in front of the first glyph at or after this position: in Devanagari that's
+    // past the matras, before post-base consonants and modifiers
+    fn reph_limit(self) -> Position {
+        match self {
+            Script::Devanagari => Position::PostConsonant,
+            Script::Bengali => Position::BeforePost,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SyllableKind {
+    Consonant,
+    Vowel,
+    // Marks with nothing to attach to, which get a dotted circle
+    Broken,
+    NonIndic,
+}
+
+// The end of the syllable starting at `start` and what kind it is. Syllables are a chain of
+// consonants joined by halants, or a vowel or placeholder, followed by matras or a final halant
+// and then modifiers.
+fn syllable_end(categories: &[Category], start: usize) -> (usize, SyllableKind) {
+    let at = |i: usize| categories.get(i).copied().unwrap_or(Category::Other);
+    let mut i = start;
+    let kind = match at(start) {
+        Category::Consonant | Category::Ra => {
+            loop {
+                i += 1;
+                if at(i) == Category::Nukta {
+                    i += 1;
+                }
+                if at(i) != Category::Halant {
+                    break;
+                }
+                let next = if at(i + 1).is_joiner() { i + 2 } else { i + 1 };
+                if !matches!(at(next), Category::Consonant | Category::Ra) {
+                    break;
+                }
+                i = next;
+            }
+            SyllableKind::Consonant
+        }
+        Category::Vowel | Category::Placeholder => {
+            i += 1;
+            if at(i) == Category::Nukta {
+                i += 1;
+            }
+            SyllableKind::Vowel
+        }
+        Category::Matra | Category::Nukta | Category::Halant | Category::Modifier => {
+            if at(i) == Category::Nukta {
+                i += 1;
+            }
+            SyllableKind::Broken
+        }
+        _ => return (start + 1, SyllableKind::NonIndic),
+    };
+
+    if at(i) == Category::Halant {
+        i += 1;
+        if at(i).is_joiner() {
+            i += 1;
+        }
+    } else {
+        loop {
+            let matra = if at(i).is_joiner() { i + 1 } else { i };
+            if at(matra) != Category::Matra {
+                break;
+            }
+            i = matra + 1;
+            if at(i) == Category::Nukta {
+                i += 1;
+            }
+            if at(i) == Category::Halant {
+                i += 1;
+            }
+        }
+    }
+    while at(i) == Category::Modifier {
+        i += 1;
+    }
+    (i.max(start + 1), kind)
+}
+
+// Substitutes a run of Devanagari or Bengali: syllables are found and put in the order their
+// glyphs are drawn, the basic features form conjuncts, then reph and pre-base matras move to
+// their final places before the presentation features and the rest apply. `glyph_index` maps
+// characters the shaper adds, like the dotted circle. Clusters index `text`, the run's
+// characters.
+pub fn substitute(plan: &mut SubstitutionPlan, glyphs: &mut Vec<GlyphInfo>, text: &[char], glyph_index: &dyn Fn(char) -> Option<u16>) {
+    let script = match text.iter().find(|&&ch| ('\u{0900}'..='\u{09FF}').contains(&ch)) {
+        Some(&ch) if ch >= '\u{0980}' => Script::Bengali,
+        _ => Script::Devanagari,
+    };
+    let virama = glyph_index(script.virama());
+
+    plan.apply_stage(glyphs, &[*b"locl", *b"ccmp"]);
+    decompose_split_matras(glyphs, text, glyph_index);
+    setup_syllables(plan, glyphs, text, virama, glyph_index);
+
+    let is_pre_base = |info: &GlyphInfo| position_of(info) == Position::PreConsonant;
+    let is_post_base = |info: &GlyphInfo| position_of(info) > Position::BaseConsonant;
+    for tag in BASIC_FEATURES {
+        match &tag {
+            b"rphf" => plan.apply_stage_to(glyphs, &[tag], &|info| position_of(info) == Position::RaToBecomeReph),
+            b"half" => plan.apply_stage_to(glyphs, &[tag], &is_pre_base),
+            b"blwf" => plan.apply_stage_to(glyphs, &[tag], &|info| is_pre_base(info) || is_post_base(info)),
+            b"pref" | b"abvf" | b"pstf" => plan.apply_stage_to(glyphs, &[tag], &is_post_base),
+            _ => plan.apply_stage(glyphs, &[tag]),
+        }
+    }
+
+    final_reordering(glyphs, script);
+    plan.apply_stage_to(glyphs, &[*b"init"], &|info| info.properties & WORD_INITIAL != 0 && position_of(info) == Position::PreMatra);
+    plan.apply_stage(glyphs, &PRESENTATION_FEATURES[1..]);
+    plan.apply_remaining(glyphs);
+}
+
+// Bengali o and au become their two parts, the first of which then reorders like any pre-base
+// matra. Fonts without both parts keep the original.
+fn decompose_split_matras(glyphs: &mut Vec<GlyphInfo>, text: &[char], glyph_index: &dyn Fn(char) -> Option<u16>) {
+    let mut index = 0;
+    while index < glyphs.len() {
+        let parts = text.get(glyphs[index].cluster)
+            .and_then(|&ch| split_matra(ch))
+            .and_then(|[first, second]| Some([glyph_index(first)?, glyph_index(second)?]));
+        if let Some(parts) = parts {
+            let original = glyphs[index];
+            glyphs.splice(index..=index, parts.iter().map(|&glyph_index| GlyphInfo { glyph_index, ..original }));
+            index += 1;
+        }
+        index += 1;
+    }
+}
+
+// The character a glyph stands for before substitution; split matra parts are told apart by
+// their glyph
+fn glyph_char(info: &GlyphInfo, text: &[char], glyph_index: &dyn Fn(char) -> Option<u16>) -> char {
+    let ch = text.get(info.cluster).copied().unwrap_or(DOTTED_CIRCLE);
+    match split_matra(ch) {
+        Some(parts) => parts.into_iter().find(|&part| glyph_index(part) == Some(info.glyph_index)).unwrap_or(ch),
+        None => ch,
+    }
+}
+
+// Finds the syllables, gives broken ones a dotted circle, and records each glyph's category and
+// position before sorting every syllable into its initial order
+fn setup_syllables(plan: &SubstitutionPlan, glyphs: &mut Vec<GlyphInfo>, text: &[char], virama: Option<u16>, glyph_index: &dyn Fn(char) -> Option<u16>) {
+    let mut categories: Vec<Category> = glyphs.iter().map(|info| category(glyph_char(info, text, glyph_index))).collect();
+    let mut start = 0;
+    let mut syllable = 0;
+    let mut previous_kind = SyllableKind::NonIndic;
+    while start < glyphs.len() {
+        let (mut end, kind) = syllable_end(&categories, start);
+        if kind == SyllableKind::Broken {
+            if let Some(dotted_circle) = glyph_index(DOTTED_CIRCLE) {
+                let cluster = glyphs[start].cluster;
+                glyphs.insert(start, GlyphInfo { glyph_index: dotted_circle, cluster, ..GlyphInfo::default() });
+                categories.insert(start, Category::Placeholder);
+                end += 1;
+            }
+        }
+
+        let word_initial = previous_kind == SyllableKind::NonIndic;
+        if kind == SyllableKind::NonIndic {
+            glyphs[start].properties = pack(categories[start], Position::End, word_initial, syllable);
+        } else {
+            let positions = initial_positions(plan, &glyphs[start..end], &categories[start..end], text, virama, glyph_index);
+            let cluster = glyphs[start..end].iter().map(|info| info.cluster).min().unwrap_or(0);
+            for (i, position) in positions.into_iter().enumerate() {
+                let info = &mut glyphs[start + i];
+                info.properties = pack(categories[start + i], position, word_initial, syllable);
+                info.cluster = cluster;
+            }
+            glyphs[start..end].sort_by_key(position_of);
+        }
+        previous_kind = kind;
+        syllable += 1;
+        start = end;
+    }
+}
+
+// Positions for one syllable: a leading Ra and halant that will become reph, the base consonant
+// with the consonants before it, consonants after it that take below- or post-base forms, and
+// matras and modifiers where they're drawn
+fn initial_positions(plan: &SubstitutionPlan, glyphs: &[GlyphInfo], categories: &[Category], text: &[char], virama: Option<u16>, glyph_index: &dyn Fn(char) -> Option<u16>) -> Vec<Position> {
+    let len = glyphs.len();
+    let has_reph = len >= 3
+        && categories[0] == Category::Ra
+        && categories[1] == Category::Halant
+        && categories[2..].iter().any(|category| category.is_base())
+        && !categories[2].is_joiner()
+        && plan.would_substitute(b"rphf", &[glyphs[0].glyph_index, glyphs[1].glyph_index]);
+    let limit = if has_reph { 2 } else { 0 };
+
+    // Consonants after a halant may take a below- or post-base form instead of being the base
+    let consonant_position = |i: usize| -> Position {
+        let follows_halant = i >= 1 && (categories[i - 1] == Category::Halant || (i >= 2 && categories[i - 1].is_joiner() && categories[i - 2] == Category::Halant));
+        let (true, Some(virama)) = (follows_halant, virama) else {
+            return Position::BaseConsonant;
+        };
+        let glyph = glyphs[i].glyph_index;
+        if plan.would_substitute(b"blwf", &[virama, glyph]) || plan.would_substitute(b"blwf", &[glyph, virama]) {
+            Position::BelowConsonant
+        } else if plan.would_substitute(b"pstf", &[virama, glyph]) || plan.would_substitute(b"pstf", &[glyph, virama]) {
+            Position::PostConsonant
+        } else {
+            Position::BaseConsonant
+        }
+    };
+
+    let mut positions = vec![Position::End; len];
+    let mut base = None;
+    for i in (limit..len).rev() {
+        if !categories[i].is_base() {
+            continue;
+        }
+        let position = consonant_position(i);
+        positions[i] = position;
+        if position == Position::BaseConsonant || i == limit {
+            base = Some(i);
+            break;
+        }
+    }
+    let base = base.unwrap_or(limit);
+
+    for i in 0..len {
+        positions[i] = match categories[i] {
+            _ if i < limit => Position::RaToBecomeReph,
+            category if category.is_base() && i < base => Position::PreConsonant,
+            _ if i == base => Position::BaseConsonant,
+            category if category.is_base() => match positions[i] {
+                Position::BaseConsonant | Position::End => Position::AfterMain,
+                position => position,
+            },
+            Category::Matra => matra_position(glyph_char(&glyphs[i], text, glyph_index)),
+            Category::Modifier => Position::Smvd,
+            // Nukta, halant and joiners stay with what they follow
+            _ => if i == 0 { Position::Start } else { positions[i - 1] },
+        };
+    }
+    positions
+}
+
+// Moves each reph behind the glyphs it's drawn over, and each pre-base matra after any halant
+// left standing before the base
+fn final_reordering(glyphs: &mut [GlyphInfo], script: Script) {
+    let mut start = 0;
+    while start < glyphs.len() {
+        let syllable = syllable_of(&glyphs[start]);
+        let end = (start..glyphs.len()).find(|&i| syllable_of(&glyphs[i]) != syllable).unwrap_or(glyphs.len());
+        reorder_syllable(&mut glyphs[start..end], script);
+        start = end;
+    }
+}
+
+fn reorder_syllable(glyphs: &mut [GlyphInfo], script: Script) {
+    let base = (0..glyphs.len()).find(|&i| position_of(&glyphs[i]) >= Position::BaseConsonant).unwrap_or(glyphs.len());
+
+    // A pre-base matra goes after the last halant that didn't join the consonants before the base
+    if let Some(halant) = (0..base).rev().find(|&i| category_of(&glyphs[i]) == Category::Halant && position_of(&glyphs[i]) == Position::PreConsonant) {
+        for i in (0..halant).rev() {
+            if position_of(&glyphs[i]) == Position::PreMatra {
+                glyphs[i..=halant].rotate_left(1);
+            }
+        }
+    }
+
+    // Reph only moves once rphf has formed it, leaving a single glyph
+    let reph_formed = position_of(&glyphs[0]) == Position::RaToBecomeReph && glyphs.get(1).is_none_or(|next| position_of(next) != Position::RaToBecomeReph);
+    if reph_formed {
+        let limit = script.reph_limit();
+        let target = (1..glyphs.len())
+            .find(|&i| position_of(&glyphs[i]) >= limit)
+            .unwrap_or(glyphs.len());
+        glyphs[..target].rotate_left(1);
+        set_position(&mut glyphs[target - 1], Position::End);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A syllable after initial reordering and rphf, the Ra and halant having become one reph
+    // glyph. Glyph ids are the characters' code points.
+    fn formed_syllable(chars: &[(char, Category, Position)]) -> Vec<GlyphInfo> {
+        chars.iter()
+            .map(|&(ch, category, position)| GlyphInfo { glyph_index: ch as u16, properties: pack(category, position, true, 0), ..GlyphInfo::default() })
+            .collect()
+    }
+
+    fn reordered(chars: &[(char, Category, Position)]) -> Vec<u16> {
+        let mut glyphs = formed_syllable(chars);
+        glyphs.sort_by_key(position_of);
+        reorder_syllable(&mut glyphs, Script::Devanagari);
+        glyphs.iter().map(|info| info.glyph_index).collect()
+    }
+
+    #[test]
+    fn devanagari_reph_goes_after_matras_and_before_modifiers() {
+        let reph = ('र', Category::Ra, Position::RaToBecomeReph);
+        let ka = ('क', Category::Consonant, Position::BaseConsonant);
+        let anusvara = ('\u{0902}', Category::Modifier, Position::Smvd);
+        for matra in ['\u{093E}', '\u{0940}', '\u{0947}', '\u{0941}'] {
+            let matra_glyph = (matra, Category::Matra, matra_position(matra));
+            assert_eq!(reordered(&[reph, ka, matra_glyph]), ['क' as u16, matra as u16, 'र' as u16]);
+            assert_eq!(reordered(&[reph, ka, matra_glyph, anusvara]), ['क' as u16, matra as u16, 'र' as u16, 0x0902]);
+        }
+    }
+
+    #[test]
+    fn devanagari_reph_goes_to_the_end_past_a_pre_base_matra() {
+        let glyphs = [
+            ('र', Category::Ra, Position::RaToBecomeReph),
+            ('क', Category::Consonant, Position::BaseConsonant),
+            ('\u{093F}', Category::Matra, matra_position('\u{093F}')),
+        ];
+        assert_eq!(reordered(&glyphs), [0x093F, 'क' as u16, 'र' as u16]);
+    }
+}
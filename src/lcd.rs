@@ -0,0 +1,205 @@
+use crate::glyph::Glyph;
+use crate::outline::Outline;
+use crate::raster::{FillRule, Rasterizer};
+
+// Physical order of the colour stripes within a pixel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SubpixelOrder {
+    #[default]
+    Rgb,
+    Bgr,
+    VerticalRgb,
+    VerticalBgr,
+}
+
+impl SubpixelOrder {
+    fn is_vertical(&self) -> bool {
+        matches!(self, SubpixelOrder::VerticalRgb | SubpixelOrder::VerticalBgr)
+    }
+
+    fn is_reversed(&self) -> bool {
+        matches!(self, SubpixelOrder::Bgr | SubpixelOrder::VerticalBgr)
+    }
+}
+
+// Five-tap FIR filter applied across subpixels to limit colour fringing. Weights are normalised
+// by their sum, so any non-zero set of weights can be used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LcdFilter {
+    pub weights: [u8; 5],
+}
+
+impl LcdFilter {
+    // Same weights as FreeType's default LCD filter
+    pub const DEFAULT: LcdFilter = LcdFilter { weights: [0x08, 0x4D, 0x56, 0x4D, 0x08] };
+    pub const LIGHT: LcdFilter = LcdFilter { weights: [0x00, 0x55, 0x56, 0x55, 0x00] };
+    pub const NONE: LcdFilter = LcdFilter { weights: [0, 0, 1, 0, 0] };
+
+    fn apply(&self, samples: &[u8]) -> Vec<u8> {
+        let total: u32 = self.weights.iter().map(|&weight| weight as u32).sum::<u32>().max(1);
+        (0..samples.len())
+            .map(|i| {
+                let sum: u32 = self.weights.iter().enumerate()
+                    .filter_map(|(tap, &weight)| {
+                        let index = (i + tap).checked_sub(2)?;
+                        samples.get(index).map(|&sample| sample as u32 * weight as u32)
+                    })
+                    .sum();
+                ((sum + total / 2) / total).min(255) as u8
+            })
+            .collect()
+    }
+}
+
+impl Default for LcdFilter {
+    fn default() -> Self {
+        LcdFilter::DEFAULT
+    }
+}
+
+// Per-channel coverage, three bytes per pixel always stored as red, green, blue regardless of
+// the subpixel order. Placement follows GlyphBitmap.
+#[derive(Debug, Clone, Default)]
+pub struct LcdBitmap {
+    pub width: usize,
+    pub height: usize,
+    pub left: i32,
+    pub top: i32,
+    pub coverage: Vec<u8>,
+}
+
+// Rasterizes at three times the resolution along the subpixel axis, filters the subpixels and
+// packs every three of them into one pixel's channels
+pub fn rasterize_outline_lcd(outline: &Outline, scale: f32, subpixel_offset: (f32, f32), fill_rule: FillRule, order: SubpixelOrder, filter: LcdFilter) -> LcdBitmap {
+    let scaled = outline.transform(scale, scale, subpixel_offset.0, subpixel_offset.1);
+    let (min_x, min_y, max_x, max_y) = match scaled.tight_bounds() {
+        Some(bounds) => bounds,
+        None => return LcdBitmap::default(),
+    };
+
+    // One extra pixel on each side of the subpixel axis leaves room for the filter to spread into
+    let vertical = order.is_vertical();
+    let (pad_x, pad_y) = if vertical { (0, 1) } else { (1, 0) };
+    let left = min_x.floor() as i32 - pad_x;
+    let bottom = min_y.floor() as i32 - pad_y;
+    let right = max_x.ceil() as i32 + pad_x;
+    let top = max_y.ceil() as i32 + pad_y;
+    let width = (right - left).max(0) as usize;
+    let height = (top - bottom).max(0) as usize;
+
+    let (scale_x, scale_y) = if vertical { (1.0, 3.0) } else { (3.0, 1.0) };
+    let bitmap_outline = scaled.transform(scale_x, -scale_y, -left as f32 * scale_x, top as f32 * scale_y);
+    let (samples_width, samples_height) = (width * scale_x as usize, height * scale_y as usize);
+    let mut rasterizer = Rasterizer::new(samples_width, samples_height);
+    rasterizer.draw_outline(&bitmap_outline);
+    let samples = rasterizer.coverage(fill_rule);
+
+    // Filter along the subpixel axis, one row (or column) of subpixels at a time
+    let mut filtered = vec![0u8; samples.len()];
+    if vertical {
+        for column in 0..samples_width {
+            let line: Vec<u8> = (0..samples_height).map(|row| samples[row * samples_width + column]).collect();
+            for (row, value) in filter.apply(&line).into_iter().enumerate() {
+                filtered[row * samples_width + column] = value;
+            }
+        }
+    } else {
+        for row in 0..samples_height {
+            let line = &samples[row * samples_width..(row + 1) * samples_width];
+            filtered[row * samples_width..(row + 1) * samples_width].copy_from_slice(&filter.apply(line));
+        }
+    }
+
+    let mut coverage = Vec::with_capacity(width * height * 3);
+    for row in 0..height {
+        for column in 0..width {
+            let mut channels = [0u8; 3];
+            for (i, channel) in channels.iter_mut().enumerate() {
+                *channel = if vertical {
+                    filtered[(row * 3 + i) * samples_width + column]
+                } else {
+                    filtered[row * samples_width + column * 3 + i]
+                };
+            }
+            if order.is_reversed() {
+                channels.reverse();
+            }
+            coverage.extend(channels);
+        }
+    }
+
+    LcdBitmap {
+        width,
+        height,
+        left,
+        top,
+        coverage,
+    }
+}
+
+pub fn rasterize_glyph_lcd(glyph: &Glyph, units_per_em: u16, pixel_size: f32, subpixel_offset: (f32, f32), fill_rule: FillRule, order: SubpixelOrder, filter: LcdFilter) -> LcdBitmap {
+    let scale = pixel_size / units_per_em as f32;
+    rasterize_outline_lcd(&Outline::from_glyph(glyph), scale, subpixel_offset, fill_rule, order, filter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A rectangle in pixels, clockwise like TrueType outlines
+    fn rectangle(x0: f32, y0: f32, x1: f32, y1: f32) -> Outline {
+        Outline::from_points(&[(x0, y0), (x0, y1), (x1, y1), (x1, y0)], &[true; 4], &[3])
+    }
+
+    fn pixel(bitmap: &LcdBitmap, column: usize, row: usize) -> [u8; 3] {
+        let start = (row * bitmap.width + column) * 3;
+        [bitmap.coverage[start], bitmap.coverage[start + 1], bitmap.coverage[start + 2]]
+    }
+
+    #[test]
+    fn stripe_order_decides_which_channel_is_lit() {
+        // Covers only the leftmost third of the pixel, which is the red stripe on an RGB panel
+        let outline = rectangle(0.0, 0.0, 1.0, 3.0);
+        let rgb = rasterize_outline_lcd(&outline, 1.0 / 3.0, (0.0, 0.0), FillRule::NonZero, SubpixelOrder::Rgb, LcdFilter::NONE);
+        let bgr = rasterize_outline_lcd(&outline, 1.0 / 3.0, (0.0, 0.0), FillRule::NonZero, SubpixelOrder::Bgr, LcdFilter::NONE);
+
+        // The padding pixel on the left is empty, the glyph's pixel comes next
+        assert_eq!((rgb.width, rgb.left), (3, -1));
+        assert_eq!(pixel(&rgb, 0, 0), [0, 0, 0]);
+        assert_eq!(pixel(&rgb, 1, 0), [255, 0, 0]);
+        assert_eq!(pixel(&bgr, 1, 0), [0, 0, 255]);
+    }
+
+    #[test]
+    fn vertical_stripes_split_the_pixel_top_to_bottom() {
+        // Covers only the bottom third, which is the last stripe for vertical RGB
+        let outline = rectangle(0.0, 0.0, 3.0, 1.0);
+        let bitmap = rasterize_outline_lcd(&outline, 1.0 / 3.0, (0.0, 0.0), FillRule::NonZero, SubpixelOrder::VerticalRgb, LcdFilter::NONE);
+        assert_eq!((bitmap.width, bitmap.height), (1, 3));
+        assert_eq!(pixel(&bitmap, 0, 1), [0, 0, 255]);
+    }
+
+    #[test]
+    fn filter_spreads_a_subpixel_by_its_weights() {
+        let mut samples = [0u8; 9];
+        samples[4] = 255;
+        assert_eq!(LcdFilter::DEFAULT.apply(&samples), [0, 0, 8, 77, 86, 77, 8, 0, 0]);
+        assert_eq!(LcdFilter::NONE.apply(&samples), samples);
+    }
+
+    #[test]
+    fn filtering_keeps_the_total_coverage() {
+        let outline = rectangle(0.0, 0.0, 2.0, 1.0);
+        let total = |filter| -> i32 {
+            let bitmap = rasterize_outline_lcd(&outline, 1.0, (0.0, 0.0), FillRule::NonZero, SubpixelOrder::Rgb, filter);
+            bitmap.coverage.iter().map(|&value| value as i32).sum()
+        };
+
+        // The padding pixels leave room for the filter, so only per-subpixel rounding changes the sum
+        let unfiltered = total(LcdFilter::NONE);
+        assert_eq!(unfiltered, 255 * 2 * 3);
+        for filter in [LcdFilter::DEFAULT, LcdFilter::LIGHT] {
+            assert!((total(filter) - unfiltered).abs() <= 6, "{:?} changed the coverage", filter);
+        }
+    }
+}
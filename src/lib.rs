@@ -0,0 +1,29 @@
+pub mod arabic;
+pub mod atlas;
+pub mod bidi;
+pub mod buffer;
+pub mod fallback;
+pub mod gdef;
+pub mod glyph;
+pub mod gpos;
+pub mod gsub;
+pub mod headless;
+pub mod hinting;
+pub mod image;
+pub mod indic;
+pub mod lcd;
+pub mod linebreak;
+pub mod metrics;
+pub mod missing;
+pub mod opentype;
+pub mod outline;
+pub mod paragraph;
+pub mod raster;
+pub mod reader;
+pub mod sdf;
+pub mod shaping;
+pub mod specimen;
+pub mod svg;
+pub mod table;
+pub mod thai;
+pub mod utils;
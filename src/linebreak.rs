@@ -0,0 +1,307 @@
+// Unicode Line_Break classes (UAX #14). Ambiguous, unknown and complex-context (SA) characters
+// are resolved to AL up front, and conditional Japanese starters (CJ) to ID as in normal line
+// breaking, so those classes don't appear.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineBreakClass {
+    BK,
+    CR,
+    LF,
+    NL,
+    SP,
+    ZW,
+    ZWJ,
+    CM,
+    WJ,
+    GL,
+    BA,
+    BB,
+    HY,
+    CB,
+    CL,
+    CP,
+    EX,
+    IN,
+    NS,
+    OP,
+    QU,
+    IS,
+    NU,
+    PO,
+    PR,
+    SY,
+    AL,
+    HL,
+    ID,
+    EB,
+    EM,
+    H2,
+    H3,
+    JL,
+    JV,
+    JT,
+    RI,
+    B2,
+}
+
+use LineBreakClass::*;
+
+// Whether the line may or must end before a character
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakOpportunity {
+    None,
+    Allowed,
+    Mandatory,
+}
+
+// Line break classes other than AL, sorted by code point. Thai and Lao letters break like AL
+// without a dictionary, and their vowel and tone marks like CM.
+const CLASS_RANGES: &[(u32, u32, LineBreakClass)] = &[
+    (0x0000, 0x0008, CM), (0x0009, 0x0009, BA), (0x000A, 0x000A, LF), (0x000B, 0x000C, BK), (0x000D, 0x000D, CR),
+    (0x000E, 0x001F, CM), (0x0020, 0x0020, SP), (0x0021, 0x0021, EX), (0x0022, 0x0022, QU), (0x0024, 0x0024, PR),
+    (0x0025, 0x0025, PO), (0x0027, 0x0027, QU), (0x0028, 0x0028, OP), (0x0029, 0x0029, CP), (0x002B, 0x002B, PR),
+    (0x002C, 0x002C, IS), (0x002D, 0x002D, HY), (0x002E, 0x002E, IS), (0x002F, 0x002F, SY), (0x0030, 0x0039, NU),
+    (0x003A, 0x003B, IS), (0x003F, 0x003F, EX), (0x005B, 0x005B, OP), (0x005C, 0x005C, PR), (0x005D, 0x005D, CP),
+    (0x007B, 0x007B, OP), (0x007C, 0x007C, BA), (0x007D, 0x007D, CL), (0x007F, 0x0084, CM), (0x0085, 0x0085, NL),
+    (0x0086, 0x009F, CM), (0x00A0, 0x00A0, GL), (0x00A1, 0x00A1, OP), (0x00A2, 0x00A2, PO), (0x00A3, 0x00A5, PR),
+    (0x00AB, 0x00AB, QU), (0x00AD, 0x00AD, BA), (0x00B0, 0x00B0, PO), (0x00B1, 0x00B1, PR), (0x00B4, 0x00B4, BB),
+    (0x00BB, 0x00BB, QU), (0x00BF, 0x00BF, OP), (0x02C8, 0x02C8, BB), (0x02CC, 0x02CC, BB), (0x02DF, 0x02DF, BB),
+    (0x0300, 0x034E, CM), (0x034F, 0x034F, GL), (0x0350, 0x035B, CM), (0x035C, 0x0362, GL), (0x0363, 0x036F, CM),
+    (0x037E, 0x037E, IS), (0x0483, 0x0489, CM), (0x0589, 0x0589, IS), (0x058A, 0x058A, BA), (0x0591, 0x05BD, CM),
+    (0x05BE, 0x05BE, BA), (0x05BF, 0x05BF, CM), (0x05C1, 0x05C2, CM), (0x05C4, 0x05C5, CM), (0x05C7, 0x05C7, CM),
+    (0x05D0, 0x05EA, HL), (0x05EF, 0x05F2, HL), (0x0609, 0x060A, PO), (0x060C, 0x060D, IS), (0x0610, 0x061A, CM),
+    (0x061F, 0x061F, EX), (0x064B, 0x065F, CM), (0x0660, 0x0669, NU), (0x066A, 0x066A, PO), (0x066B, 0x066C, NU),
+    (0x0670, 0x0670, CM), (0x06D4, 0x06D4, EX), (0x06D6, 0x06DC, CM), (0x06DF, 0x06E4, CM), (0x06E7, 0x06E8, CM),
+    (0x06EA, 0x06ED, CM), (0x06F0, 0x06F9, NU), (0x0711, 0x0711, CM), (0x0730, 0x074A, CM), (0x07A6, 0x07B0, CM),
+    (0x07C0, 0x07C9, NU), (0x07EB, 0x07F3, CM), (0x07F8, 0x07F8, IS), (0x07F9, 0x07F9, EX), (0x08D3, 0x08E1, CM),
+    (0x08E3, 0x0903, CM), (0x093A, 0x093C, CM), (0x093E, 0x094F, CM), (0x0951, 0x0957, CM), (0x0962, 0x0963, CM),
+    (0x0964, 0x0965, BA), (0x0966, 0x096F, NU), (0x0981, 0x0983, CM), (0x09BC, 0x09BC, CM), (0x09BE, 0x09C4, CM),
+    (0x09C7, 0x09C8, CM), (0x09CB, 0x09CD, CM), (0x09D7, 0x09D7, CM), (0x09E2, 0x09E3, CM), (0x09E6, 0x09EF, NU),
+    (0x09F2, 0x09F3, PO), (0x09F9, 0x09F9, PO), (0x09FB, 0x09FB, PR), (0x0E31, 0x0E31, CM), (0x0E34, 0x0E3A, CM),
+    (0x0E3F, 0x0E3F, PR), (0x0E47, 0x0E4E, CM), (0x0E50, 0x0E59, NU), (0x0E5A, 0x0E5B, BA), (0x0EB1, 0x0EB1, CM),
+    (0x0EB4, 0x0EBC, CM), (0x0EC8, 0x0ECD, CM), (0x0ED0, 0x0ED9, NU), (0x0F0B, 0x0F0B, BA), (0x0F0C, 0x0F0C, GL),
+    (0x1100, 0x115F, JL), (0x1160, 0x11A7, JV), (0x11A8, 0x11FF, JT), (0x1680, 0x1680, BA), (0x1AB0, 0x1AFF, CM),
+    (0x1DC0, 0x1DFF, CM), (0x2000, 0x2006, BA), (0x2007, 0x2007, GL), (0x2008, 0x200A, BA), (0x200B, 0x200B, ZW),
+    (0x200C, 0x200C, CM), (0x200D, 0x200D, ZWJ), (0x200E, 0x200F, CM), (0x2010, 0x2010, BA), (0x2011, 0x2011, GL),
+    (0x2012, 0x2013, BA), (0x2014, 0x2014, B2), (0x2018, 0x2019, QU), (0x201A, 0x201A, OP), (0x201B, 0x201D, QU),
+    (0x201E, 0x201E, OP), (0x201F, 0x201F, QU), (0x2024, 0x2026, IN), (0x2027, 0x2027, BA), (0x2028, 0x2029, BK),
+    (0x202A, 0x202E, CM), (0x202F, 0x202F, GL), (0x2030, 0x2037, PO), (0x2039, 0x203A, QU), (0x203C, 0x203D, NS),
+    (0x2044, 0x2044, IS), (0x2045, 0x2045, OP), (0x2046, 0x2046, CL), (0x2047, 0x2049, NS), (0x2056, 0x2056, BA),
+    (0x2058, 0x205B, BA), (0x205D, 0x205F, BA), (0x2060, 0x2060, WJ), (0x2066, 0x206F, CM), (0x207D, 0x207D, OP),
+    (0x207E, 0x207E, CL), (0x208D, 0x208D, OP), (0x208E, 0x208E, CL), (0x20A0, 0x20A6, PR), (0x20A7, 0x20A7, PO),
+    (0x20A8, 0x20B5, PR), (0x20B6, 0x20B6, PO), (0x20B7, 0x20BA, PR), (0x20BB, 0x20BB, PO), (0x20BC, 0x20BD, PR),
+    (0x20BE, 0x20BE, PO), (0x20BF, 0x20C0, PR), (0x20D0, 0x20F0, CM), (0x2103, 0x2103, PO), (0x2109, 0x2109, PO),
+    (0x2116, 0x2116, PR), (0x2212, 0x2213, PR), (0x2308, 0x2308, OP), (0x2309, 0x2309, CL), (0x230A, 0x230A, OP),
+    (0x230B, 0x230B, CL), (0x231A, 0x231B, ID), (0x2329, 0x2329, OP), (0x232A, 0x232A, CL), (0x23F0, 0x23F3, ID),
+    (0x261D, 0x261D, EB), (0x26F9, 0x26F9, EB), (0x270A, 0x270D, EB), (0x2768, 0x2768, OP), (0x2769, 0x2769, CL),
+    (0x276A, 0x276A, OP), (0x276B, 0x276B, CL), (0x276C, 0x276C, OP), (0x276D, 0x276D, CL), (0x276E, 0x276E, OP),
+    (0x276F, 0x276F, CL), (0x2770, 0x2770, OP), (0x2771, 0x2771, CL), (0x2772, 0x2772, OP), (0x2773, 0x2773, CL),
+    (0x2774, 0x2774, OP), (0x2775, 0x2775, CL), (0x27C5, 0x27C5, OP), (0x27C6, 0x27C6, CL), (0x27E6, 0x27E6, OP),
+    (0x27E7, 0x27E7, CL), (0x27E8, 0x27E8, OP), (0x27E9, 0x27E9, CL), (0x27EA, 0x27EA, OP), (0x27EB, 0x27EB, CL),
+    (0x27EC, 0x27EC, OP), (0x27ED, 0x27ED, CL), (0x27EE, 0x27EE, OP), (0x27EF, 0x27EF, CL), (0x2983, 0x2983, OP),
+    (0x2984, 0x2984, CL), (0x2985, 0x2985, OP), (0x2986, 0x2986, CL), (0x2987, 0x2987, OP), (0x2988, 0x2988, CL),
+    (0x2989, 0x2989, OP), (0x298A, 0x298A, CL), (0x298B, 0x298B, OP), (0x298C, 0x298C, CL), (0x298D, 0x298D, OP),
+    (0x298E, 0x298E, CL), (0x298F, 0x298F, OP), (0x2990, 0x2990, CL), (0x2991, 0x2991, OP), (0x2992, 0x2992, CL),
+    (0x2993, 0x2993, OP), (0x2994, 0x2994, CL), (0x2995, 0x2995, OP), (0x2996, 0x2996, CL), (0x2997, 0x2997, OP),
+    (0x2998, 0x2998, CL), (0x29D8, 0x29D8, OP), (0x29D9, 0x29D9, CL), (0x29DA, 0x29DA, OP), (0x29DB, 0x29DB, CL),
+    (0x29FC, 0x29FC, OP), (0x29FD, 0x29FD, CL), (0x2E18, 0x2E18, OP), (0x2E22, 0x2E22, OP), (0x2E23, 0x2E23, CL),
+    (0x2E24, 0x2E24, OP), (0x2E25, 0x2E25, CL), (0x2E26, 0x2E26, OP), (0x2E27, 0x2E27, CL), (0x2E28, 0x2E28, OP),
+    (0x2E29, 0x2E29, CL), (0x2E80, 0x2FFF, ID), (0x3000, 0x3000, BA), (0x3001, 0x3002, CL), (0x3003, 0x3004, ID),
+    (0x3005, 0x3005, NS), (0x3006, 0x3007, ID), (0x3008, 0x3008, OP), (0x3009, 0x3009, CL), (0x300A, 0x300A, OP),
+    (0x300B, 0x300B, CL), (0x300C, 0x300C, OP), (0x300D, 0x300D, CL), (0x300E, 0x300E, OP), (0x300F, 0x300F, CL),
+    (0x3010, 0x3010, OP), (0x3011, 0x3011, CL), (0x3012, 0x3013, ID), (0x3014, 0x3014, OP), (0x3015, 0x3015, CL),
+    (0x3016, 0x3016, OP), (0x3017, 0x3017, CL), (0x3018, 0x3018, OP), (0x3019, 0x3019, CL), (0x301A, 0x301A, OP),
+    (0x301B, 0x301B, CL), (0x301C, 0x301C, NS), (0x301D, 0x301D, OP), (0x301E, 0x301F, CL), (0x3020, 0x3029, ID),
+    (0x302A, 0x302F, CM), (0x3030, 0x303A, ID), (0x303B, 0x303C, NS), (0x303D, 0x3098, ID), (0x3099, 0x309A, CM),
+    (0x309B, 0x309E, NS), (0x309F, 0x309F, ID), (0x30A0, 0x30A0, NS), (0x30A1, 0x30FA, ID), (0x30FB, 0x30FE, NS),
+    (0x30FF, 0x4DBF, ID), (0x4E00, 0x9FFF, ID), (0xA000, 0xA48C, ID), (0xD7B0, 0xD7C6, JV), (0xD7CB, 0xD7FB, JT),
+    (0xF900, 0xFAFF, ID), (0xFE00, 0xFE0F, CM), (0xFE10, 0xFE10, IS), (0xFE11, 0xFE12, CL), (0xFE13, 0xFE14, IS),
+    (0xFE15, 0xFE16, EX), (0xFE17, 0xFE17, OP), (0xFE18, 0xFE18, CL), (0xFE19, 0xFE19, IN), (0xFE20, 0xFE2F, CM),
+    (0xFE30, 0xFE34, ID), (0xFE35, 0xFE35, OP), (0xFE36, 0xFE36, CL), (0xFE37, 0xFE37, OP), (0xFE38, 0xFE38, CL),
+    (0xFE39, 0xFE39, OP), (0xFE3A, 0xFE3A, CL), (0xFE3B, 0xFE3B, OP), (0xFE3C, 0xFE3C, CL), (0xFE3D, 0xFE3D, OP),
+    (0xFE3E, 0xFE3E, CL), (0xFE3F, 0xFE3F, OP), (0xFE40, 0xFE40, CL), (0xFE41, 0xFE41, OP), (0xFE42, 0xFE42, CL),
+    (0xFE43, 0xFE43, OP), (0xFE44, 0xFE44, CL), (0xFE45, 0xFE46, ID), (0xFE47, 0xFE47, OP), (0xFE48, 0xFE48, CL),
+    (0xFE49, 0xFE4F, ID), (0xFE50, 0xFE50, CL), (0xFE51, 0xFE51, ID), (0xFE52, 0xFE52, CL), (0xFE54, 0xFE55, NS),
+    (0xFE56, 0xFE57, EX), (0xFE58, 0xFE58, ID), (0xFE59, 0xFE59, OP), (0xFE5A, 0xFE5A, CL), (0xFE5B, 0xFE5B, OP),
+    (0xFE5C, 0xFE5C, CL), (0xFE5D, 0xFE5D, OP), (0xFE5E, 0xFE5E, CL), (0xFE5F, 0xFE68, ID), (0xFE69, 0xFE69, PR),
+    (0xFE6A, 0xFE6A, PO), (0xFEFF, 0xFEFF, WJ), (0xFF01, 0xFF01, EX), (0xFF02, 0xFF03, ID), (0xFF04, 0xFF04, PR),
+    (0xFF05, 0xFF05, PO), (0xFF06, 0xFF07, ID), (0xFF08, 0xFF08, OP), (0xFF09, 0xFF09, CL), (0xFF0A, 0xFF0B, ID),
+    (0xFF0C, 0xFF0C, CL), (0xFF0D, 0xFF0D, ID), (0xFF0E, 0xFF0E, CL), (0xFF0F, 0xFF19, ID), (0xFF1A, 0xFF1B, NS),
+    (0xFF1C, 0xFF1E, ID), (0xFF1F, 0xFF1F, EX), (0xFF20, 0xFF3A, ID), (0xFF3B, 0xFF3B, OP), (0xFF3C, 0xFF3C, ID),
+    (0xFF3D, 0xFF3D, CL), (0xFF3E, 0xFF5A, ID), (0xFF5B, 0xFF5B, OP), (0xFF5C, 0xFF5C, ID), (0xFF5D, 0xFF5D, CL),
+    (0xFF5E, 0xFF5E, ID), (0xFF5F, 0xFF5F, OP), (0xFF60, 0xFF61, CL), (0xFF62, 0xFF62, OP), (0xFF63, 0xFF64, CL),
+    (0xFF65, 0xFF65, NS), (0xFF9E, 0xFF9F, NS), (0xFFE0, 0xFFE0, PO), (0xFFE1, 0xFFE1, PR), (0xFFE2, 0xFFE4, ID),
+    (0xFFE5, 0xFFE6, PR), (0xFFF9, 0xFFFB, CM), (0xFFFC, 0xFFFC, CB), (0x1F000, 0x1F1E5, ID), (0x1F1E6, 0x1F1FF, RI),
+    (0x1F200, 0x1F384, ID), (0x1F385, 0x1F385, EB), (0x1F386, 0x1F3C1, ID), (0x1F3C2, 0x1F3C4, EB), (0x1F3C5, 0x1F3C6, ID),
+    (0x1F3C7, 0x1F3C7, EB), (0x1F3C8, 0x1F3C9, ID), (0x1F3CA, 0x1F3CC, EB), (0x1F3CD, 0x1F3FA, ID), (0x1F3FB, 0x1F3FF, EM),
+    (0x1F400, 0x1F441, ID), (0x1F442, 0x1F443, EB), (0x1F444, 0x1F445, ID), (0x1F446, 0x1F450, EB), (0x1F451, 0x1F465, ID),
+    (0x1F466, 0x1F478, EB), (0x1F479, 0x1F47B, ID), (0x1F47C, 0x1F47C, EB), (0x1F47D, 0x1F480, ID), (0x1F481, 0x1F483, EB),
+    (0x1F484, 0x1F484, ID), (0x1F485, 0x1F487, EB), (0x1F488, 0x1F4A9, ID), (0x1F4AA, 0x1F4AA, EB), (0x1F4AB, 0x1F644, ID),
+    (0x1F645, 0x1F647, EB), (0x1F648, 0x1F64A, ID), (0x1F64B, 0x1F64F, EB), (0x1F650, 0x1F6A2, ID), (0x1F6A3, 0x1F6A3, EB),
+    (0x1F6A4, 0x1F6B3, ID), (0x1F6B4, 0x1F6B6, EB), (0x1F6B7, 0x1F6BF, ID), (0x1F6C0, 0x1F6C0, EB), (0x1F6C1, 0x1F917, ID),
+    (0x1F918, 0x1F91F, EB), (0x1F920, 0x1F925, ID), (0x1F926, 0x1F926, EB), (0x1F927, 0x1F92F, ID), (0x1F930, 0x1F939, EB),
+    (0x1F93A, 0x1F93B, ID), (0x1F93C, 0x1F93E, EB), (0x1F93F, 0x1FAFF, ID), (0x20000, 0x3FFFD, ID), (0xE0001, 0xE007F, CM),
+    (0xE0100, 0xE01EF, CM),
+];
+
+pub fn line_break_class(ch: char) -> LineBreakClass {
+    let code_point = ch as u32;
+    // Hangul syllables are LV when they have no final consonant and LVT otherwise
+    if (0xAC00..=0xD7A3).contains(&code_point) {
+        return if (code_point - 0xAC00).is_multiple_of(28) { H2 } else { H3 };
+    }
+    let index = CLASS_RANGES.partition_point(|&(_, end, _)| end < code_point);
+    match CLASS_RANGES.get(index) {
+        Some(&(start, _, class)) if start <= code_point => class,
+        _ => AL,
+    }
+}
+
+fn is_hard_break(class: LineBreakClass) -> bool {
+    matches!(class, BK | CR | LF | NL)
+}
+
+// Characters that end a line and are never drawn
+pub fn is_newline(ch: char) -> bool {
+    is_hard_break(line_break_class(ch))
+}
+
+// The break opportunity before each character of the text and at its end, following the
+// default rules of UAX #14. The start never breaks and the end always must.
+pub fn line_breaks(text: &[char]) -> Vec<BreakOpportunity> {
+    let mut breaks = vec![BreakOpportunity::None; text.len() + 1];
+    let Some(&first) = text.first() else {
+        return breaks;
+    };
+    breaks[text.len()] = BreakOpportunity::Mandatory;
+
+    let classes: Vec<LineBreakClass> = text.iter().map(|&ch| line_break_class(ch)).collect();
+    // LB10: marks with nothing to attach to are alphabetic
+    let resolve = |class: LineBreakClass| if matches!(class, CM | ZWJ) { AL } else { class };
+    // The class of the previous character once marks are folded into what they follow, and
+    // the class before any spaces leading up to the current character
+    let mut previous = resolve(line_break_class(first));
+    let mut before_spaces = previous;
+    let mut after_zero_width_space = classes[0] == ZW;
+    let mut regional_indicators = usize::from(previous == RI);
+
+    for index in 1..text.len() {
+        let raw_previous = classes[index - 1];
+        let class = classes[index];
+        let opportunity = if raw_previous == BK || (matches!(raw_previous, CR | LF | NL) && !(raw_previous == CR && class == LF)) {
+            // LB4, LB5
+            BreakOpportunity::Mandatory
+        } else if raw_previous == CR || is_hard_break(class) || matches!(class, SP | ZW) {
+            // LB5, LB6, LB7
+            BreakOpportunity::None
+        } else if after_zero_width_space {
+            // LB8
+            BreakOpportunity::Allowed
+        } else if raw_previous == ZWJ || (matches!(class, CM | ZWJ) && !matches!(previous, SP | ZW) && !is_hard_break(previous)) {
+            // LB8a, LB9: marks stay with what they follow and take its class
+            if matches!(class, CM | ZWJ) {
+                continue;
+            }
+            BreakOpportunity::None
+        } else if pair_allows_break(previous, before_spaces, resolve(class), regional_indicators) {
+            BreakOpportunity::Allowed
+        } else {
+            BreakOpportunity::None
+        };
+        breaks[index] = opportunity;
+
+        let class = resolve(class);
+        regional_indicators = if class == RI { regional_indicators + 1 } else { 0 };
+        if class != SP {
+            before_spaces = class;
+            after_zero_width_space = class == ZW;
+        }
+        previous = class;
+    }
+    breaks
+}
+
+// LB11 to LB31 for a pair of characters, with marks already resolved. `before_spaces` is the
+// class before any spaces between the two, and `regional_indicators` how many flags' halves
+// directly precede the second.
+fn pair_allows_break(previous: LineBreakClass, before_spaces: LineBreakClass, class: LineBreakClass, regional_indicators: usize) -> bool {
+    let alphabetic = |class: LineBreakClass| matches!(class, AL | HL);
+    let hangul = |class: LineBreakClass| matches!(class, JL | JV | JT | H2 | H3);
+    match (previous, class) {
+        (WJ, _) | (_, WJ) | (GL, _) => false,
+        (SP | BA | HY, GL) => true,
+        (_, GL) => false,
+        (_, CL | CP | EX | IS | SY) => false,
+        _ if before_spaces == OP => false,
+        _ if before_spaces == QU && class == OP => false,
+        _ if matches!(before_spaces, CL | CP) && class == NS => false,
+        _ if before_spaces == B2 && class == B2 => false,
+        (SP, _) => true,
+        (_, QU) | (QU, _) => false,
+        (_, CB) | (CB, _) => true,
+        (_, BA | HY | NS) | (BB, _) => false,
+        (SY, HL) => false,
+        (_, IN) => false,
+        (AL | HL, NU) | (NU, AL | HL) => false,
+        (PR, ID | EB | EM) | (ID | EB | EM, PO) => false,
+        (PR | PO, AL | HL) | (AL | HL, PR | PO) => false,
+        (CL | CP | NU, PO | PR) | (PO | PR, OP | NU) | (HY | IS | NU | SY, NU) => false,
+        (JL, JL | JV | H2 | H3) | (JV | H2, JV | JT) | (JT | H3, JT) => false,
+        (previous, PO) if hangul(previous) => false,
+        (PR, class) if hangul(class) => false,
+        (previous, class) if alphabetic(previous) && alphabetic(class) => false,
+        (IS, AL | HL) => false,
+        (AL | HL | NU, OP) | (CP, AL | HL | NU) => false,
+        (RI, RI) => regional_indicators.is_multiple_of(2),
+        (EB, EM) => false,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use BreakOpportunity::{Allowed, Mandatory, None as Never};
+
+    fn breaks(text: &str) -> Vec<BreakOpportunity> {
+        line_breaks(&text.chars().collect::<Vec<char>>())
+    }
+
+    // Indices where a break is allowed, leaving out the mandatory one at the end
+    fn allowed(text: &str) -> Vec<usize> {
+        breaks(text).iter().enumerate().filter(|&(_, &opportunity)| opportunity == Allowed).map(|(index, _)| index).collect()
+    }
+
+    #[test]
+    fn marks_fold_into_the_character_they_follow() {
+        // LB9: no break before the acute, which then breaks like the e it's on
+        assert_eq!(breaks("e\u{301} x"), [Never, Never, Never, Allowed, Mandatory]);
+        // and takes the class of a digit, so the percent sign still can't be split off
+        assert_eq!(breaks("1\u{301}%"), [Never, Never, Never, Mandatory]);
+        // LB10: a mark after a space is alphabetic
+        assert_eq!(allowed("a \u{301}b"), [2]);
+    }
+
+    #[test]
+    fn hyphens_stay_with_the_word_before() {
+        // LB21 keeps the hyphen on the line, with a break allowed after it
+        assert_eq!(allowed("well-known"), [5]);
+    }
+
+    #[test]
+    fn numbers_with_prefixes_postfixes_and_separators_stay_whole() {
+        // LB25 for $1.50, 50% and a minus sign
+        assert_eq!(allowed("$1.50 50% -5"), [6, 10]);
+    }
+
+    #[test]
+    fn regional_indicators_pair_into_flags() {
+        // LB30a: four halves make two flags, which may break apart but not in the middle
+        assert_eq!(allowed("\u{1F1EB}\u{1F1F7}\u{1F1E9}\u{1F1EA}"), [2]);
+    }
+
+    #[test]
+    fn line_must_end_after_newlines_but_not_inside_crlf() {
+        assert_eq!(breaks("a\nb"), [Never, Never, Mandatory, Mandatory]);
+        assert_eq!(breaks("a\r\nb"), [Never, Never, Never, Mandatory, Mandatory]);
+    }
+}
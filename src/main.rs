@@ -1,97 +1,225 @@
-mod buffer;
-mod glyph;
-mod reader;
+#[cfg(feature = "sdl2")]
 mod renderer;
-mod table;
-mod utils;
 mod config;
+mod font_file;
 
 use config::Config;
-use reader::{FontParser, read_table_directory};
-use utils::read_file_to_byte_array;
-use buffer::ByteBuffer;
-use renderer::AppState;
-use sdl2::event::{Event, WindowEvent};
-use sdl2::keyboard::Keycode;
-use sdl2::mouse::MouseButton;
-use sdl2::render::Canvas;
-use sdl2::video::Window;
-use sdl2::Sdl;
-use clap::{App, Arg};
+use font_file::FontFile;
+use ttf_parser::reader::FontParser;
+use ttf_parser::gpos::{GlyphPosition, DEFAULT_FEATURES as DEFAULT_POSITIONING_FEATURES};
+use ttf_parser::gsub::GlyphInfo;
+use ttf_parser::opentype::merge_feature_settings;
+use ttf_parser::fallback::FontFallback;
+use ttf_parser::shaping::{Direction, PositionedGlyph};
+use ttf_parser::headless::{render_lines, RenderOptions};
+use ttf_parser::hinting::HintedGlyph;
+use ttf_parser::metrics::LineMetrics;
+use ttf_parser::missing::{hex_box_advance, hex_box_glyph, missing_characters, MissingGlyphs};
+use ttf_parser::paragraph::{layout_text, ParagraphOptions};
+use ttf_parser::svg::text_svg;
+use ttf_parser::glyph::Glyph;
+use ttf_parser::specimen::{specimen_png_pages, specimen_svg_pages, SpecimenGlyph, SpecimenOptions};
+use ttf_parser::table::{CmapFormat4, HheaTable, HmtxTable};
+use std::collections::HashMap;
 
 fn main() -> Result<(), String> {
     let config = Config::from_args();
 
-    let bytes = read_file_to_byte_array(&config.font_path);
-    let mut byte_buffer = ByteBuffer::new(bytes);
+    let (mut parsers, fonts): (Vec<FontParser>, Vec<FontFile>) = config.font_paths.iter().map(|path| FontFile::load(path)).unzip();
+    let primary = &fonts[0];
+    let units_per_em = primary.head_table.units_per_em;
+    let line_metrics = LineMetrics::new(&primary.hhea_table, primary.os2_table.as_ref());
 
-    let table_records = read_table_directory(&mut byte_buffer);
-    let mut parser = FontParser::new(byte_buffer, table_records);
+    // Each font is hinted with its own program at the same pixel size. Headless output is drawn
+    // at --size, so that is the size its outlines are fitted to.
+    let headless = config.png_path.is_some() || config.pgm_path.is_some() || config.svg_path.is_some();
+    let hinting_ppem = match config.hinting_ppem {
+        Some(_) if headless => Some((config.pixel_size.round() as u16).max(1)),
+        ppem => ppem,
+    };
+    let mut hinters = match hinting_ppem {
+        Some(ppem) => parsers.iter_mut().zip(&fonts).map(|(parser, font)| font.hinter(parser, ppem)).collect::<Result<Vec<_>, _>>()?,
+        None => Vec::new(),
+    };
 
-    let head_table = parser.read_head_table().expect("head table not found");
-    let maxp_table = parser.read_maxp_table().expect("maxp table not found");
-    let total_glyphs = maxp_table.num_glyphs;
-    let hhea_table = parser.read_hhea_table().expect("hhea table not found");
-    let hmtx_table = parser.read_hmtx_table(total_glyphs, hhea_table.num_h_metrics).expect("hmtx table not found");
-    let glyph_offsets = parser.read_glyph_offsets(total_glyphs, head_table.index_to_loc_format).expect("glyph offsets not found");
-    let cmap_table = parser.read_cmap_table().expect("cmap table not found");
-    let cmap_subtable = parser.read_cmap_subtable(&cmap_table).expect("cmap subtable not found");
-
-    let sdl_context: Sdl = sdl2::init()?;
-    let video_subsystem = sdl_context.video()?;
-
-    let (width, height) = (800, 800);
-
-    let window: Window = video_subsystem
-        .window("Glyph Renderer", width, height)
-        .position_centered()
-        .resizable()
-        .build()
-        .map_err(|e| e.to_string())?;
-
-    let mut canvas: Canvas<Window> = window.into_canvas().build().map_err(|e| e.to_string())?;
+    if config.specimen_path.is_some() {
+        return write_specimen(&config, &mut parsers[0], &primary.glyph_offsets, &primary.hmtx_table, &primary.hhea_table, units_per_em, &primary.cmap_subtable);
+    }
 
-    let mut event_pump = sdl_context.event_pump()?;
+    let fallback = FontFallback { fonts: fonts.iter().map(FontFile::fallback_font).collect() };
 
     let mut glyphs = Vec::new();
+    let mut positions = Vec::new();
+    let mut glyph_fonts = Vec::new(); // The font index of each glyph, line by line
+    let mut line_offsets = Vec::new();
+    let wrap_width = config.wrap_width.map(|width| (width * units_per_em as f32 / config.pixel_size).round() as i32);
 
     if config.print_all_glyphs {
         let mut all_glyphs = vec![];
-        for i in 0..total_glyphs {
-            if let Some(glyph_data) = parser.read_glyph(&glyph_offsets, i, &hmtx_table) {
+        for i in 0..primary.maxp_table.num_glyphs {
+            if let Some(glyph_data) = parsers[0].read_glyph(&primary.glyph_offsets, i, &primary.hmtx_table) {
                 all_glyphs.push(glyph_data);
             }
         }
-        // Create 2D vector of glyphs with 15 glyphs per line
+        // Create 2D vector of glyphs with 15 glyphs per line, or as many as fit the wrap width
         let mut line_glyphs = vec![];
-        for (i, glyph) in all_glyphs.into_iter().enumerate() {
-            if i % 15 == 0 && !line_glyphs.is_empty() {
+        let mut line_width = 0.0;
+        for glyph in all_glyphs {
+            let line_full = match wrap_width {
+                Some(width) => line_width + glyph.advance_width > width as f64,
+                None => line_glyphs.len() == 15,
+            };
+            if line_full && !line_glyphs.is_empty() {
                 glyphs.push(line_glyphs);
                 line_glyphs = vec![];
+                line_width = 0.0;
             }
+            line_width += glyph.advance_width;
             line_glyphs.push(glyph);
         }
         if !line_glyphs.is_empty() {
             glyphs.push(line_glyphs);
         }
+
+        let settings = merge_feature_settings(&DEFAULT_POSITIONING_FEATURES, &config.features);
+        for line in &glyphs {
+            let glyph_infos: Vec<GlyphInfo> = line.iter().map(|glyph| GlyphInfo { glyph_index: glyph.glyph_index, ..GlyphInfo::default() }).collect();
+            positions.push(fallback.fonts[0].font.position(&glyph_infos, &config.script, &config.language, Direction::LeftToRight, &settings));
+            glyph_fonts.push(vec![0; line.len()]);
+        }
     } else {
-        let mut glyph_indices = Vec::new();
-        for ch in config.input_string.chars() {
-            if let Some(glyph_index) = cmap_subtable.char_to_glyph_index(ch as u16) {
-                glyph_indices.push(glyph_index);
+        let missing = missing_characters(&config.input_string, &|ch| fallback.covers(ch));
+        if !missing.is_empty() {
+            println!("{} characters not found in any font:", missing.len());
+            for missing in &missing {
+                println!("  U+{:04X} {:?} x{}", missing.ch as u32, missing.ch, missing.count);
             }
         }
 
-        let mut line_glyphs = Vec::new();
-        for glyph_index in glyph_indices {
-            if let Some(glyph_data) = parser.read_glyph(&glyph_offsets, glyph_index, &hmtx_table) {
-                line_glyphs.push(glyph_data);
+        // Hex boxes count as a font after the real ones, with the index of their character in
+        // the missing list as their glyph id
+        let hex_box_font = fonts.len();
+        let paragraph_options = ParagraphOptions { width: wrap_width, alignment: config.alignment };
+        let lines = layout_text(&config.input_string, config.direction, &paragraph_options, &|text, direction| {
+            let chars: Vec<char> = text.chars().collect();
+            fallback.shape_paragraph(text, &config.script, &config.language, direction, &config.features).into_iter()
+                .filter_map(|glyph| match config.missing_glyphs {
+                    _ if glyph.glyph_id != 0 => Some(glyph),
+                    MissingGlyphs::Skip => None,
+                    MissingGlyphs::Notdef => Some(glyph),
+                    MissingGlyphs::HexBox => {
+                        let ch = chars[glyph.cluster];
+                        match missing.iter().position(|missing| missing.ch == ch) {
+                            Some(index) => Some(PositionedGlyph { glyph_id: index as u16, font_index: hex_box_font, x_advance: hex_box_advance(ch, units_per_em), ..glyph }),
+                            None => Some(glyph),
+                        }
+                    }
+                })
+                .collect()
+        });
+        for line in lines {
+            let mut line_glyphs = Vec::new();
+            let mut line_positions = Vec::new();
+            let mut line_fonts = Vec::new();
+            for positioned in line.glyphs {
+                let glyph_data = match fonts.get(positioned.font_index) {
+                    Some(font) => parsers[positioned.font_index].read_glyph(&font.glyph_offsets, positioned.glyph_id, &font.hmtx_table),
+                    None => Some(hex_box_glyph(missing[positioned.glyph_id as usize].ch, positioned.glyph_id, units_per_em)),
+                };
+                if let Some(glyph_data) = glyph_data {
+                    line_glyphs.push(glyph_data);
+                    line_positions.push(positioned.position());
+                    line_fonts.push(positioned.font_index);
+                }
             }
+            glyphs.push(line_glyphs);
+            positions.push(line_positions);
+            glyph_fonts.push(line_fonts);
+            line_offsets.push(line.x);
+        }
+    }
+
+    // Hinted outlines in pixels, kept so headless rendering can draw them without rescaling
+    let mut hinted_glyphs: Vec<Vec<Option<HintedGlyph>>> = glyphs.iter().map(|line| vec![None; line.len()]).collect();
+    let glyph_slots = glyphs.iter_mut().flatten().zip(positions.iter_mut().flatten()).zip(glyph_fonts.iter().flatten()).zip(hinted_glyphs.iter_mut().flatten());
+    for (((glyph, position), &font_index), hinted_glyph) in glyph_slots {
+        // Shaping scaled the positions of fallback glyphs to the primary font's units already
+        let scale = fallback.scale(font_index);
+        if let Some(hinter) = hinters.get_mut(font_index) {
+            match hinter.hint_glyph(glyph) {
+                Ok(hinted) => {
+                    let hinted_outline = hinted.to_glyph(glyph, fonts[font_index].head_table.units_per_em);
+                    // Hinting rounds the advance to the pixel grid, so the shaped advance follows
+                    if position.x_advance != 0 {
+                        position.x_advance += ((hinted_outline.advance_width - glyph.advance_width) * scale).round() as i32;
+                    }
+                    *glyph = hinted_outline;
+                    *hinted_glyph = Some(hinted);
+                }
+                Err(error) => println!("Glyph {} left unhinted: {}", glyph.glyph_index, error),
+            }
+        }
+        if font_index != 0 && font_index != fonts.len() {
+            *glyph = glyph.scaled(scale);
         }
-        glyphs.push(line_glyphs);
     }
 
-    let mut app_state = AppState::new(glyphs, width as i16, height as i16, config.debug, config.outline_thickness)?;
+    if config.png_path.is_some() || config.pgm_path.is_some() || config.svg_path.is_some() {
+        let options = RenderOptions {
+            pixel_size: config.pixel_size,
+            foreground: config.foreground,
+            background: config.background,
+            padding: config.padding,
+            subpixel_order: config.subpixel_order,
+        };
+        if config.png_path.is_some() || config.pgm_path.is_some() {
+            let image = render_lines(&glyphs, &positions, &line_offsets, &hinted_glyphs, units_per_em, &line_metrics, &options);
+            if let Some(path) = &config.png_path {
+                std::fs::write(path, image.to_png()).map_err(|e| e.to_string())?;
+            }
+            if let Some(path) = &config.pgm_path {
+                std::fs::write(path, image.to_pgm()).map_err(|e| e.to_string())?;
+            }
+        }
+        if let Some(path) = &config.svg_path {
+            std::fs::write(path, text_svg(&glyphs, &positions, &line_offsets, units_per_em, &line_metrics, &options)).map_err(|e| e.to_string())?;
+        }
+        return Ok(());
+    }
+
+    run_viewer(&config, glyphs, positions, line_offsets, line_metrics.line_height() as f64, glyph_fonts)
+}
+
+// Shows the glyphs in a window that can be zoomed with the wheel and panned by dragging
+#[cfg(feature = "sdl2")]
+fn run_viewer(config: &Config, glyphs: Vec<Vec<Glyph>>, positions: Vec<Vec<GlyphPosition>>, line_offsets: Vec<i32>, line_height: f64, glyph_fonts: Vec<Vec<usize>>) -> Result<(), String> {
+    use renderer::AppState;
+    use sdl2::event::{Event, WindowEvent};
+    use sdl2::keyboard::Keycode;
+    use sdl2::mouse::MouseButton;
+    use sdl2::render::Canvas;
+    use sdl2::video::Window;
+    use sdl2::Sdl;
+
+    let sdl_context: Sdl = sdl2::init()?;
+    let video_subsystem = sdl_context.video()?;
+
+    let (width, height) = (800, 800);
+
+    let window: Window = video_subsystem
+        .window("Glyph Renderer", width, height)
+        .position_centered()
+        .resizable()
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let mut canvas: Canvas<Window> = window.into_canvas().build().map_err(|e| e.to_string())?;
+
+    let mut event_pump = sdl_context.event_pump()?;
+
+    let mut app_state = AppState::new(glyphs, positions, width as i16, height as i16, config.debug, config.outline_thickness)?;
+    app_state.set_line_layout(line_offsets, line_height);
+    app_state.set_glyph_fonts(glyph_fonts);
 
     'running: loop {
         let mouse_state = event_pump.mouse_state();
@@ -110,25 +238,17 @@ fn main() -> Result<(), String> {
                         app_state.zoom(false, mouse_x, mouse_y);
                     }
                 },
-                Event::MouseButtonDown { x, y, mouse_btn, .. } => {
-                    if mouse_btn == MouseButton::Left {
-                        app_state.start_drag(x, y);
-                    }
+                Event::MouseButtonDown { x, y, mouse_btn: MouseButton::Left, .. } => {
+                    app_state.start_drag(x, y);
                 },
-                Event::MouseButtonUp { mouse_btn, .. } => {
-                    if mouse_btn == MouseButton::Left {
-                        app_state.end_drag();
-                    }
+                Event::MouseButtonUp { mouse_btn: MouseButton::Left, .. } => {
+                    app_state.end_drag();
                 },
-                Event::MouseMotion { x, y, mousestate, .. } => {
-                    if mousestate.left() {
-                        app_state.update_drag(x, y);
-                    }
+                Event::MouseMotion { x, y, mousestate, .. } if mousestate.left() => {
+                    app_state.update_drag(x, y);
                 },
-                Event::Window { win_event, .. } => {
-                    if let WindowEvent::Resized(width, height) = win_event {
-                        app_state.update_canvas_dimensions(width as i16, height as i16);
-                    }
+                Event::Window { win_event: WindowEvent::Resized(width, height), .. } => {
+                    app_state.update_canvas_dimensions(width as i16, height as i16);
                 },
                 _ => {}
             }
@@ -139,3 +259,64 @@ fn main() -> Result<(), String> {
 
     Ok(())
 }
+
+#[cfg(not(feature = "sdl2"))]
+fn run_viewer(_config: &Config, _glyphs: Vec<Vec<Glyph>>, _positions: Vec<Vec<GlyphPosition>>, _line_offsets: Vec<i32>, _line_height: f64, _glyph_fonts: Vec<Vec<usize>>) -> Result<(), String> {
+    Err("Built without the sdl2 feature, so there is no viewer window; use --png, --pgm, --svg or --specimen".to_string())
+}
+
+// Renders every glyph into specimen pages. A single page is written to the --specimen path; more
+// pages get their number inserted before the extension.
+fn write_specimen(config: &Config, parser: &mut FontParser, glyph_offsets: &Vec<u32>, hmtx_table: &HmtxTable, hhea_table: &HheaTable, units_per_em: u16, cmap_subtable: &CmapFormat4) -> Result<(), String> {
+    let path = config.specimen_path.as_deref().unwrap_or_default();
+    let post_table = parser.read_post_table();
+    let mut code_points = cmap_subtable.code_points_by_glyph();
+
+    let glyphs: Vec<SpecimenGlyph> = (0..glyph_offsets.len().saturating_sub(1) as u16)
+        .filter_map(|glyph_index| parser.read_glyph(glyph_offsets, glyph_index, hmtx_table))
+        .map(|glyph| SpecimenGlyph {
+            name: post_table.as_ref().and_then(|post| post.glyph_name(glyph.glyph_index)).map(|name| name.to_string()),
+            code_points: code_points.remove(&glyph.glyph_index).unwrap_or_default(),
+            glyph,
+        })
+        .collect();
+
+    let options = SpecimenOptions {
+        pixel_size: config.pixel_size,
+        columns: config.specimen_columns,
+        rows_per_page: config.specimen_rows,
+        foreground: config.foreground,
+        background: config.background,
+        ..SpecimenOptions::default()
+    };
+
+    let pages: Vec<Vec<u8>> = if path.ends_with(".svg") {
+        specimen_svg_pages(&glyphs, units_per_em, hhea_table, &options).into_iter().map(String::into_bytes).collect()
+    } else {
+        // Labels use the font's own ASCII glyphs, and hex boxes for any it lacks, such as in
+        // symbol fonts, so they never vanish
+        let mut label_glyphs = HashMap::new();
+        for ch in ' '..='~' {
+            let glyph = cmap_subtable.char_to_glyph_index(ch as u16)
+                .filter(|&glyph_index| glyph_index != 0)
+                .and_then(|glyph_index| parser.read_glyph(glyph_offsets, glyph_index, hmtx_table))
+                .unwrap_or_else(|| hex_box_glyph(ch, 0, units_per_em));
+            label_glyphs.insert(ch, glyph);
+        }
+        specimen_png_pages(&glyphs, units_per_em, hhea_table, &label_glyphs, &options).iter().map(|image| image.to_png()).collect()
+    };
+
+    for (page, data) in pages.iter().enumerate() {
+        let page_path = if pages.len() == 1 {
+            path.to_string()
+        } else {
+            match path.rfind('.') {
+                Some(dot) => format!("{}-{}{}", &path[..dot], page + 1, &path[dot..]),
+                None => format!("{}-{}", path, page + 1),
+            }
+        };
+        std::fs::write(&page_path, data).map_err(|e| e.to_string())?;
+    }
+    println!("Wrote {} glyphs to {} page(s)", glyphs.len(), pages.len());
+    Ok(())
+}
@@ -0,0 +1,220 @@
+use crate::glyph::Glyph;
+use crate::table::{HheaTable, Os2Table, VheaTable, VmtxTable, VorgTable};
+
+// Horizontal metrics of one glyph in font units. The right side bearing is what is left of the
+// advance after the left side bearing and the glyph's width.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HorizontalMetrics {
+    pub advance_width: i32,
+    pub left_side_bearing: i32,
+    pub right_side_bearing: i32,
+}
+
+// OS/2 fsSelection bit telling layout to space lines by the typographic metrics
+const USE_TYPO_METRICS: u16 = 1 << 7;
+
+// Baseline spacing of horizontal text in font units. Uses the OS/2 typographic metrics when the
+// font asks for them, hhea otherwise, and the OS/2 Windows metrics when hhea has none.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineMetrics {
+    pub ascender: i32,
+    pub descender: i32,
+    pub line_gap: i32,
+}
+
+impl LineMetrics {
+    pub fn new(hhea_table: &HheaTable, os2_table: Option<&Os2Table>) -> Self {
+        match os2_table {
+            Some(os2) if os2.fs_selection & USE_TYPO_METRICS != 0 => LineMetrics {
+                ascender: os2.typo_ascender as i32,
+                descender: os2.typo_descender as i32,
+                line_gap: os2.typo_line_gap as i32,
+            },
+            Some(os2) if hhea_table.ascender == 0 && hhea_table.descender == 0 => LineMetrics {
+                ascender: os2.win_ascent as i32,
+                descender: -(os2.win_descent as i32),
+                line_gap: 0,
+            },
+            _ => LineMetrics {
+                ascender: hhea_table.ascender as i32,
+                descender: hhea_table.descender as i32,
+                line_gap: hhea_table.line_gap as i32,
+            },
+        }
+    }
+
+    // Distance from one baseline to the next
+    pub fn line_height(&self) -> i32 {
+        self.ascender - self.descender + self.line_gap
+    }
+}
+
+// Vertical layout metrics for every glyph. Uses vhea/vmtx (and VORG for origins) when the font
+// has them; otherwise every glyph advances by the OS/2 typographic ascender to descender, with
+// its vertical origin on the ascender line, falling back to hhea when OS/2 is missing too.
+#[derive(Debug)]
+pub struct VerticalMetrics {
+    vmtx_table: Option<VmtxTable>,
+    vorg_table: Option<VorgTable>,
+    pub ascender: i16,
+    pub descender: i16,
+    pub line_gap: i16,
+    default_advance_height: u16,
+    default_origin_y: i16,
+}
+
+impl VerticalMetrics {
+    pub fn new(vhea_table: Option<VheaTable>, vmtx_table: Option<VmtxTable>, vorg_table: Option<VorgTable>, os2_table: Option<&Os2Table>, hhea_table: &HheaTable) -> Self {
+        let (typo_ascender, typo_descender, typo_line_gap) = match os2_table {
+            Some(os2) => (os2.typo_ascender, os2.typo_descender, os2.typo_line_gap),
+            None => (hhea_table.ascender, hhea_table.descender, hhea_table.line_gap),
+        };
+        let default_advance_height = (typo_ascender as i32 - typo_descender as i32).max(0) as u16;
+
+        // vhea measures from the vertical centre line, so without it assume a centred em box
+        let (ascender, descender, line_gap) = match &vhea_table {
+            Some(vhea) => (vhea.ascender, vhea.descender, vhea.line_gap),
+            None => {
+                let half = (default_advance_height / 2) as i16;
+                (half, -half, typo_line_gap)
+            }
+        };
+        // Without vmtx there is nothing to index, so it is dropped along with vhea
+        let vmtx_table = vmtx_table.filter(|_| vhea_table.is_some());
+
+        VerticalMetrics {
+            vmtx_table,
+            vorg_table,
+            ascender,
+            descender,
+            line_gap,
+            default_advance_height,
+            default_origin_y: typo_ascender,
+        }
+    }
+
+    pub fn has_vertical_tables(&self) -> bool {
+        self.vmtx_table.is_some()
+    }
+
+    pub fn advance_height(&self, glyph_index: u16) -> u16 {
+        self.vmtx_table.as_ref()
+            .and_then(|vmtx| vmtx.advance_heights.get(glyph_index as usize).copied())
+            .unwrap_or(self.default_advance_height)
+    }
+
+    // Y coordinate of the vertical origin, the point the pen sits on when moving down a column
+    pub fn vertical_origin_y(&self, glyph: &Glyph) -> i16 {
+        if let Some(vorg) = &self.vorg_table {
+            return vorg.vert_origin_y(glyph.glyph_index);
+        }
+        match self.vmtx_table.as_ref().and_then(|vmtx| vmtx.top_side_bearings.get(glyph.glyph_index as usize)) {
+            Some(&top_side_bearing) => glyph.ymax.saturating_add(top_side_bearing),
+            None => self.default_origin_y,
+        }
+    }
+
+    // Distance from the vertical origin down to the top of the glyph's bounding box
+    pub fn top_side_bearing(&self, glyph: &Glyph) -> i16 {
+        match self.vmtx_table.as_ref().and_then(|vmtx| vmtx.top_side_bearings.get(glyph.glyph_index as usize)) {
+            Some(&top_side_bearing) if self.vorg_table.is_none() => top_side_bearing,
+            _ => self.vertical_origin_y(glyph).saturating_sub(glyph.ymax),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glyph(glyph_index: u16, ymax: i16) -> Glyph {
+        Glyph {
+            glyph_index,
+            num_contours: 0,
+            xmin: 0,
+            ymin: 0,
+            xmax: 0,
+            ymax,
+            end_pts_of_contours: vec![],
+            x_coordinates: vec![],
+            y_coordinates: vec![],
+            flags: vec![],
+            processed_points: vec![],
+            raw_end_pts_of_contours: vec![],
+            instructions: vec![],
+            advance_width: 0.0,
+            left_side_bearing: 0,
+        }
+    }
+
+    fn hhea() -> HheaTable {
+        HheaTable { ascender: 850, descender: -250, line_gap: 0, num_h_metrics: 2 }
+    }
+
+    fn os2() -> Os2Table {
+        Os2Table {
+            version: 4,
+            x_avg_char_width: 500,
+            weight_class: 400,
+            width_class: 5,
+            fs_selection: 0,
+            typo_ascender: 880,
+            typo_descender: -120,
+            typo_line_gap: 40,
+            win_ascent: 900,
+            win_descent: 200,
+            x_height: 500,
+            cap_height: 700,
+        }
+    }
+
+    fn vhea() -> Option<VheaTable> {
+        Some(VheaTable { ascender: 500, descender: -500, line_gap: 0, advance_height_max: 1100, num_v_metrics: 2 })
+    }
+
+    fn vmtx() -> Option<VmtxTable> {
+        Some(VmtxTable { advance_heights: vec![1000, 1100], top_side_bearings: vec![100, 50] })
+    }
+
+    #[test]
+    fn vmtx_gives_advances_and_origins_from_the_top_side_bearing() {
+        let metrics = VerticalMetrics::new(vhea(), vmtx(), None, Some(&os2()), &hhea());
+        assert!(metrics.has_vertical_tables());
+        assert_eq!((metrics.ascender, metrics.descender, metrics.line_gap), (500, -500, 0));
+        assert_eq!(metrics.advance_height(1), 1100);
+        assert_eq!(metrics.vertical_origin_y(&glyph(1, 700)), 750);
+        assert_eq!(metrics.top_side_bearing(&glyph(1, 700)), 50);
+        // Glyphs past the end of vmtx take the OS/2 advance
+        assert_eq!(metrics.advance_height(5), 1000);
+    }
+
+    #[test]
+    fn vorg_overrides_the_origin_but_not_the_advance() {
+        let vorg = VorgTable { default_vert_origin_y: 880, vert_origin_y_metrics: vec![(1, 900)] };
+        let metrics = VerticalMetrics::new(vhea(), vmtx(), Some(vorg), Some(&os2()), &hhea());
+        assert_eq!(metrics.advance_height(1), 1100);
+        assert_eq!(metrics.vertical_origin_y(&glyph(1, 700)), 900);
+        assert_eq!(metrics.vertical_origin_y(&glyph(0, 700)), 880);
+        assert_eq!(metrics.top_side_bearing(&glyph(1, 700)), 200);
+    }
+
+    #[test]
+    fn without_vhea_the_os2_typographic_metrics_are_used() {
+        // vmtx alone can't be indexed without vhea, so it is ignored
+        let metrics = VerticalMetrics::new(None, vmtx(), None, Some(&os2()), &hhea());
+        assert!(!metrics.has_vertical_tables());
+        assert_eq!((metrics.ascender, metrics.descender, metrics.line_gap), (500, -500, 40));
+        assert_eq!(metrics.advance_height(1), 1000);
+        assert_eq!(metrics.vertical_origin_y(&glyph(1, 700)), 880);
+        assert_eq!(metrics.top_side_bearing(&glyph(1, 700)), 180);
+    }
+
+    #[test]
+    fn without_os2_the_hhea_metrics_are_used() {
+        let metrics = VerticalMetrics::new(None, None, None, None, &hhea());
+        assert_eq!((metrics.ascender, metrics.descender, metrics.line_gap), (550, -550, 0));
+        assert_eq!(metrics.advance_height(0), 1100);
+        assert_eq!(metrics.vertical_origin_y(&glyph(0, 700)), 850);
+        assert_eq!(metrics.top_side_bearing(&glyph(0, 700)), 150);
+    }
+}
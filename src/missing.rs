@@ -0,0 +1,146 @@
+use crate::glyph::{process_points, Glyph};
+use crate::linebreak::is_newline;
+use crate::shaping::is_default_ignorable;
+
+// How to draw characters that no font has a glyph for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissingGlyphs {
+    // Leave them out, as if they weren't in the text
+    Skip,
+    // The font's glyph 0, usually an empty box
+    #[default]
+    Notdef,
+    // A box showing the character's code point in hex digits
+    HexBox,
+}
+
+// A character of the text that no font maps, with how often it occurs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MissingCharacter {
+    pub ch: char,
+    pub count: usize,
+}
+
+// Characters of the text that `covers` says no font has, in order of first appearance. Newlines
+// and invisible characters that shaping drops anyway aren't counted.
+pub fn missing_characters(text: &str, covers: &dyn Fn(char) -> bool) -> Vec<MissingCharacter> {
+    let mut missing: Vec<MissingCharacter> = Vec::new();
+    for ch in text.chars().filter(|&ch| !is_newline(ch) && !is_default_ignorable(ch) && !covers(ch)) {
+        match missing.iter_mut().find(|missing| missing.ch == ch) {
+            Some(missing) => missing.count += 1,
+            None => missing.push(MissingCharacter { ch, count: 1 }),
+        }
+    }
+    missing
+}
+
+// Hex box layout in ems. Digits are drawn seven-segment style, two rows of two for the Basic
+// Multilingual Plane and two rows of three above it.
+const SIDE_BEARING: f64 = 0.05;
+const BORDER: f64 = 0.04;
+const PADDING: f64 = 0.06;
+const DIGIT_WIDTH: f64 = 0.16;
+const DIGIT_HEIGHT: f64 = 0.24;
+const DIGIT_GAP: f64 = 0.06;
+const STROKE: f64 = 0.04;
+
+// Segments lit for each hex digit, bits 0 to 6 being a to g: top, upper right, lower right,
+// bottom, lower left, upper left and middle
+const DIGIT_SEGMENTS: [u8; 16] = [
+    0x3F, 0x06, 0x5B, 0x4F, 0x66, 0x6D, 0x7D, 0x07, 0x7F, 0x6F, 0x77, 0x7C, 0x39, 0x5E, 0x79, 0x71,
+];
+
+fn hex_digits(ch: char) -> String {
+    match ch as u32 {
+        code_point @ 0..=0xFFFF => format!("{:04X}", code_point),
+        code_point => format!("{:06X}", code_point),
+    }
+}
+
+// Width of the box in ems, without side bearings
+fn box_width(columns: usize) -> f64 {
+    2.0 * (BORDER + PADDING) + columns as f64 * DIGIT_WIDTH + (columns - 1) as f64 * DIGIT_GAP
+}
+
+pub fn hex_box_advance(ch: char, units_per_em: u16) -> i32 {
+    let columns = hex_digits(ch).len() / 2;
+    ((box_width(columns) + 2.0 * SIDE_BEARING) * units_per_em as f64).round() as i32
+}
+
+// A synthetic glyph in font units showing the character's code point in a box, to stand in for
+// a character no font has. `glyph_index` is whatever the caller needs to tell boxes apart.
+pub fn hex_box_glyph(ch: char, glyph_index: u16, units_per_em: u16) -> Glyph {
+    let digits = hex_digits(ch);
+    let columns = digits.len() / 2;
+    let width = box_width(columns);
+    let height = 2.0 * (BORDER + PADDING + DIGIT_HEIGHT) + DIGIT_GAP;
+
+    // Contours in ems, the inside of the box wound the other way to cut it out
+    let mut contours = vec![
+        clockwise(SIDE_BEARING, 0.0, width, height),
+        counterclockwise(SIDE_BEARING + BORDER, BORDER, width - 2.0 * BORDER, height - 2.0 * BORDER),
+    ];
+    for (index, digit) in digits.chars().enumerate() {
+        let (row, column) = (index / columns, index % columns);
+        let x = SIDE_BEARING + BORDER + PADDING + column as f64 * (DIGIT_WIDTH + DIGIT_GAP);
+        let y = BORDER + PADDING + if row == 0 { DIGIT_HEIGHT + DIGIT_GAP } else { 0.0 };
+        let segments = DIGIT_SEGMENTS[digit.to_digit(16).unwrap_or(0) as usize];
+        let half = DIGIT_HEIGHT / 2.0;
+        let rectangles = [
+            (x, y + DIGIT_HEIGHT - STROKE, DIGIT_WIDTH, STROKE),
+            (x + DIGIT_WIDTH - STROKE, y + half, STROKE, half),
+            (x + DIGIT_WIDTH - STROKE, y, STROKE, half),
+            (x, y, DIGIT_WIDTH, STROKE),
+            (x, y, STROKE, half),
+            (x, y + half, STROKE, half),
+            (x, y + (DIGIT_HEIGHT - STROKE) / 2.0, DIGIT_WIDTH, STROKE),
+        ];
+        for (segment, &(x, y, width, height)) in rectangles.iter().enumerate() {
+            if segments & (1 << segment) != 0 {
+                contours.push(clockwise(x, y, width, height));
+            }
+        }
+    }
+
+    let em = units_per_em as f64;
+    let to_units = |value: f64| (value * em).round() as i16;
+    let mut x_coordinates = Vec::new();
+    let mut y_coordinates = Vec::new();
+    let mut raw_end_pts_of_contours = Vec::new();
+    for contour in &contours {
+        for &(x, y) in contour {
+            x_coordinates.push(to_units(x));
+            y_coordinates.push(to_units(y));
+        }
+        raw_end_pts_of_contours.push(x_coordinates.len() as u16 - 1);
+    }
+    let flags = vec![1; x_coordinates.len()]; // All on-curve
+    let (processed_points, end_pts_of_contours) = process_points(&x_coordinates, &y_coordinates, &flags, &raw_end_pts_of_contours);
+
+    Glyph {
+        glyph_index,
+        num_contours: contours.len() as i16,
+        xmin: to_units(SIDE_BEARING),
+        ymin: 0,
+        xmax: to_units(SIDE_BEARING + width),
+        ymax: to_units(height),
+        end_pts_of_contours,
+        x_coordinates,
+        y_coordinates,
+        flags,
+        processed_points,
+        raw_end_pts_of_contours,
+        instructions: Vec::new(),
+        advance_width: hex_box_advance(ch, units_per_em) as f64,
+        left_side_bearing: to_units(SIDE_BEARING),
+    }
+}
+
+// TrueType fills clockwise contours, y pointing up
+fn clockwise(x: f64, y: f64, width: f64, height: f64) -> Vec<(f64, f64)> {
+    vec![(x, y), (x, y + height), (x + width, y + height), (x + width, y)]
+}
+
+fn counterclockwise(x: f64, y: f64, width: f64, height: f64) -> Vec<(f64, f64)> {
+    vec![(x, y), (x + width, y), (x + width, y + height), (x, y + height)]
+}
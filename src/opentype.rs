@@ -0,0 +1,661 @@
+use crate::buffer::ByteBuffer;
+use crate::gdef::{GdefTable, GlyphClass};
+
+// Structures shared by the OpenType layout tables (GDEF, GSUB and GPOS). Offsets in these tables
+// are relative to the start of the structure holding them, so readers take absolute positions.
+
+// The glyphs a subtable applies to, each with its coverage index
+#[derive(Debug, Clone)]
+pub enum Coverage {
+    // Format 1: sorted glyph ids, the coverage index being the position in the list
+    Glyphs(Vec<u16>),
+    // Format 2: sorted (start glyph, end glyph, coverage index of the start glyph) ranges
+    Ranges(Vec<(u16, u16, u16)>),
+}
+
+impl Coverage {
+    pub fn index(&self, glyph_index: u16) -> Option<u16> {
+        match self {
+            Coverage::Glyphs(glyphs) => glyphs.binary_search(&glyph_index).ok().map(|found| found as u16),
+            Coverage::Ranges(ranges) => {
+                let found = ranges.partition_point(|&(_, end, _)| end < glyph_index);
+                match ranges.get(found) {
+                    Some(&(start, _, start_index)) if start <= glyph_index => Some(start_index + (glyph_index - start)),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    pub fn contains(&self, glyph_index: u16) -> bool {
+        self.index(glyph_index).is_some()
+    }
+}
+
+// Assigns glyphs to classes; every glyph not mentioned is in class 0
+#[derive(Debug, Clone)]
+pub enum ClassDef {
+    // Format 1: classes for a consecutive run of glyphs
+    Array { start_glyph: u16, classes: Vec<u16> },
+    // Format 2: sorted (start glyph, end glyph, class) ranges
+    Ranges(Vec<(u16, u16, u16)>),
+}
+
+impl Default for ClassDef {
+    fn default() -> Self {
+        ClassDef::Ranges(Vec::new())
+    }
+}
+
+impl ClassDef {
+    pub fn class(&self, glyph_index: u16) -> u16 {
+        match self {
+            ClassDef::Array { start_glyph, classes } => glyph_index.checked_sub(*start_glyph)
+                .and_then(|index| classes.get(index as usize).copied())
+                .unwrap_or(0),
+            ClassDef::Ranges(ranges) => {
+                let found = ranges.partition_point(|&(_, end, _)| end < glyph_index);
+                match ranges.get(found) {
+                    Some(&(start, _, class)) if start <= glyph_index => class,
+                    _ => 0,
+                }
+            }
+        }
+    }
+}
+
+// Reads an offset at the current position and resolves it against `base`; zero means absent
+pub fn read_offset16(buffer: &mut ByteBuffer, base: usize) -> Option<usize> {
+    match buffer.read_u16() {
+        0 => None,
+        offset => Some(base + offset as usize),
+    }
+}
+
+pub fn read_offset32(buffer: &mut ByteBuffer, base: usize) -> Option<usize> {
+    match buffer.read_u32() {
+        0 => None,
+        offset => Some(base + offset as usize),
+    }
+}
+
+// Tag from text such as "latn", padded with spaces as short tags are in the font
+pub fn parse_tag(text: &str) -> Option<[u8; 4]> {
+    let bytes = text.as_bytes();
+    if bytes.is_empty() || bytes.len() > 4 || !text.is_ascii() {
+        return None;
+    }
+    let mut tag = *b"    ";
+    tag[..bytes.len()].copy_from_slice(bytes);
+    Some(tag)
+}
+
+pub fn read_coverage(buffer: &mut ByteBuffer, position: usize) -> Coverage {
+    buffer.set_position(position);
+    match buffer.read_u16() {
+        1 => {
+            let glyph_count = buffer.read_u16();
+            Coverage::Glyphs(buffer.read_array::<u16>(glyph_count as usize))
+        }
+        2 => {
+            let range_count = buffer.read_u16();
+            Coverage::Ranges((0..range_count).map(|_| (buffer.read_u16(), buffer.read_u16(), buffer.read_u16())).collect())
+        }
+        _ => Coverage::Glyphs(Vec::new()), // Unknown formats cover nothing
+    }
+}
+
+pub fn read_class_def(buffer: &mut ByteBuffer, position: usize) -> ClassDef {
+    buffer.set_position(position);
+    match buffer.read_u16() {
+        1 => {
+            let start_glyph = buffer.read_u16();
+            let glyph_count = buffer.read_u16();
+            ClassDef::Array { start_glyph, classes: buffer.read_array::<u16>(glyph_count as usize) }
+        }
+        2 => {
+            let range_count = buffer.read_u16();
+            ClassDef::Ranges((0..range_count).map(|_| (buffer.read_u16(), buffer.read_u16(), buffer.read_u16())).collect())
+        }
+        _ => ClassDef::default(),
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LangSys {
+    pub required_feature_index: Option<u16>,
+    pub feature_indices: Vec<u16>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Script {
+    pub tag: [u8; 4],
+    pub default_lang_sys: Option<LangSys>,
+    pub lang_systems: Vec<([u8; 4], LangSys)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Feature {
+    pub tag: [u8; 4],
+    pub lookup_indices: Vec<u16>,
+}
+
+// Lookup flag bits
+pub const RIGHT_TO_LEFT: u16 = 0x0001;
+pub const IGNORE_BASE_GLYPHS: u16 = 0x0002;
+pub const IGNORE_LIGATURES: u16 = 0x0004;
+pub const IGNORE_MARKS: u16 = 0x0008;
+pub const USE_MARK_FILTERING_SET: u16 = 0x0010;
+pub const MARK_ATTACHMENT_TYPE: u16 = 0xFF00;
+
+// A lookup and its subtables, which GSUB and GPOS each parse into their own type
+#[derive(Debug, Clone)]
+pub struct Lookup<T> {
+    pub lookup_type: u16,
+    pub lookup_flag: u16,
+    pub mark_filtering_set: Option<u16>,
+    pub subtables: Vec<T>,
+}
+
+impl<T> Lookup<T> {
+    // Whether the lookup flag says to pass over this glyph, as classified by GDEF. Without GDEF
+    // nothing is classified and so nothing is skipped.
+    pub fn skips(&self, glyph_index: u16, gdef: Option<&GdefTable>) -> bool {
+        let gdef = match gdef {
+            Some(gdef) => gdef,
+            None => return false,
+        };
+        match gdef.glyph_class(glyph_index) {
+            GlyphClass::Base => self.lookup_flag & IGNORE_BASE_GLYPHS != 0,
+            GlyphClass::Ligature => self.lookup_flag & IGNORE_LIGATURES != 0,
+            GlyphClass::Mark => {
+                if self.lookup_flag & IGNORE_MARKS != 0 {
+                    return true;
+                }
+                if let Some(set_index) = self.mark_filtering_set {
+                    return !gdef.is_in_mark_glyph_set(set_index, glyph_index);
+                }
+                let mark_attachment_type = (self.lookup_flag & MARK_ATTACHMENT_TYPE) >> 8;
+                mark_attachment_type != 0 && gdef.mark_attachment_class(glyph_index) != mark_attachment_type
+            }
+            _ => false,
+        }
+    }
+}
+
+fn read_lang_sys(buffer: &mut ByteBuffer, position: usize) -> LangSys {
+    buffer.set_position(position);
+    buffer.skip_bytes(2); // Skip lookup order, which is reserved
+    let required_feature_index = match buffer.read_u16() {
+        0xFFFF => None,
+        index => Some(index),
+    };
+    let feature_index_count = buffer.read_u16();
+    LangSys {
+        required_feature_index,
+        feature_indices: buffer.read_array::<u16>(feature_index_count as usize),
+    }
+}
+
+pub fn read_script_list(buffer: &mut ByteBuffer, position: usize) -> Vec<Script> {
+    buffer.set_position(position);
+    let script_count = buffer.read_u16();
+    let records: Vec<([u8; 4], usize)> = (0..script_count)
+        .map(|_| (buffer.read_tag(), position + buffer.read_u16() as usize))
+        .collect();
+
+    records.into_iter()
+        .map(|(tag, script_start)| {
+            buffer.set_position(script_start);
+            let default_lang_sys = read_offset16(buffer, script_start);
+            let lang_sys_count = buffer.read_u16();
+            let lang_sys_records: Vec<([u8; 4], usize)> = (0..lang_sys_count)
+                .map(|_| (buffer.read_tag(), script_start + buffer.read_u16() as usize))
+                .collect();
+            Script {
+                tag,
+                default_lang_sys: default_lang_sys.map(|lang_sys_start| read_lang_sys(buffer, lang_sys_start)),
+                lang_systems: lang_sys_records.into_iter()
+                    .map(|(tag, lang_sys_start)| (tag, read_lang_sys(buffer, lang_sys_start)))
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+pub fn read_feature_list(buffer: &mut ByteBuffer, position: usize) -> Vec<Feature> {
+    buffer.set_position(position);
+    let feature_count = buffer.read_u16();
+    let records: Vec<([u8; 4], usize)> = (0..feature_count)
+        .map(|_| (buffer.read_tag(), position + buffer.read_u16() as usize))
+        .collect();
+
+    records.into_iter()
+        .map(|(tag, feature_start)| {
+            buffer.set_position(feature_start);
+            buffer.skip_bytes(2); // Skip feature params
+            let lookup_index_count = buffer.read_u16();
+            Feature {
+                tag,
+                lookup_indices: buffer.read_array::<u16>(lookup_index_count as usize),
+            }
+        })
+        .collect()
+}
+
+// Reads every lookup, handing each subtable's type and absolute position to `read_subtable`
+pub fn read_lookup_list<T>(buffer: &mut ByteBuffer, position: usize, mut read_subtable: impl FnMut(&mut ByteBuffer, u16, usize) -> T) -> Vec<Lookup<T>> {
+    buffer.set_position(position);
+    let lookup_count = buffer.read_u16();
+    let lookup_starts: Vec<usize> = (0..lookup_count).map(|_| position + buffer.read_u16() as usize).collect();
+
+    lookup_starts.into_iter()
+        .map(|lookup_start| {
+            buffer.set_position(lookup_start);
+            let lookup_type = buffer.read_u16();
+            let lookup_flag = buffer.read_u16();
+            let subtable_count = buffer.read_u16();
+            let subtable_starts: Vec<usize> = (0..subtable_count).map(|_| lookup_start + buffer.read_u16() as usize).collect();
+            let mark_filtering_set = if lookup_flag & USE_MARK_FILTERING_SET != 0 { Some(buffer.read_u16()) } else { None };
+            Lookup {
+                lookup_type,
+                lookup_flag,
+                mark_filtering_set,
+                subtables: subtable_starts.into_iter()
+                    .map(|subtable_start| read_subtable(buffer, lookup_type, subtable_start))
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+// A feature to apply and its value: 0 turns it off, 1 turns it on and higher values pick among
+// alternates, the way `salt=2` asks for the second alternate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeatureSetting {
+    pub tag: [u8; 4],
+    pub value: u16,
+}
+
+impl FeatureSetting {
+    pub fn new(tag: [u8; 4]) -> Self {
+        FeatureSetting { tag, value: 1 }
+    }
+
+    // Parses "liga", "+liga", "-liga" or "salt=2"
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (text, value) = match text.split_once('=') {
+            Some((tag, value)) => (tag, value.parse::<u16>().map_err(|_| format!("Invalid feature value in {}", text))?),
+            None => match text.strip_prefix('-') {
+                Some(tag) => (tag, 0),
+                None => (text.strip_prefix('+').unwrap_or(text), 1),
+            },
+        };
+        let tag = parse_tag(text).ok_or_else(|| format!("Invalid feature tag {}", text))?;
+        Ok(FeatureSetting { tag, value })
+    }
+}
+
+// Applies `overrides` on top of `defaults`: a setting replaces the default for its tag, and
+// settings with value 0 drop the feature
+pub fn merge_feature_settings(defaults: &[[u8; 4]], overrides: &[FeatureSetting]) -> Vec<FeatureSetting> {
+    let mut settings: Vec<FeatureSetting> = defaults.iter().map(|&tag| FeatureSetting::new(tag)).collect();
+    for setting in overrides {
+        settings.retain(|existing| existing.tag != setting.tag);
+        settings.push(*setting);
+    }
+    settings.retain(|setting| setting.value != 0);
+    settings
+}
+
+// The lookups that make up the enabled features for a script and language, in lookup list
+// order, each with the value of the feature that asked for it. A missing script falls back to
+// DFLT (or dflt), then latn; a missing language to the script's default. The language system's
+// required feature is always included.
+pub fn feature_lookups(scripts: &[Script], features: &[Feature], script_tag: &[u8; 4], language_tag: &[u8; 4], settings: &[FeatureSetting]) -> Vec<(u16, u16)> {
+    let script = [script_tag, b"DFLT", b"dflt", b"latn"].iter()
+        .find_map(|&tag| scripts.iter().find(|script| &script.tag == tag));
+    let lang_sys = match script {
+        Some(script) => script.lang_systems.iter()
+            .find(|(tag, _)| tag == language_tag)
+            .map(|(_, lang_sys)| lang_sys)
+            .or(script.default_lang_sys.as_ref()),
+        None => None,
+    };
+    let lang_sys = match lang_sys {
+        Some(lang_sys) => lang_sys,
+        None => return Vec::new(),
+    };
+
+    let required = lang_sys.required_feature_index.map(|index| (index, 1));
+    let enabled = lang_sys.feature_indices.iter().filter_map(|&index| {
+        let feature = features.get(index as usize)?;
+        let setting = settings.iter().rev().find(|setting| setting.tag == feature.tag && setting.value != 0)?;
+        Some((index, setting.value))
+    });
+    let mut lookups: Vec<(u16, u16)> = required.into_iter()
+        .chain(enabled)
+        .filter_map(|(index, value)| features.get(index as usize).map(|feature| (feature, value)))
+        .flat_map(|(feature, value)| feature.lookup_indices.iter().map(move |&lookup_index| (lookup_index, value)))
+        .collect();
+    // Stable, so a lookup shared by two features keeps the value of the first
+    lookups.sort_by_key(|&(lookup_index, _)| lookup_index);
+    lookups.dedup_by_key(|&mut (lookup_index, _)| lookup_index);
+    lookups
+}
+
+// Indices of the lookups that make up `feature_tags`, as for feature_lookups
+pub fn feature_lookup_indices(scripts: &[Script], features: &[Feature], script_tag: &[u8; 4], language_tag: &[u8; 4], feature_tags: &[[u8; 4]]) -> Vec<u16> {
+    let settings: Vec<FeatureSetting> = feature_tags.iter().map(|&tag| FeatureSetting::new(tag)).collect();
+    feature_lookups(scripts, features, script_tag, language_tag, &settings).into_iter()
+        .map(|(lookup_index, _)| lookup_index)
+        .collect()
+}
+
+// A nested lookup to apply at one position of a matched input sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SequenceLookup {
+    pub sequence_index: u16,
+    pub lookup_index: u16,
+}
+
+// A context rule holding glyph ids or classes, depending on the subtable format. Backtrack is
+// stored nearest glyph first, as in the font, and input leaves out the first glyph, which the
+// subtable's coverage (and rule set) already matched.
+#[derive(Debug, Clone, Default)]
+pub struct ContextRule {
+    pub backtrack: Vec<u16>,
+    pub input: Vec<u16>,
+    pub lookahead: Vec<u16>,
+    pub lookups: Vec<SequenceLookup>,
+}
+
+// Sequence context (GSUB 5, GPOS 7) and chained sequence context (GSUB 6, GPOS 8) subtables.
+// Plain contexts are read as chained ones with nothing before or after the input.
+#[derive(Debug, Clone)]
+pub enum SequenceContext {
+    // Format 1: rule sets per covered first glyph, with rules of glyph ids
+    Glyphs { coverage: Coverage, rule_sets: Vec<Vec<ContextRule>> },
+    // Format 2: rule sets per class of the first glyph, with rules of classes
+    Classes {
+        coverage: Coverage,
+        backtrack_class_def: ClassDef,
+        input_class_def: ClassDef,
+        lookahead_class_def: ClassDef,
+        rule_sets: Vec<Vec<ContextRule>>,
+    },
+    // Format 3: one coverage per position, first input glyph included
+    Coverages {
+        backtrack: Vec<Coverage>,
+        input: Vec<Coverage>,
+        lookahead: Vec<Coverage>,
+        lookups: Vec<SequenceLookup>,
+    },
+}
+
+// Positions of the next `count` glyphs before or after `index` (not included) that aren't
+// skipped, provided each satisfies `matches` along with its place in the sequence
+pub fn match_sequence(len: usize, index: usize, count: usize, forward: bool, skips: &dyn Fn(usize) -> bool, matches: impl Fn(usize, usize) -> bool) -> Option<Vec<usize>> {
+    let mut positions = Vec::with_capacity(count);
+    let mut position = index;
+    while positions.len() < count {
+        loop {
+            position = if forward { position + 1 } else { position.checked_sub(1)? };
+            if position >= len {
+                return None;
+            }
+            if !skips(position) {
+                break;
+            }
+        }
+        if !matches(positions.len(), position) {
+            return None;
+        }
+        positions.push(position);
+    }
+    Some(positions)
+}
+
+impl SequenceContext {
+    // Finds the first rule that matches with its input starting at `index`, returning the
+    // positions of the input glyphs and the rule's lookups. `glyph_at` reads the run and
+    // `skips` says which positions the lookup passes over.
+    pub fn match_at(&self, len: usize, index: usize, glyph_at: &dyn Fn(usize) -> u16, skips: &dyn Fn(usize) -> bool) -> Option<(Vec<usize>, &[SequenceLookup])> {
+        let first = glyph_at(index);
+        match self {
+            SequenceContext::Glyphs { coverage, rule_sets } => {
+                let rule_set = rule_sets.get(coverage.index(first)? as usize)?;
+                rule_set.iter().find_map(|rule| {
+                    let positions = match_rule(rule, len, index, skips, |_, position, value| glyph_at(position) == value)?;
+                    Some((positions, rule.lookups.as_slice()))
+                })
+            }
+            SequenceContext::Classes { coverage, backtrack_class_def, input_class_def, lookahead_class_def, rule_sets } => {
+                coverage.index(first)?;
+                let rule_set = rule_sets.get(input_class_def.class(first) as usize)?;
+                rule_set.iter().find_map(|rule| {
+                    let positions = match_rule(rule, len, index, skips, |sequence, position, value| {
+                        let class_def = match sequence {
+                            Sequence::Backtrack => backtrack_class_def,
+                            Sequence::Input => input_class_def,
+                            Sequence::Lookahead => lookahead_class_def,
+                        };
+                        class_def.class(glyph_at(position)) == value
+                    })?;
+                    Some((positions, rule.lookups.as_slice()))
+                })
+            }
+            SequenceContext::Coverages { backtrack, input, lookahead, lookups } => {
+                if !input.first()?.contains(first) {
+                    return None;
+                }
+                let mut positions = vec![index];
+                positions.extend(match_sequence(len, index, input.len() - 1, true, skips, |i, position| input[i + 1].contains(glyph_at(position)))?);
+                match_sequence(len, index, backtrack.len(), false, skips, |i, position| backtrack[i].contains(glyph_at(position)))?;
+                let last = *positions.last()?;
+                match_sequence(len, last, lookahead.len(), true, skips, |i, position| lookahead[i].contains(glyph_at(position)))?;
+                Some((positions, lookups.as_slice()))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Sequence {
+    Backtrack,
+    Input,
+    Lookahead,
+}
+
+// Matches a rule's input, backtrack and lookahead around `index`, returning the input positions
+fn match_rule(rule: &ContextRule, len: usize, index: usize, skips: &dyn Fn(usize) -> bool, matches: impl Fn(Sequence, usize, u16) -> bool) -> Option<Vec<usize>> {
+    let mut positions = vec![index];
+    positions.extend(match_sequence(len, index, rule.input.len(), true, skips, |i, position| matches(Sequence::Input, position, rule.input[i]))?);
+    match_sequence(len, index, rule.backtrack.len(), false, skips, |i, position| matches(Sequence::Backtrack, position, rule.backtrack[i]))?;
+    let last = *positions.last()?;
+    match_sequence(len, last, rule.lookahead.len(), true, skips, |i, position| matches(Sequence::Lookahead, position, rule.lookahead[i]))?;
+    Some(positions)
+}
+
+fn read_sequence_lookups(buffer: &mut ByteBuffer, count: u16) -> Vec<SequenceLookup> {
+    (0..count)
+        .map(|_| SequenceLookup {
+            sequence_index: buffer.read_u16(),
+            lookup_index: buffer.read_u16(),
+        })
+        .collect()
+}
+
+// Reads the coverage at each offset from `base`
+pub fn read_coverages(buffer: &mut ByteBuffer, base: usize, offsets: Vec<u16>) -> Vec<Coverage> {
+    offsets.into_iter().map(|offset| read_coverage(buffer, base + offset as usize)).collect()
+}
+
+// Rule sets for formats 1 and 2, which differ only in what the values mean
+fn read_rule_sets(buffer: &mut ByteBuffer, subtable_start: usize, chained: bool) -> Vec<Vec<ContextRule>> {
+    let rule_set_count = buffer.read_u16();
+    let rule_sets: Vec<Option<usize>> = (0..rule_set_count).map(|_| read_offset16(buffer, subtable_start)).collect();
+    rule_sets.into_iter()
+        .map(|rule_set| {
+            let rule_set_start = match rule_set {
+                Some(position) => position,
+                None => return Vec::new(),
+            };
+            buffer.set_position(rule_set_start);
+            let rule_count = buffer.read_u16();
+            let rules: Vec<usize> = (0..rule_count).map(|_| rule_set_start + buffer.read_u16() as usize).collect();
+            rules.into_iter()
+                .map(|rule_start| {
+                    buffer.set_position(rule_start);
+                    if chained {
+                        let backtrack_count = buffer.read_u16();
+                        let backtrack = buffer.read_array::<u16>(backtrack_count as usize);
+                        let input_count = buffer.read_u16();
+                        let input = buffer.read_array::<u16>(input_count.saturating_sub(1) as usize);
+                        let lookahead_count = buffer.read_u16();
+                        let lookahead = buffer.read_array::<u16>(lookahead_count as usize);
+                        let lookup_count = buffer.read_u16();
+                        ContextRule { backtrack, input, lookahead, lookups: read_sequence_lookups(buffer, lookup_count) }
+                    } else {
+                        let input_count = buffer.read_u16();
+                        let lookup_count = buffer.read_u16();
+                        let input = buffer.read_array::<u16>(input_count.saturating_sub(1) as usize);
+                        ContextRule { input, lookups: read_sequence_lookups(buffer, lookup_count), ..ContextRule::default() }
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Reads a sequence context subtable, or a chained one when `chained` is set
+pub fn read_sequence_context(buffer: &mut ByteBuffer, subtable_start: usize, chained: bool) -> Option<SequenceContext> {
+    buffer.set_position(subtable_start);
+    match buffer.read_u16() {
+        1 => {
+            let coverage = read_offset16(buffer, subtable_start)?;
+            let rule_sets = read_rule_sets(buffer, subtable_start, chained);
+            Some(SequenceContext::Glyphs { coverage: read_coverage(buffer, coverage), rule_sets })
+        }
+        2 => {
+            let coverage = read_offset16(buffer, subtable_start)?;
+            let (backtrack_class_def, input_class_def, lookahead_class_def) = if chained {
+                (read_offset16(buffer, subtable_start), read_offset16(buffer, subtable_start), read_offset16(buffer, subtable_start))
+            } else {
+                (None, read_offset16(buffer, subtable_start), None)
+            };
+            let rule_sets = read_rule_sets(buffer, subtable_start, chained);
+            let mut read_class_def_at = |position: Option<usize>| position.map(|position| read_class_def(buffer, position)).unwrap_or_default();
+            Some(SequenceContext::Classes {
+                backtrack_class_def: read_class_def_at(backtrack_class_def),
+                input_class_def: read_class_def_at(input_class_def),
+                lookahead_class_def: read_class_def_at(lookahead_class_def),
+                coverage: read_coverage(buffer, coverage),
+                rule_sets,
+            })
+        }
+        3 if chained => {
+            let backtrack_count = buffer.read_u16();
+            let backtrack_offsets = buffer.read_array::<u16>(backtrack_count as usize);
+            let input_count = buffer.read_u16();
+            let input_offsets = buffer.read_array::<u16>(input_count as usize);
+            let lookahead_count = buffer.read_u16();
+            let lookahead_offsets = buffer.read_array::<u16>(lookahead_count as usize);
+            let lookup_count = buffer.read_u16();
+            let lookups = read_sequence_lookups(buffer, lookup_count);
+            Some(SequenceContext::Coverages {
+                backtrack: read_coverages(buffer, subtable_start, backtrack_offsets),
+                input: read_coverages(buffer, subtable_start, input_offsets),
+                lookahead: read_coverages(buffer, subtable_start, lookahead_offsets),
+                lookups,
+            })
+        }
+        3 => {
+            let input_count = buffer.read_u16();
+            let lookup_count = buffer.read_u16();
+            let input_offsets = buffer.read_array::<u16>(input_count as usize);
+            let lookups = read_sequence_lookups(buffer, lookup_count);
+            Some(SequenceContext::Coverages {
+                backtrack: Vec::new(),
+                input: read_coverages(buffer, subtable_start, input_offsets),
+                lookahead: Vec::new(),
+                lookups,
+            })
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: u16 = 1;
+    const LIGATURE: u16 = 2;
+    const UNCLASSIFIED: u16 = 5;
+    // Marks 10 and 12 are in mark attachment class 1 and mark 11 in class 2. Only 12 is in mark
+    // glyph set 0.
+    const MARK_A: u16 = 10;
+    const MARK_B: u16 = 11;
+    const MARK_C: u16 = 12;
+    const MARKS: [u16; 3] = [MARK_A, MARK_B, MARK_C];
+    const ALL: [u16; 6] = [BASE, LIGATURE, UNCLASSIFIED, MARK_A, MARK_B, MARK_C];
+
+    fn gdef() -> GdefTable {
+        GdefTable {
+            glyph_class_def: ClassDef::Ranges(vec![(BASE, BASE, 1), (LIGATURE, LIGATURE, 2), (MARK_A, MARK_C, 3)]),
+            mark_attach_class_def: ClassDef::Array { start_glyph: MARK_A, classes: vec![1, 2, 1] },
+            mark_glyph_sets: vec![Coverage::Glyphs(vec![MARK_C])],
+            ..GdefTable::default()
+        }
+    }
+
+    fn lookup(lookup_flag: u16) -> Lookup<()> {
+        let mark_filtering_set = if lookup_flag & USE_MARK_FILTERING_SET != 0 { Some(0) } else { None };
+        Lookup { lookup_type: 1, lookup_flag, mark_filtering_set, subtables: vec![] }
+    }
+
+    fn skipped(lookup: &Lookup<()>, glyphs: &[u16]) -> Vec<u16> {
+        let gdef = gdef();
+        glyphs.iter().copied().filter(|&glyph| lookup.skips(glyph, Some(&gdef))).collect()
+    }
+
+    #[test]
+    fn nothing_is_skipped_without_gdef_or_flags() {
+        assert!(ALL.iter().all(|&glyph| !lookup(IGNORE_MARKS | IGNORE_BASE_GLYPHS).skips(glyph, None)));
+        assert!(skipped(&lookup(0), &ALL).is_empty());
+    }
+
+    #[test]
+    fn glyph_class_flags_skip_their_class() {
+        assert_eq!(skipped(&lookup(IGNORE_BASE_GLYPHS), &ALL), [BASE]);
+        assert_eq!(skipped(&lookup(IGNORE_LIGATURES), &ALL), [LIGATURE]);
+        assert_eq!(skipped(&lookup(IGNORE_MARKS), &ALL), MARKS);
+    }
+
+    #[test]
+    fn mark_attachment_type_skips_marks_of_other_classes() {
+        assert_eq!(skipped(&lookup(0x0100), &ALL), [MARK_B]);
+        assert_eq!(skipped(&lookup(0x0200), &ALL), [MARK_A, MARK_C]);
+    }
+
+    #[test]
+    fn mark_filtering_set_skips_marks_outside_the_set() {
+        assert_eq!(skipped(&lookup(USE_MARK_FILTERING_SET), &ALL), [MARK_A, MARK_B]);
+        // A set the font doesn't have contains no marks
+        let missing_set = Lookup { mark_filtering_set: Some(3), ..lookup(USE_MARK_FILTERING_SET) };
+        assert_eq!(skipped(&missing_set, &ALL), MARKS);
+    }
+
+    #[test]
+    fn ignore_marks_wins_over_mark_filters() {
+        assert_eq!(skipped(&lookup(IGNORE_MARKS | USE_MARK_FILTERING_SET), &MARKS), MARKS);
+        assert_eq!(skipped(&lookup(IGNORE_MARKS | 0x0100), &MARKS), MARKS);
+    }
+
+    #[test]
+    fn mark_filtering_set_wins_over_mark_attachment_type() {
+        // Class 2 would keep only MARK_B, but the set keeps only MARK_C
+        assert_eq!(skipped(&lookup(USE_MARK_FILTERING_SET | 0x0200), &MARKS), [MARK_A, MARK_B]);
+    }
+}
@@ -0,0 +1,202 @@
+use crate::glyph::Glyph;
+use crate::hinting::HintedGlyph;
+
+// A piece of a closed contour, with y pointing up
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Segment {
+    Line((f32, f32), (f32, f32)),
+    Quad((f32, f32), (f32, f32), (f32, f32)),
+}
+
+impl Segment {
+    pub fn start(&self) -> (f32, f32) {
+        match *self {
+            Segment::Line(p0, _) | Segment::Quad(p0, _, _) => p0,
+        }
+    }
+
+    pub fn end(&self) -> (f32, f32) {
+        match *self {
+            Segment::Line(_, p1) | Segment::Quad(_, _, p1) => p1,
+        }
+    }
+
+    fn map(&self, f: impl Fn((f32, f32)) -> (f32, f32)) -> Segment {
+        match *self {
+            Segment::Line(p0, p1) => Segment::Line(f(p0), f(p1)),
+            Segment::Quad(p0, p1, p2) => Segment::Quad(f(p0), f(p1), f(p2)),
+        }
+    }
+}
+
+// Glyph outline as explicit line and quadratic segments, with the implied on-curve points of
+// consecutive off-curve points filled in
+#[derive(Debug, Clone, Default)]
+pub struct Outline {
+    pub contours: Vec<Vec<Segment>>,
+}
+
+impl Outline {
+    // Font units, shifted so the glyph origin (left phantom point) is at x = 0
+    pub fn from_glyph(glyph: &Glyph) -> Self {
+        let origin_x = glyph.origin_x() as f32;
+        let points: Vec<(f32, f32)> = glyph.x_coordinates.iter().zip(&glyph.y_coordinates)
+            .map(|(&x, &y)| (x as f32 - origin_x, y as f32))
+            .collect();
+        let on_curve: Vec<bool> = glyph.flags.iter().map(|&flag| (flag & 1) != 0).collect();
+        Self::from_points(&points, &on_curve, &glyph.raw_end_pts_of_contours)
+    }
+
+    // Hinted outlines are already in pixels
+    pub fn from_hinted_glyph(hinted: &HintedGlyph) -> Self {
+        Self::from_points(&hinted.points, &hinted.on_curve, &hinted.end_pts_of_contours)
+    }
+
+    pub fn from_points(points: &[(f32, f32)], on_curve: &[bool], end_pts_of_contours: &[u16]) -> Self {
+        let mut contours = Vec::with_capacity(end_pts_of_contours.len());
+        let mut start = 0;
+        for &end in end_pts_of_contours {
+            let end = end as usize;
+            if end < start || end >= points.len() || end >= on_curve.len() {
+                break;
+            }
+            let contour = contour_segments(&points[start..=end], &on_curve[start..=end]);
+            if !contour.is_empty() {
+                contours.push(contour);
+            }
+            start = end + 1;
+        }
+        Outline { contours }
+    }
+
+    // Applies `x * scale_x + dx`, `y * scale_y + dy` to every point
+    pub fn transform(&self, scale_x: f32, scale_y: f32, dx: f32, dy: f32) -> Outline {
+        Outline {
+            contours: self.contours.iter()
+                .map(|contour| contour.iter()
+                    .map(|segment| segment.map(|(x, y)| (x * scale_x + dx, y * scale_y + dy)))
+                    .collect())
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contours.is_empty()
+    }
+
+    // Bounds of all points including off-curve control points, as (min_x, min_y, max_x, max_y)
+    pub fn control_bounds(&self) -> Option<(f32, f32, f32, f32)> {
+        let mut bounds: Option<(f32, f32, f32, f32)> = None;
+        let mut include = |(x, y): (f32, f32)| {
+            bounds = Some(match bounds {
+                Some((min_x, min_y, max_x, max_y)) => (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)),
+                None => (x, y, x, y),
+            });
+        };
+        for segment in self.contours.iter().flatten() {
+            match *segment {
+                Segment::Line(p0, p1) => {
+                    include(p0);
+                    include(p1);
+                }
+                Segment::Quad(p0, p1, p2) => {
+                    include(p0);
+                    include(p1);
+                    include(p2);
+                }
+            }
+        }
+        bounds
+    }
+
+    // Exact bounds of the curves themselves: endpoints plus any quadratic extrema, so control
+    // points pulled outside the curve do not widen the box
+    pub fn tight_bounds(&self) -> Option<(f32, f32, f32, f32)> {
+        let mut bounds: Option<(f32, f32, f32, f32)> = None;
+        let mut include = |(x, y): (f32, f32)| {
+            bounds = Some(match bounds {
+                Some((min_x, min_y, max_x, max_y)) => (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)),
+                None => (x, y, x, y),
+            });
+        };
+        for segment in self.contours.iter().flatten() {
+            include(segment.start());
+            include(segment.end());
+            if let Segment::Quad(p0, p1, p2) = *segment {
+                // Each axis peaks where its derivative 2((1-t)(p1-p0) + t(p2-p1)) is zero
+                let extremum = |a: f32, b: f32, c: f32| {
+                    let denominator = a - 2.0 * b + c;
+                    if denominator == 0.0 {
+                        return None;
+                    }
+                    let t = (a - b) / denominator;
+                    (t > 0.0 && t < 1.0).then_some(t)
+                };
+                for t in [extremum(p0.0, p1.0, p2.0), extremum(p0.1, p1.1, p2.1)].into_iter().flatten() {
+                    let mt = 1.0 - t;
+                    include((
+                        mt * mt * p0.0 + 2.0 * mt * t * p1.0 + t * t * p2.0,
+                        mt * mt * p0.1 + 2.0 * mt * t * p1.1 + t * t * p2.1,
+                    ));
+                }
+            }
+        }
+        bounds
+    }
+}
+
+fn midpoint(a: (f32, f32), b: (f32, f32)) -> (f32, f32) {
+    ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0)
+}
+
+fn contour_segments(points: &[(f32, f32)], on_curve: &[bool]) -> Vec<Segment> {
+    let count = points.len();
+    if count < 2 {
+        return vec![];
+    }
+
+    // Start from an on-curve point, or the implied one between the first two off-curve points
+    let (start_point, first) = match on_curve.iter().position(|&on| on) {
+        Some(index) => (points[index], index),
+        None => (midpoint(points[0], points[1]), 0),
+    };
+
+    let mut segments = Vec::with_capacity(count);
+    let mut current = start_point;
+    let mut control: Option<(f32, f32)> = None;
+    for step in 1..=count {
+        let index = (first + step) % count;
+        let point = points[index];
+        // Closing back on the start point when it was implied rather than stored
+        let is_closing = step == count;
+        if on_curve[index] || is_closing {
+            let target = if is_closing { start_point } else { point };
+            if !is_closing || on_curve[index] {
+                match control.take() {
+                    Some(c) => segments.push(Segment::Quad(current, c, target)),
+                    None => segments.push(Segment::Line(current, target)),
+                }
+            } else {
+                // The first point is off-curve and the start was implied from it
+                match control.take() {
+                    Some(c) => {
+                        let implied = midpoint(c, point);
+                        segments.push(Segment::Quad(current, c, implied));
+                        segments.push(Segment::Quad(implied, point, target));
+                    }
+                    None => segments.push(Segment::Quad(current, point, target)),
+                }
+            }
+            current = target;
+        } else {
+            if let Some(c) = control {
+                let implied = midpoint(c, point);
+                segments.push(Segment::Quad(current, c, implied));
+                current = implied;
+            }
+            control = Some(point);
+        }
+    }
+
+    segments
+}
@@ -0,0 +1,234 @@
+use std::ops::Range;
+
+use crate::bidi::BidiParagraph;
+use crate::linebreak::{is_newline, line_breaks, BreakOpportunity};
+use crate::shaping::{Direction, PositionedGlyph};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Alignment {
+    #[default]
+    Left,
+    Right,
+    Center,
+    // Stretches the spaces of wrapped lines to fill the width. The last line of a paragraph
+    // starts on the paragraph's side.
+    Justify,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParagraphOptions {
+    pub width: Option<i32>, // Wrap width in font units; None only breaks at newlines
+    pub alignment: Alignment,
+}
+
+// One laid out line of text in font units: its glyphs in visual order, how far it starts from
+// the left edge of the text, and its width. Clusters index the characters of the whole text.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TextLine {
+    pub glyphs: Vec<PositionedGlyph>,
+    pub x: i32,
+    pub width: i32,
+}
+
+// Breaks text into lines at its newlines and, given a width, wherever UAX #14 allows a break
+// before the text would overflow it, then aligns them. Words wider than the line overflow it.
+// `shape` shapes a line of text, given the direction of the paragraph it's in, into positioned
+// glyphs with clusters counted from the start of that line.
+pub fn layout_text(text: &str, direction: Option<Direction>, options: &ParagraphOptions, shape: &dyn Fn(&str, Option<Direction>) -> Vec<PositionedGlyph>) -> Vec<TextLine> {
+    let chars: Vec<char> = text.chars().collect();
+    let breaks = line_breaks(&chars);
+
+    let mut lines = Vec::new();
+    // The direction of each line's paragraph and whether the line ends it, which justification
+    // leaves the line alone for
+    let mut line_paragraphs = Vec::new();
+    let mut start = 0;
+    for end in (1..=chars.len()).filter(|&index| breaks[index] == BreakOpportunity::Mandatory) {
+        let mut content_end = end;
+        while content_end > start && is_newline(chars[content_end - 1]) {
+            content_end -= 1;
+        }
+        let paragraph_direction = BidiParagraph::new(&chars[start..content_end], direction).direction();
+        let shape_line = |range: Range<usize>| -> TextLine {
+            let line_text: String = chars[range.clone()].iter().collect();
+            let glyphs: Vec<PositionedGlyph> = shape(&line_text, Some(paragraph_direction)).into_iter()
+                .map(|glyph| PositionedGlyph { cluster: glyph.cluster + range.start, ..glyph })
+                .collect();
+            let width = glyphs.iter().map(|glyph| glyph.x_advance).sum();
+            TextLine { glyphs, x: 0, width }
+        };
+
+        match options.width {
+            Some(width) => {
+                for range in wrap(&chars, &breaks, start..content_end, width, paragraph_direction, shape) {
+                    lines.push(shape_line(range));
+                }
+            }
+            None => lines.push(shape_line(start..trim_end(&chars, start..content_end))),
+        }
+        line_paragraphs.resize(lines.len(), (false, paragraph_direction));
+        if let Some(last) = line_paragraphs.last_mut() {
+            last.0 = true;
+        }
+        start = end;
+    }
+
+    align(&mut lines, &chars, &line_paragraphs, options);
+    lines
+}
+
+// The end of a range of characters without its trailing spaces, which hang past the line
+fn trim_end(chars: &[char], range: Range<usize>) -> usize {
+    let mut end = range.end;
+    while end > range.start && chars[end - 1].is_whitespace() {
+        end -= 1;
+    }
+    end
+}
+
+// Splits one paragraph into the character ranges of its lines, measured from the paragraph
+// shaped whole. Each line takes as much as fits up to a break opportunity.
+fn wrap(chars: &[char], breaks: &[BreakOpportunity], paragraph: Range<usize>, width: i32, direction: Direction, shape: &dyn Fn(&str, Option<Direction>) -> Vec<PositionedGlyph>) -> Vec<Range<usize>> {
+    let paragraph_text: String = chars[paragraph.clone()].iter().collect();
+    let mut advances = vec![0; paragraph.len()];
+    for glyph in shape(&paragraph_text, Some(direction)) {
+        if let Some(advance) = advances.get_mut(glyph.cluster) {
+            *advance += glyph.x_advance;
+        }
+    }
+    let measure = |range: Range<usize>| -> i32 {
+        let end = trim_end(chars, range.clone());
+        advances[range.start - paragraph.start..end - paragraph.start].iter().sum()
+    };
+
+    let mut lines = Vec::new();
+    let mut line_start = paragraph.start;
+    let mut last_fit = None;
+    let mut candidates = (paragraph.start + 1..paragraph.end)
+        .filter(|&index| breaks[index] == BreakOpportunity::Allowed)
+        .chain(std::iter::once(paragraph.end))
+        .peekable();
+    while let Some(&candidate) = candidates.peek() {
+        if measure(line_start..candidate) <= width {
+            last_fit = Some(candidate);
+            candidates.next();
+        } else if let Some(fit) = last_fit.take() {
+            lines.push(line_start..trim_end(chars, line_start..fit));
+            line_start = fit;
+        } else {
+            // Nothing fits, so the line overflows up to the first break
+            lines.push(line_start..trim_end(chars, line_start..candidate));
+            line_start = candidate;
+            candidates.next();
+        }
+    }
+    if line_start < paragraph.end || lines.is_empty() {
+        lines.push(line_start..trim_end(chars, line_start..paragraph.end));
+    }
+    lines
+}
+
+// Sets where each line starts within the wrap width, or the widest line when there's none, and
+// widens the spaces of justified lines
+fn align(lines: &mut [TextLine], chars: &[char], line_paragraphs: &[(bool, Direction)], options: &ParagraphOptions) {
+    let text_width = options.width.unwrap_or_else(|| lines.iter().map(|line| line.width).max().unwrap_or(0));
+    for (line, &(ends_paragraph, direction)) in lines.iter_mut().zip(line_paragraphs) {
+        // Lines justification can't stretch start on the paragraph's side
+        let alignment = match (options.alignment, direction) {
+            (Alignment::Justify, _) if !ends_paragraph && justify(line, chars, text_width - line.width) => Alignment::Left,
+            (Alignment::Justify, Direction::LeftToRight) => Alignment::Left,
+            (Alignment::Justify, Direction::RightToLeft) => Alignment::Right,
+            (alignment, _) => alignment,
+        };
+
+        let space = text_width - line.width;
+        line.x = match alignment {
+            Alignment::Right => space,
+            Alignment::Center => space / 2,
+            _ => 0,
+        };
+    }
+}
+
+// Shares out the space left on a line between its spaces, a unit more for the first ones when
+// it doesn't divide evenly. Returns whether the line had spaces to stretch.
+fn justify(line: &mut TextLine, chars: &[char], space: i32) -> bool {
+    let spaces: Vec<usize> = (0..line.glyphs.len())
+        .filter(|&index| chars.get(line.glyphs[index].cluster).is_some_and(|&ch| ch == ' ' || ch == '\u{3000}'))
+        .collect();
+    if spaces.is_empty() || space < 0 {
+        return false;
+    }
+    let count = spaces.len() as i32;
+    for (nth, &index) in spaces.iter().enumerate() {
+        line.glyphs[index].x_advance += space / count + i32::from((nth as i32) < space % count);
+    }
+    line.width += space;
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every character is one glyph 10 units wide
+    fn monospaced(text: &str, _: Option<Direction>) -> Vec<PositionedGlyph> {
+        text.chars().enumerate()
+            .map(|(cluster, ch)| PositionedGlyph { glyph_id: ch as u16, cluster, x_advance: 10, ..PositionedGlyph::default() })
+            .collect()
+    }
+
+    fn layout(text: &str, width: Option<i32>, alignment: Alignment) -> Vec<TextLine> {
+        layout_text(text, Some(Direction::LeftToRight), &ParagraphOptions { width, alignment }, &monospaced)
+    }
+
+    fn line_text(line: &TextLine, text: &str) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        line.glyphs.iter().map(|glyph| chars[glyph.cluster]).collect()
+    }
+
+    fn line_texts(text: &str, width: Option<i32>) -> Vec<String> {
+        layout(text, width, Alignment::Left).iter().map(|line| line_text(line, text)).collect()
+    }
+
+    #[test]
+    fn wraps_at_the_last_break_that_fits() {
+        // The trailing space hangs past the line, so "aa bb" fits in 50
+        assert_eq!(line_texts("aa bb cc", Some(50)), ["aa bb", "cc"]);
+    }
+
+    #[test]
+    fn first_word_wider_than_the_line_overflows_it() {
+        assert_eq!(line_texts("abcdefgh ij", Some(50)), ["abcdefgh", "ij"]);
+    }
+
+    #[test]
+    fn empty_paragraph_keeps_its_line() {
+        for width in [None, Some(50)] {
+            let lines = layout("a\n\nb", width, Alignment::Left);
+            assert_eq!(lines.len(), 3);
+            assert!(lines[1].glyphs.is_empty());
+            assert_eq!(lines[1].width, 0);
+        }
+    }
+
+    #[test]
+    fn justify_gives_the_remainder_to_the_first_spaces() {
+        let text = "a b c dddddddd";
+        let lines = layout(text, Some(61), Alignment::Justify);
+        assert_eq!(line_text(&lines[0], text), "a b c");
+        let advances: Vec<i32> = lines[0].glyphs.iter().map(|glyph| glyph.x_advance).collect();
+        assert_eq!(advances, [10, 16, 10, 15, 10]);
+        assert_eq!((lines[0].x, lines[0].width), (0, 61));
+        // The paragraph's last line isn't stretched
+        assert_eq!((lines[1].x, lines[1].width), (0, 80));
+    }
+
+    #[test]
+    fn alignment_without_a_width_uses_the_widest_line() {
+        let lines = layout("abcd\nab", None, Alignment::Center);
+        assert_eq!((lines[0].x, lines[1].x), (0, 10));
+        let lines = layout("abcd\nab", None, Alignment::Right);
+        assert_eq!((lines[0].x, lines[1].x), (0, 20));
+    }
+}
@@ -0,0 +1,198 @@
+use crate::glyph::Glyph;
+use crate::hinting::HintedGlyph;
+use crate::outline::{Outline, Segment};
+
+// Maximum distance in pixels between a flattened curve and the true quadratic
+const FLATTENING_TOLERANCE: f32 = 0.1;
+
+// TrueType outlines are meant to be filled with the non-zero rule; even-odd is for sources that
+// expect it, such as legacy fonts and SVG content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FillRule {
+    #[default]
+    NonZero,
+    EvenOdd,
+}
+
+// 8-bit coverage rows, top to bottom. `left` and `top` place the bitmap relative to the glyph
+// origin: `left` pixels to the right, with its first row `top` pixels above the baseline.
+#[derive(Debug, Clone, Default)]
+pub struct GlyphBitmap {
+    pub width: usize,
+    pub height: usize,
+    pub left: i32,
+    pub top: i32,
+    pub coverage: Vec<u8>,
+}
+
+// Scanline rasterizer accumulating exact signed area per pixel. Each row keeps one spare cell
+// on the right so edges touching the last column have somewhere to put their remainder.
+pub struct Rasterizer {
+    width: usize,
+    height: usize,
+    accumulation: Vec<f32>,
+}
+
+impl Rasterizer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Rasterizer {
+            width,
+            height,
+            accumulation: vec![0.0; (width + 2) * height],
+        }
+    }
+
+    // Points are in bitmap pixels with y pointing down
+    pub fn draw_line(&mut self, p0: (f32, f32), p1: (f32, f32)) {
+        if p0.1 == p1.1 || self.height == 0 {
+            return;
+        }
+        let (direction, top, bottom) = if p0.1 < p1.1 { (1.0, p0, p1) } else { (-1.0, p1, p0) };
+        let dxdy = (bottom.0 - top.0) / (bottom.1 - top.1);
+        let stride = self.width + 2;
+        let max_x = self.width as f32;
+
+        let first_row = top.1.max(0.0) as usize;
+        let last_row = (bottom.1.ceil().max(0.0) as usize).min(self.height);
+        // Edges starting above the bitmap enter it part of the way along
+        let mut x = top.0 + (first_row as f32 - top.1).max(0.0) * dxdy;
+
+        for row in first_row..last_row {
+            let row_top = (row as f32).max(top.1);
+            let row_bottom = ((row + 1) as f32).min(bottom.1);
+            let dy = row_bottom - row_top;
+            let next_x = x + dxdy * dy;
+            let area = dy * direction;
+
+            let (x0, x1) = if x < next_x { (x, next_x) } else { (next_x, x) };
+            let (x0, x1) = (x0.clamp(0.0, max_x), x1.clamp(0.0, max_x));
+            let line = &mut self.accumulation[row * stride..(row + 1) * stride];
+            let x0_floor = x0.floor();
+            let x0_index = x0_floor as usize;
+            let x1_ceil = x1.ceil();
+            let x1_index = x1_ceil as usize;
+
+            if x1_index <= x0_index + 1 {
+                // The edge stays within one pixel column on this row
+                let x_mid = 0.5 * (x0 + x1) - x0_floor;
+                line[x0_index] += area - area * x_mid;
+                line[x0_index + 1] += area * x_mid;
+            } else {
+                // Spread the area over the columns the edge crosses, trapezoid by trapezoid
+                let inverse_width = (x1 - x0).recip();
+                let x0_fraction = x0 - x0_floor;
+                let first_area = 0.5 * inverse_width * (1.0 - x0_fraction) * (1.0 - x0_fraction);
+                let x1_fraction = x1 - x1_ceil + 1.0;
+                let last_area = 0.5 * inverse_width * x1_fraction * x1_fraction;
+
+                line[x0_index] += area * first_area;
+                if x1_index == x0_index + 2 {
+                    line[x0_index + 1] += area * (1.0 - first_area - last_area);
+                } else {
+                    let second_area = inverse_width * (1.5 - x0_fraction);
+                    line[x0_index + 1] += area * (second_area - first_area);
+                    for cell in &mut line[x0_index + 2..x1_index - 1] {
+                        *cell += area * inverse_width;
+                    }
+                    let before_last = second_area + (x1_index - x0_index - 3) as f32 * inverse_width;
+                    line[x1_index - 1] += area * (1.0 - before_last - last_area);
+                }
+                line[x1_index] += area * last_area;
+            }
+
+            x = next_x;
+        }
+    }
+
+    pub fn draw_quad(&mut self, p0: (f32, f32), p1: (f32, f32), p2: (f32, f32)) {
+        // Subdivide based on how far the control point pulls the curve away from its chord
+        let deviation_x = p0.0 - 2.0 * p1.0 + p2.0;
+        let deviation_y = p0.1 - 2.0 * p1.1 + p2.1;
+        let deviation = (deviation_x * deviation_x + deviation_y * deviation_y).sqrt();
+        let steps = 1 + (deviation / (8.0 * FLATTENING_TOLERANCE)).sqrt().ceil() as usize;
+
+        let mut previous = p0;
+        for step in 1..=steps {
+            let t = step as f32 / steps as f32;
+            let mt = 1.0 - t;
+            let point = (
+                mt * mt * p0.0 + 2.0 * mt * t * p1.0 + t * t * p2.0,
+                mt * mt * p0.1 + 2.0 * mt * t * p1.1 + t * t * p2.1,
+            );
+            self.draw_line(previous, point);
+            previous = point;
+        }
+    }
+
+    // Draws an outline already transformed into bitmap pixels
+    pub fn draw_outline(&mut self, outline: &Outline) {
+        for segment in outline.contours.iter().flatten() {
+            match *segment {
+                Segment::Line(p0, p1) => self.draw_line(p0, p1),
+                Segment::Quad(p0, p1, p2) => self.draw_quad(p0, p1, p2),
+            }
+        }
+    }
+
+    // Sums the accumulated area along each row; the running total is the winding-weighted
+    // coverage, which the non-zero rule clamps to full coverage and the even-odd rule folds
+    // so that every second overlap is empty again
+    pub fn coverage(&self, fill_rule: FillRule) -> Vec<u8> {
+        let stride = self.width + 2;
+        let mut coverage = Vec::with_capacity(self.width * self.height);
+        for row in 0..self.height {
+            let mut total = 0.0;
+            for &cell in &self.accumulation[row * stride..row * stride + self.width] {
+                total += cell;
+                let value = match fill_rule {
+                    FillRule::NonZero => total.abs().min(1.0),
+                    FillRule::EvenOdd => {
+                        let folded = total.abs() % 2.0;
+                        if folded > 1.0 { 2.0 - folded } else { folded }
+                    }
+                };
+                coverage.push((value * 255.0 + 0.5) as u8);
+            }
+        }
+        coverage
+    }
+}
+
+// Rasterizes an outline in font units scaled by `scale`, shifted by a subpixel offset in pixels
+pub fn rasterize_outline(outline: &Outline, scale: f32, subpixel_offset: (f32, f32), fill_rule: FillRule) -> GlyphBitmap {
+    let scaled = outline.transform(scale, scale, subpixel_offset.0, subpixel_offset.1);
+    let (min_x, min_y, max_x, max_y) = match scaled.tight_bounds() {
+        Some(bounds) => bounds,
+        None => return GlyphBitmap::default(),
+    };
+
+    let left = min_x.floor() as i32;
+    let bottom = min_y.floor() as i32;
+    let right = max_x.ceil() as i32;
+    let top = max_y.ceil() as i32;
+    let width = (right - left).max(0) as usize;
+    let height = (top - bottom).max(0) as usize;
+
+    // Move into bitmap space: origin at the top-left corner, y pointing down
+    let bitmap_outline = scaled.transform(1.0, -1.0, -left as f32, top as f32);
+    let mut rasterizer = Rasterizer::new(width, height);
+    rasterizer.draw_outline(&bitmap_outline);
+
+    GlyphBitmap {
+        width,
+        height,
+        left,
+        top,
+        coverage: rasterizer.coverage(fill_rule),
+    }
+}
+
+pub fn rasterize_glyph(glyph: &Glyph, units_per_em: u16, pixel_size: f32, subpixel_offset: (f32, f32), fill_rule: FillRule) -> GlyphBitmap {
+    let scale = pixel_size / units_per_em as f32;
+    rasterize_outline(&Outline::from_glyph(glyph), scale, subpixel_offset, fill_rule)
+}
+
+// Hinted glyphs are grid-fitted at their own ppem, so they are rasterized without further scaling
+pub fn rasterize_hinted_glyph(hinted: &HintedGlyph, subpixel_offset: (f32, f32), fill_rule: FillRule) -> GlyphBitmap {
+    rasterize_outline(&Outline::from_hinted_glyph(hinted), 1.0, subpixel_offset, fill_rule)
+}
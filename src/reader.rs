@@ -1,8 +1,15 @@
 use crate::buffer::ByteBuffer;
-use crate::glyph::Glyph;
-use crate::table::{TableRecord, EncodingRecord, TableName, MaxpTable, HeadTable, HheaTable, HmtxTable, CmapTable, CmapFormat4};
+use crate::gdef::{read_gdef, GdefTable};
+use crate::gpos::{read_gpos, GposTable};
+use crate::gsub::{read_gsub, GsubTable};
+use crate::glyph::{Glyph, process_points};
+use crate::metrics::VerticalMetrics;
+use crate::table::{TableRecord, EncodingRecord, TableName, MaxpTable, HeadTable, HheaTable, HmtxTable, CmapTable, CmapFormat4, PostTable, MAC_GLYPH_NAMES, VheaTable, VmtxTable, VorgTable, Os2Table, KernTable, KernSubtable, KernFormat, KernClassTable};
 use crate::utils::get_platform_id;
 
+// Real fonts nest components a few levels deep at most, so anything deeper is a cycle or a broken font
+const MAX_COMPONENT_DEPTH: usize = 8;
+
 pub struct FontParser {
     buffer: ByteBuffer,
     table_records: Vec<TableRecord>,
@@ -14,19 +21,47 @@ impl FontParser {
     }
 
     pub fn read_table<T>(&mut self, table_name: TableName, read_fn: Box<dyn Fn(&mut ByteBuffer) -> T>) -> Option<T> {
-        if let Some(record) = self.table_records.iter().find(|&record| &record.tag == table_name.as_tag()) {
-            self.buffer.set_position(record.absolute_offset as usize);
-            Some(read_fn(&mut self.buffer))
-        } else {
+        let table = self.read_optional_table(table_name, read_fn);
+        if table.is_none() {
             println!("{:?} table not found", table_name.as_tag());
-            None
         }
+        table
+    }
+
+    // Same as read_table, but for tables that many fonts leave out, so a missing one isn't reported
+    pub fn read_optional_table<T>(&mut self, table_name: TableName, read_fn: Box<dyn Fn(&mut ByteBuffer) -> T>) -> Option<T> {
+        let record = self.table_records.iter().find(|&record| &record.tag == table_name.as_tag())?;
+        self.buffer.set_position(record.absolute_offset as usize);
+        Some(read_fn(&mut self.buffer))
     }
 
     pub fn read_maxp_table(&mut self) -> Option<MaxpTable> {
         self.read_table(TableName::Maxp, Box::new(|buffer| {
-            buffer.skip_bytes(4);
-            MaxpTable { num_glyphs: buffer.read_u16() }
+            let version = buffer.read_u32();
+            let num_glyphs = buffer.read_u16();
+            // Version 0.5 (CFF outlines) stops after numGlyphs
+            let mut fields = [0u16; 13];
+            if version == 0x00010000 {
+                for field in fields.iter_mut() {
+                    *field = buffer.read_u16();
+                }
+            }
+            MaxpTable {
+                num_glyphs,
+                max_points: fields[0],
+                max_contours: fields[1],
+                max_composite_points: fields[2],
+                max_composite_contours: fields[3],
+                max_zones: fields[4],
+                max_twilight_points: fields[5],
+                max_storage: fields[6],
+                max_function_defs: fields[7],
+                max_instruction_defs: fields[8],
+                max_stack_elements: fields[9],
+                max_size_of_instructions: fields[10],
+                max_component_elements: fields[11],
+                max_component_depth: fields[12],
+            }
         }))
     }
 
@@ -35,16 +70,28 @@ impl FontParser {
             buffer.skip_bytes(2 + 2 + 4 + 4);
             let magic_number = buffer.read_u32();
             assert_eq!(magic_number, 0x5F0F3CF5);
-            buffer.skip_bytes(2 + 2 + 8 + 8 + 2 + 2 + 2 + 2 + 2 + 2 + 2);
-            HeadTable { index_to_loc_format: buffer.read_i16() }
+            let _flags = buffer.read_u16();
+            let units_per_em = buffer.read_u16();
+            buffer.skip_bytes(8 + 8 + 2 + 2 + 2 + 2 + 2 + 2 + 2);
+            HeadTable {
+                units_per_em,
+                index_to_loc_format: buffer.read_i16(),
+            }
         }))
     }
 
     pub fn read_hhea_table(&mut self) -> Option<HheaTable> {
         self.read_table(TableName::Hhea, Box::new(|buffer| {
-            buffer.skip_bytes(34); // Skip to numOfLongHorMetrics
+            buffer.skip_bytes(4); // Skip version
+            let ascender = buffer.read_i16();
+            let descender = buffer.read_i16();
+            let line_gap = buffer.read_i16();
+            buffer.skip_bytes(24); // Skip to numOfLongHorMetrics
             let num_h_metrics = buffer.read_u16();
             HheaTable {
+                ascender,
+                descender,
+                line_gap,
                 num_h_metrics,
             }
         }))
@@ -74,6 +121,267 @@ impl FontParser {
         }))
     }
 
+    pub fn read_vhea_table(&mut self) -> Option<VheaTable> {
+        self.read_optional_table(TableName::Vhea, Box::new(|buffer| {
+            buffer.skip_bytes(4); // Skip version
+            let ascender = buffer.read_i16();
+            let descender = buffer.read_i16();
+            let line_gap = buffer.read_i16();
+            let advance_height_max = buffer.read_u16();
+            buffer.skip_bytes(22); // Skip to numOfLongVerMetrics
+            let num_v_metrics = buffer.read_u16();
+            VheaTable {
+                ascender,
+                descender,
+                line_gap,
+                advance_height_max,
+                num_v_metrics,
+            }
+        }))
+    }
+
+    pub fn read_vmtx_table(&mut self, num_glyphs: u16, num_v_metrics: u16) -> Option<VmtxTable> {
+        self.read_optional_table(TableName::Vmtx, Box::new(move |buffer| {
+            let mut advance_heights = Vec::with_capacity(num_glyphs as usize);
+            let mut top_side_bearings = Vec::with_capacity(num_glyphs as usize);
+
+            for _ in 0..num_v_metrics {
+                advance_heights.push(buffer.read_u16());
+                top_side_bearings.push(buffer.read_i16());
+            }
+
+            // As in hmtx, trailing glyphs repeat the last advance and only store a bearing
+            let last_advance_height = advance_heights.last().copied().unwrap_or(0);
+            for _ in num_v_metrics..num_glyphs {
+                advance_heights.push(last_advance_height);
+                top_side_bearings.push(buffer.read_i16());
+            }
+
+            VmtxTable {
+                advance_heights,
+                top_side_bearings,
+            }
+        }))
+    }
+
+    pub fn read_vorg_table(&mut self) -> Option<VorgTable> {
+        self.read_optional_table(TableName::Vorg, Box::new(|buffer| {
+            buffer.skip_bytes(4); // Skip major and minor version
+            let default_vert_origin_y = buffer.read_i16();
+            let num_vert_origin_y_metrics = buffer.read_u16();
+            let vert_origin_y_metrics = (0..num_vert_origin_y_metrics)
+                .map(|_| (buffer.read_u16(), buffer.read_i16()))
+                .collect();
+            VorgTable {
+                default_vert_origin_y,
+                vert_origin_y_metrics,
+            }
+        }))
+    }
+
+    // Gathers vhea, vmtx, VORG and OS/2, any of which may be missing
+    pub fn read_vertical_metrics(&mut self, num_glyphs: u16, hhea_table: &HheaTable) -> VerticalMetrics {
+        let vhea_table = self.read_vhea_table();
+        let vmtx_table = vhea_table.as_ref().and_then(|vhea| self.read_vmtx_table(num_glyphs, vhea.num_v_metrics));
+        let vorg_table = self.read_vorg_table();
+        let os2_table = self.read_os2_table();
+        VerticalMetrics::new(vhea_table, vmtx_table, vorg_table, os2_table.as_ref(), hhea_table)
+    }
+
+    pub fn read_os2_table(&mut self) -> Option<Os2Table> {
+        self.read_optional_table(TableName::Os2, Box::new(|buffer| {
+            let version = buffer.read_u16();
+            let x_avg_char_width = buffer.read_i16();
+            let weight_class = buffer.read_u16();
+            let width_class = buffer.read_u16();
+            buffer.skip_bytes(2 + 2 * 10 + 2 + 10 + 4 * 4 + 4); // Skip fsType through achVendID
+            let fs_selection = buffer.read_u16();
+            buffer.skip_bytes(4); // Skip first and last char index
+            let typo_ascender = buffer.read_i16();
+            let typo_descender = buffer.read_i16();
+            let typo_line_gap = buffer.read_i16();
+            let win_ascent = buffer.read_u16();
+            let win_descent = buffer.read_u16();
+            let (x_height, cap_height) = if version >= 2 {
+                buffer.skip_bytes(8); // Skip code page ranges
+                (buffer.read_i16(), buffer.read_i16())
+            } else {
+                (0, 0)
+            };
+            Os2Table {
+                version,
+                x_avg_char_width,
+                weight_class,
+                width_class,
+                fs_selection,
+                typo_ascender,
+                typo_descender,
+                typo_line_gap,
+                win_ascent,
+                win_descent,
+                x_height,
+                cap_height,
+            }
+        }))
+    }
+
+    fn table_length(&self, table_name: TableName) -> usize {
+        self.table_records.iter()
+            .find(|&record| &record.tag == table_name.as_tag())
+            .map_or(0, |record| record.length as usize)
+    }
+
+    pub fn read_fpgm_table(&mut self) -> Option<Vec<u8>> {
+        let length = self.table_length(TableName::Fpgm);
+        self.read_optional_table(TableName::Fpgm, Box::new(move |buffer| buffer.read_array::<u8>(length)))
+    }
+
+    pub fn read_prep_table(&mut self) -> Option<Vec<u8>> {
+        let length = self.table_length(TableName::Prep);
+        self.read_optional_table(TableName::Prep, Box::new(move |buffer| buffer.read_array::<u8>(length)))
+    }
+
+    pub fn read_cvt_table(&mut self) -> Option<Vec<i16>> {
+        let length = self.table_length(TableName::Cvt);
+        self.read_optional_table(TableName::Cvt, Box::new(move |buffer| buffer.read_array::<i16>(length / 2)))
+    }
+
+    pub fn read_post_table(&mut self) -> Option<PostTable> {
+        let length = self.table_length(TableName::Post);
+        self.read_optional_table(TableName::Post, Box::new(move |buffer| {
+            let table_end = buffer.current_position() + length;
+            let version = buffer.read_u32();
+            let italic_angle = buffer.read_i32();
+            let underline_position = buffer.read_i16();
+            let underline_thickness = buffer.read_i16();
+            let is_fixed_pitch = buffer.read_u32();
+            buffer.skip_bytes(16); // Skip memory usage hints
+
+            let glyph_names = match version {
+                0x00010000 => MAC_GLYPH_NAMES.iter().map(|name| name.to_string()).collect(),
+                0x00020000 => {
+                    let num_glyphs = buffer.read_u16();
+                    let name_indices = buffer.read_array::<u16>(num_glyphs as usize);
+                    // Indices from 258 up refer to Pascal strings following the index array
+                    let mut custom_names = Vec::new();
+                    while buffer.current_position() < table_end {
+                        let name_length = buffer.read_u8() as usize;
+                        let name_length = name_length.min(table_end - buffer.current_position());
+                        custom_names.push(String::from_utf8_lossy(buffer.read_bytes(name_length)).into_owned());
+                    }
+                    name_indices.iter()
+                        .map(|&index| match index as usize {
+                            index if index < MAC_GLYPH_NAMES.len() => MAC_GLYPH_NAMES[index].to_string(),
+                            index => custom_names.get(index - MAC_GLYPH_NAMES.len()).cloned().unwrap_or_default(),
+                        })
+                        .collect()
+                }
+                0x00025000 => {
+                    // Deprecated: each glyph's name is a standard one at an offset from its own index
+                    let num_glyphs = buffer.read_u16();
+                    (0..num_glyphs as i32)
+                        .map(|glyph_index| {
+                            let index = glyph_index + buffer.read_i8() as i32;
+                            MAC_GLYPH_NAMES.get(index as usize).map(|name| name.to_string()).unwrap_or_default()
+                        })
+                        .collect()
+                }
+                _ => Vec::new(),
+            };
+
+            PostTable {
+                version,
+                italic_angle,
+                underline_position,
+                underline_thickness,
+                is_fixed_pitch,
+                glyph_names,
+            }
+        }))
+    }
+
+    pub fn read_kern_table(&mut self) -> Option<KernTable> {
+        let length = self.table_length(TableName::Kern);
+        self.read_optional_table(TableName::Kern, Box::new(move |buffer| {
+            let table_end = buffer.current_position() + length;
+            // Apple's version 1.0 is a 32-bit fixed number whose first half reads as 1
+            let version = match buffer.read_u16() {
+                1 => {
+                    buffer.skip_bytes(2);
+                    0x00010000
+                }
+                _ => 0,
+            };
+            let num_tables = if version == 0 { buffer.read_u16() as u32 } else { buffer.read_u32() };
+
+            let mut subtables = Vec::new();
+            for _ in 0..num_tables {
+                let subtable_start = buffer.current_position();
+                if subtable_start + 8 > table_end {
+                    break;
+                }
+                let mut subtable = KernSubtable {
+                    horizontal: true,
+                    minimum: false,
+                    cross_stream: false,
+                    variation: false,
+                    is_override: false,
+                    format: KernFormat::Unsupported(0),
+                };
+                let (subtable_length, format) = if version == 0 {
+                    buffer.skip_bytes(2); // Skip subtable version
+                    let subtable_length = buffer.read_u16() as usize;
+                    let coverage = buffer.read_u16();
+                    subtable.horizontal = coverage & 0x0001 != 0;
+                    subtable.minimum = coverage & 0x0002 != 0;
+                    subtable.cross_stream = coverage & 0x0004 != 0;
+                    subtable.is_override = coverage & 0x0008 != 0;
+                    (subtable_length, (coverage >> 8) as u8)
+                } else {
+                    let subtable_length = buffer.read_u32() as usize;
+                    let coverage = buffer.read_u16();
+                    buffer.skip_bytes(2); // Skip tuple index
+                    subtable.horizontal = coverage & 0x8000 == 0;
+                    subtable.cross_stream = coverage & 0x4000 != 0;
+                    subtable.variation = coverage & 0x2000 != 0;
+                    (subtable_length, (coverage & 0x00FF) as u8)
+                };
+
+                subtable.format = match format {
+                    0 => read_kern_pairs(buffer),
+                    2 => read_kern_class_array(buffer, subtable_start, table_end),
+                    3 if version != 0 => read_kern_index_array(buffer),
+                    format => KernFormat::Unsupported(format),
+                };
+                // Version 0 lengths are 16 bits and overflow on large pair lists, so format 0
+                // subtables end where their pairs do
+                let next_subtable = match subtable.format {
+                    KernFormat::Pairs(_) => buffer.current_position(),
+                    _ => subtable_start + subtable_length,
+                };
+                subtables.push(subtable);
+                buffer.set_position(next_subtable.min(table_end));
+            }
+
+            KernTable {
+                version,
+                subtables,
+            }
+        }))
+    }
+
+    pub fn read_gdef_table(&mut self) -> Option<GdefTable> {
+        self.read_optional_table(TableName::Gdef, Box::new(read_gdef))
+    }
+
+    pub fn read_gpos_table(&mut self) -> Option<GposTable> {
+        self.read_optional_table(TableName::Gpos, Box::new(read_gpos))
+    }
+
+    pub fn read_gsub_table(&mut self) -> Option<GsubTable> {
+        self.read_optional_table(TableName::Gsub, Box::new(read_gsub))
+    }
+
     pub fn read_cmap_table(&mut self) -> Option<CmapTable> {
         self.read_table(TableName::Cmap, Box::new(|buffer| {
             let table_start = buffer.current_position();
@@ -168,12 +476,43 @@ impl FontParser {
     }
 
     pub fn read_glyph(&mut self, glyph_offsets: &Vec<u32>, glyph_index: u16, hmtx_table: &HmtxTable) -> Option<Glyph> {
+        self.read_glyph_at_depth(glyph_offsets, glyph_index, hmtx_table, 0)
+    }
+
+    // Composite glyphs read their components recursively, one level deeper each time
+    fn read_glyph_at_depth(&mut self, glyph_offsets: &Vec<u32>, glyph_index: u16, hmtx_table: &HmtxTable, depth: usize) -> Option<Glyph> {
+        if depth > MAX_COMPONENT_DEPTH {
+            return None;
+        }
         if glyph_index as usize >= glyph_offsets.len() - 1 {
             return None; // Glyph index out of bounds
         }
         if let Some(record) = self.table_records.iter().find(|&record| &record.tag == TableName::Glyf.as_tag()) {
             let start_offset = glyph_offsets[glyph_index as usize] as usize;
             let end_offset = glyph_offsets[glyph_index as usize + 1] as usize;
+            let advance_width = hmtx_table.advance_widths[glyph_index as usize] as f64;
+            let left_side_bearing = hmtx_table.left_side_bearings.get(glyph_index as usize).copied().unwrap_or(0);
+
+            // Glyphs without an outline (such as space) have no data in the glyf table
+            if start_offset == end_offset {
+                return Some(Glyph {
+                    glyph_index,
+                    num_contours: 0,
+                    xmin: 0,
+                    ymin: 0,
+                    xmax: 0,
+                    ymax: 0,
+                    end_pts_of_contours: vec![],
+                    raw_end_pts_of_contours: vec![],
+                    x_coordinates: vec![],
+                    y_coordinates: vec![],
+                    flags: vec![],
+                    processed_points: vec![],
+                    instructions: vec![],
+                    advance_width,
+                    left_side_bearing,
+                });
+            }
     
             self.buffer.set_position(record.absolute_offset as usize + start_offset);
             let num_contours = self.buffer.read_i16();
@@ -190,7 +529,7 @@ impl FontParser {
                 }
     
                 let instruction_length = self.buffer.read_u16();
-                let _instructions = self.buffer.read_array::<u8>(instruction_length as usize);
+                let instructions = self.buffer.read_array::<u8>(instruction_length as usize);
     
                 let num_points = if num_contours > 0 {
                     end_pts_of_contours[num_contours as usize - 1] + 1
@@ -258,63 +597,8 @@ impl FontParser {
                     previous_y = y;
                 }
     
-                // Process points for debugging
-                let mut processed_points = Vec::new();
-                let mut adjusted_end_pts_of_contours = end_pts_of_contours.clone();
-                let mut i = 0;
-                let mut contour_index = 0;
-    
-                while i < x_coordinates.len() {
-                    let x = x_coordinates[i];
-                    let y = y_coordinates[i];
-                    processed_points.push((x, y));
-    
-                    if (flags[i] & 1) == 0 { // If this point is off-curve
-                        let mut next_i = i + 1;
-                        if next_i >= x_coordinates.len() || next_i > end_pts_of_contours[contour_index] as usize {
-                            next_i = if contour_index > 0 { (end_pts_of_contours[contour_index - 1] + 1).into() } else { 0 };
-                        }
-    
-                        if (flags[next_i] & 1) == 0 { // Next point is also off-curve
-                            let mid_point = ((x + x_coordinates[next_i]) / 2, (y + y_coordinates[next_i]) / 2);
-                            processed_points.push(mid_point);
-                            // Increment the end point indices for the current and subsequent contours
-                            for j in contour_index..end_pts_of_contours.len() {
-                                adjusted_end_pts_of_contours[j] += 1;
-                            }
-                        }
-                    } else {
-                        // Check for consecutive on-curve points
-                        let mut next_i = i + 1;
-                        if next_i >= x_coordinates.len() || next_i > end_pts_of_contours[contour_index] as usize {
-                            // Loop back to the first point of the current contour
-                            next_i = if contour_index > 0 { (end_pts_of_contours[contour_index - 1] + 1).into() } else { 0 };
-                        }
-    
-                        if (flags[next_i] & 1) != 0 { // Next point is also on-curve
-                            let mid_point = ((x + x_coordinates[next_i]) / 2, (y + y_coordinates[next_i]) / 2);
-                            processed_points.push(mid_point);
-                            // Increment the end point indices for the current and subsequent contours
-                            for j in contour_index..end_pts_of_contours.len() {
-                                adjusted_end_pts_of_contours[j] += 1;
-                            }
-                        }
-                    }
-    
-                    i += 1;
-    
-                    if contour_index < end_pts_of_contours.len() && i > end_pts_of_contours[contour_index] as usize {
-                        contour_index += 1;
-                    }
-                }
-    
-                // Do not add the first point again to close the contour if it already exists
-                if num_contours > 0 && processed_points.last() == Some(&processed_points[0]) {
-                    processed_points.pop();
-                    adjusted_end_pts_of_contours[contour_index - 1] -= 1;
-                }
-    
-                let advance_width = hmtx_table.advance_widths[glyph_index as usize] as f64;
+                let (processed_points, adjusted_end_pts_of_contours) =
+                    process_points(&x_coordinates, &y_coordinates, &flags, &end_pts_of_contours);
     
                 Some(Glyph {
                     glyph_index,
@@ -324,71 +608,123 @@ impl FontParser {
                     xmax,
                     ymax,
                     end_pts_of_contours: adjusted_end_pts_of_contours,
+                    raw_end_pts_of_contours: end_pts_of_contours,
                     x_coordinates,
                     y_coordinates,
                     flags,
                     processed_points, // Add processed points to Glyph
+                    instructions,
                     advance_width,
+                    left_side_bearing,
                 })
             } else {
                 // Compound glyph
-                let mut components = Vec::new();
+                let mut x_coordinates = Vec::new();
+                let mut y_coordinates = Vec::new();
+                let mut flags = Vec::new();
+                let mut end_pts_of_contours = Vec::new();
+                let mut advance_width = advance_width;
+                let mut left_side_bearing = left_side_bearing;
+                let mut component_flags;
                 loop {
-                    let flags = self.buffer.read_u16();
+                    component_flags = self.buffer.read_u16();
                     let component_index = self.buffer.read_u16();
-    
-                    let arg1 = if (flags & 0x0001) != 0 {
-                        self.buffer.read_i16() as f64
-                    } else {
-                        self.buffer.read_u8() as f64
-                    };
-    
-                    let arg2 = if (flags & 0x0001) != 0 {
-                        self.buffer.read_i16() as f64
-                    } else {
-                        self.buffer.read_u8() as f64
+
+                    // Offsets are signed, point numbers (when ARGS_ARE_XY_VALUES is clear) are unsigned
+                    let (arg1, arg2) = match ((component_flags & 0x0001) != 0, (component_flags & 0x0002) != 0) {
+                        (true, true) => (self.buffer.read_i16() as f64, self.buffer.read_i16() as f64),
+                        (true, false) => (self.buffer.read_u16() as f64, self.buffer.read_u16() as f64),
+                        (false, true) => (self.buffer.read_i8() as f64, self.buffer.read_i8() as f64),
+                        (false, false) => (self.buffer.read_u8() as f64, self.buffer.read_u8() as f64),
                     };
-    
-                    let (dx, dy) = if (flags & 0x0002) != 0 {
-                        (arg1, arg2)
+
+                    // Component transform (a, b, c, d) stored as F2Dot14
+                    let mut read_f2dot14 = || self.buffer.read_i16() as f64 / 16384.0;
+                    let (a, b, c, d) = if (component_flags & 0x0008) != 0 {
+                        let scale = read_f2dot14();
+                        (scale, 0.0, 0.0, scale)
+                    } else if (component_flags & 0x0040) != 0 {
+                        let x_scale = read_f2dot14();
+                        let y_scale = read_f2dot14();
+                        (x_scale, 0.0, 0.0, y_scale)
+                    } else if (component_flags & 0x0080) != 0 {
+                        (read_f2dot14(), read_f2dot14(), read_f2dot14(), read_f2dot14())
                     } else {
-                        (0.0, 0.0)
+                        (1.0, 0.0, 0.0, 1.0)
                     };
-    
-                    if let Some(mut glyph_data) = self.read_glyph(glyph_offsets, component_index, hmtx_table) {
-                        for i in 0..glyph_data.x_coordinates.len() {
-                            glyph_data.x_coordinates[i] += dx as i16;
-                            glyph_data.y_coordinates[i] += dy as i16;
+
+                    // Reading the component moves the buffer, so come back to the next component record afterwards
+                    let next_record_position = self.buffer.current_position();
+                    let component = self.read_glyph_at_depth(glyph_offsets, component_index, hmtx_table, depth + 1);
+                    self.buffer.set_position(next_record_position);
+
+                    if let Some(component) = component {
+                        let transformed: Vec<(f64, f64)> = component.x_coordinates.iter().zip(&component.y_coordinates)
+                            .map(|(&x, &y)| (a * x as f64 + c * y as f64, b * x as f64 + d * y as f64))
+                            .collect();
+
+                        let (dx, dy) = if (component_flags & 0x0002) != 0 {
+                            if (component_flags & 0x0800) != 0 {
+                                // SCALED_COMPONENT_OFFSET: the offset is transformed along with the points
+                                (a * arg1 + c * arg2, b * arg1 + d * arg2)
+                            } else {
+                                (arg1, arg2)
+                            }
+                        } else {
+                            // Point matching: move the component so its point arg2 lands on combined point arg1
+                            match (x_coordinates.get(arg1 as usize), y_coordinates.get(arg1 as usize), transformed.get(arg2 as usize)) {
+                                (Some(&x), Some(&y), Some(&(component_x, component_y))) => (x as f64 - component_x, y as f64 - component_y),
+                                _ => (0.0, 0.0),
+                            }
+                        };
+
+                        // USE_MY_METRICS: the composite takes its metrics from this component, whose
+                        // origin moved with the component offset
+                        if (component_flags & 0x0200) != 0 {
+                            advance_width = component.advance_width;
+                            left_side_bearing = (xmin as i32 - (component.origin_x() + dx.round() as i32)) as i16;
                         }
-                        components.push(glyph_data);
+
+                        let point_offset = x_coordinates.len() as u16;
+                        for (x, y) in transformed {
+                            x_coordinates.push((x + dx).round() as i16);
+                            y_coordinates.push((y + dy).round() as i16);
+                        }
+                        flags.extend(component.flags);
+                        end_pts_of_contours.extend(component.raw_end_pts_of_contours.iter().map(|&end| end + point_offset));
                     }
-    
-                    if (flags & 0x0020) == 0 {
+
+                    if (component_flags & 0x0020) == 0 {
                         break;
                     }
                 }
-    
-                // Combine the components into one glyph
-                let combined_glyph = components.into_iter().reduce(|mut acc, mut glyph| {
-                    acc.x_coordinates.append(&mut glyph.x_coordinates);
-                    acc.y_coordinates.append(&mut glyph.y_coordinates);
-                    acc.end_pts_of_contours.append(&mut glyph.end_pts_of_contours);
-                    acc
-                });
-    
-                combined_glyph.map(|glyph| Glyph {
+
+                let instructions = if (component_flags & 0x0100) != 0 {
+                    let instruction_length = self.buffer.read_u16();
+                    self.buffer.read_array::<u8>(instruction_length as usize)
+                } else {
+                    vec![]
+                };
+
+                let (processed_points, adjusted_end_pts_of_contours) =
+                    process_points(&x_coordinates, &y_coordinates, &flags, &end_pts_of_contours);
+
+                Some(Glyph {
                     glyph_index,
-                    num_contours: glyph.end_pts_of_contours.len() as i16,
+                    num_contours: end_pts_of_contours.len() as i16,
                     xmin,
                     ymin,
                     xmax,
                     ymax,
-                    end_pts_of_contours: glyph.end_pts_of_contours,
-                    x_coordinates: glyph.x_coordinates,
-                    y_coordinates: glyph.y_coordinates,
-                    flags: vec![],              // Compound glyphs do not have flags
-                    processed_points: vec![],   // This can be populated if needed
-                    advance_width: hmtx_table.advance_widths[glyph_index as usize] as f64,
+                    end_pts_of_contours: adjusted_end_pts_of_contours,
+                    raw_end_pts_of_contours: end_pts_of_contours,
+                    x_coordinates,
+                    y_coordinates,
+                    flags,
+                    processed_points,
+                    instructions,
+                    advance_width,
+                    left_side_bearing,
                 })
             }
         } else {
@@ -406,6 +742,73 @@ fn read_loca_table_32(buffer: &mut ByteBuffer, num_glyphs: u16) -> Vec<u32> {
     buffer.read_array::<u32>((num_glyphs + 1) as usize)
 }
 
+fn read_kern_pairs(buffer: &mut ByteBuffer) -> KernFormat {
+    let num_pairs = buffer.read_u16();
+    buffer.skip_bytes(6); // Skip binary search parameters
+    let mut pairs: Vec<(u32, i16)> = (0..num_pairs)
+        .map(|_| ((buffer.read_u16() as u32) << 16 | buffer.read_u16() as u32, buffer.read_i16()))
+        .collect();
+    // Lookups binary search, so don't trust the font to have sorted them
+    pairs.sort_by_key(|&(pair, _)| pair);
+    KernFormat::Pairs(pairs)
+}
+
+// Format 2 offsets are relative to the start of the subtable, header included
+fn read_kern_class_array(buffer: &mut ByteBuffer, subtable_start: usize, table_end: usize) -> KernFormat {
+    buffer.skip_bytes(2); // Skip row width
+    let left_offset = buffer.read_u16() as usize;
+    let right_offset = buffer.read_u16() as usize;
+    let array_offset = buffer.read_u16();
+
+    let mut read_class_table = |offset: usize| {
+        buffer.set_position(subtable_start + offset);
+        let first_glyph = buffer.read_u16();
+        let num_glyphs = buffer.read_u16();
+        KernClassTable {
+            first_glyph,
+            classes: buffer.read_array::<u16>(num_glyphs as usize),
+        }
+    };
+    let left_classes = read_class_table(left_offset);
+    let right_classes = read_class_table(right_offset);
+
+    // The array is as long as the largest pair of class offsets reaches
+    let max_left = left_classes.classes.iter().copied().max().unwrap_or(0) as usize;
+    let max_right = right_classes.classes.iter().copied().max().unwrap_or(0) as usize;
+    let array_start = subtable_start + array_offset as usize;
+    let available = table_end.saturating_sub(array_start) / 2;
+    let count = match (max_left + max_right).checked_sub(array_offset as usize) {
+        Some(extent) => (extent / 2 + 1).min(available),
+        None => 0,
+    };
+    buffer.set_position(array_start);
+    KernFormat::ClassArray {
+        left_classes,
+        right_classes,
+        array_offset,
+        values: buffer.read_array::<i16>(count),
+    }
+}
+
+fn read_kern_index_array(buffer: &mut ByteBuffer) -> KernFormat {
+    let glyph_count = buffer.read_u16() as usize;
+    let kern_value_count = buffer.read_u8() as usize;
+    let left_class_count = buffer.read_u8() as usize;
+    let right_class_count = buffer.read_u8();
+    buffer.skip_bytes(1); // Skip flags
+    let kern_values = buffer.read_array::<i16>(kern_value_count);
+    let left_classes = buffer.read_array::<u8>(glyph_count);
+    let right_classes = buffer.read_array::<u8>(glyph_count);
+    let kern_index = buffer.read_array::<u8>(left_class_count * right_class_count as usize);
+    KernFormat::IndexArray {
+        left_classes,
+        right_classes,
+        right_class_count,
+        kern_index,
+        kern_values,
+    }
+}
+
 pub fn read_table_directory(buffer: &mut ByteBuffer) -> Vec<TableRecord> {
     let _sfnt_version = buffer.read_u32();
     let num_tables = buffer.read_u16();
@@ -417,8 +820,37 @@ pub fn read_table_directory(buffer: &mut ByteBuffer) -> Vec<TableRecord> {
         let tag = buffer.read_tag();
         let _checksum = buffer.read_u32();
         let absolute_offset = buffer.read_u32();
-        let _length = buffer.read_u32();
+        let length = buffer.read_u32();
 
-        TableRecord { tag, absolute_offset }
+        TableRecord { tag, absolute_offset, length }
     }).collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn self_referencing_composite_glyph_stops_at_the_depth_limit() {
+        // Glyph 0 is a composite whose only component is glyph 0 at offset (0, 0)
+        let glyf = vec![0xFF, 0xFF, 0, 0, 0, 0, 0, 0, 0, 0, 0x00, 0x02, 0x00, 0x00, 0, 0];
+        let table_records = vec![TableRecord { tag: *b"glyf", absolute_offset: 0, length: glyf.len() as u32 }];
+        let mut parser = FontParser::new(ByteBuffer::new(glyf), table_records);
+        let hmtx_table = HmtxTable { advance_widths: vec![500], left_side_bearings: vec![0] };
+
+        let glyph = parser.read_glyph(&vec![0, 16], 0, &hmtx_table).unwrap();
+        assert!(glyph.x_coordinates.is_empty());
+        assert!(glyph.raw_end_pts_of_contours.is_empty());
+    }
+
+    #[test]
+    fn missing_optional_tables_read_as_none() {
+        let table_records = vec![TableRecord { tag: *b"glyf", absolute_offset: 0, length: 0 }];
+        let mut parser = FontParser::new(ByteBuffer::new(vec![]), table_records);
+        assert!(parser.read_kern_table().is_none());
+        assert!(parser.read_fpgm_table().is_none());
+        assert!(parser.read_cvt_table().is_none());
+        assert!(parser.read_os2_table().is_none());
+        assert!(parser.read_vorg_table().is_none());
+    }
+}
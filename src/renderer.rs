@@ -3,10 +3,13 @@ use sdl2::render::Canvas;
 use sdl2::video::Window;
 use sdl2::pixels::Color;
 
-use crate::glyph::{Glyph, GlyphCache};
+use ttf_parser::glyph::{Glyph, GlyphCache};
+use ttf_parser::gpos::GlyphPosition;
 
 pub struct AppState {
     glyphs: Vec<Vec<Glyph>>,
+    positions: Vec<Vec<GlyphPosition>>, // Advance and offset of each glyph, in font units
+    line_offsets: Vec<i32>, // Where each line starts from the left, in font units
     canvas_dimensions: Dimensions,
     zoom_level: f64,
     debug: bool, // Enables debug visuals
@@ -14,7 +17,8 @@ pub struct AppState {
     offset: (f64, f64),
     line_height: f64,
     outline_thickness: i32, // Outline thickness parameter
-    glyph_cache: GlyphCache, // Glyph cache
+    glyph_fonts: Vec<Vec<usize>>, // Font index of each glyph, for glyphs from fallback fonts
+    glyph_caches: Vec<GlyphCache>, // Glyph cache per font, since glyph indices only mean something within one
 }
 
 struct Dimensions {
@@ -23,9 +27,11 @@ struct Dimensions {
 }
 
 impl AppState {
-    pub fn new(glyphs: Vec<Vec<Glyph>>, width: i16, height: i16, debug: bool, outline_thickness: i32) -> Result<Self, String> {
+    pub fn new(glyphs: Vec<Vec<Glyph>>, positions: Vec<Vec<GlyphPosition>>, width: i16, height: i16, debug: bool, outline_thickness: i32) -> Result<Self, String> {
         Ok(AppState {
             glyphs,
+            positions,
+            line_offsets: Vec::new(),
             canvas_dimensions: Dimensions { width, height },
             debug,
             zoom_level: 1.0,
@@ -33,10 +39,28 @@ impl AppState {
             offset: (0.0, 0.0),
             line_height: 1500.0, // Default line height
             outline_thickness, // Outline thickness parameter
-            glyph_cache: GlyphCache::new(),
+            glyph_fonts: Vec::new(),
+            glyph_caches: vec![GlyphCache::new()],
         })
     }
 
+    // Aligns lines by their offsets and spaces baselines by the font's line height
+    pub fn set_line_layout(&mut self, line_offsets: Vec<i32>, line_height: f64) {
+        self.line_offsets = line_offsets;
+        self.line_height = line_height;
+    }
+
+    // Tells glyphs from different fonts apart so they don't share cached outlines
+    pub fn set_glyph_fonts(&mut self, glyph_fonts: Vec<Vec<usize>>) {
+        let font_count = glyph_fonts.iter().flatten().max().map_or(1, |&font_index| font_index + 1);
+        self.glyph_caches.resize_with(font_count.max(self.glyph_caches.len()), GlyphCache::new);
+        self.glyph_fonts = glyph_fonts;
+    }
+
+    fn font_index(&self, line_index: usize, glyph_index: usize) -> usize {
+        self.glyph_fonts.get(line_index).and_then(|fonts| fonts.get(glyph_index)).copied().unwrap_or(0)
+    }
+
     pub fn update_canvas_dimensions(&mut self, width: i16, height: i16) {
         self.canvas_dimensions.width = width;
         self.canvas_dimensions.height = height;
@@ -54,9 +78,10 @@ impl AppState {
         self.offset.1 += dy;
 
         // Update the cache with the new zoom level
-        for line in &self.glyphs {
-            for glyph in line {
-                self.glyph_cache.update_cache(glyph, self.zoom_level);
+        for (line_index, line) in self.glyphs.iter().enumerate() {
+            for (glyph_index, glyph) in line.iter().enumerate() {
+                let font_index = self.font_index(line_index, glyph_index);
+                self.glyph_caches[font_index].update_cache(glyph, self.zoom_level);
             }
         }
     }
@@ -79,14 +104,6 @@ impl AppState {
         self.drag_start = None;
     }
 
-    fn get_glyph_bounding_box(&self, glyph: &Glyph) -> (i16, i16, i16, i16) {
-        let min_x = *glyph.x_coordinates.iter().min().unwrap_or(&0);
-        let max_x = *glyph.x_coordinates.iter().max().unwrap_or(&0);
-        let min_y = *glyph.y_coordinates.iter().min().unwrap_or(&0);
-        let max_y = *glyph.y_coordinates.iter().max().unwrap_or(&0);
-        (min_x, max_x, min_y, max_y)
-    }
-
     fn draw_bezier<T: sdl2::render::RenderTarget>(&self, canvas: &mut Canvas<T>, points: &[(i16, i16)], color: Color) -> Result<(), String> {
         if points.len() < 3 {
             return Err("Need at least 3 points to draw a quadratic Bézier curve".into());
@@ -105,9 +122,9 @@ impl AppState {
             // Draw circles at each control point for debugging
             if self.debug {
                 // println!("Points {:?}", points);
-                canvas.filled_circle(vx[0] as i16, vy[0] as i16, (10.0 * self.zoom_level) as i16, Color::RGB(255, 0, 0))?;
-                canvas.filled_circle(vx[1] as i16, vy[1] as i16, (5.0 * self.zoom_level) as i16, Color::RGB(0, 255, 0))?;
-                canvas.filled_circle(vx[2] as i16, vy[2] as i16, (2.0 * self.zoom_level) as i16, Color::RGB(0, 0, 255))?;
+                canvas.filled_circle(vx[0], vy[0], (10.0 * self.zoom_level) as i16, Color::RGB(255, 0, 0))?;
+                canvas.filled_circle(vx[1], vy[1], (5.0 * self.zoom_level) as i16, Color::RGB(0, 255, 0))?;
+                canvas.filled_circle(vx[2], vy[2], (2.0 * self.zoom_level) as i16, Color::RGB(0, 0, 255))?;
             }
         }
 
@@ -130,18 +147,20 @@ impl AppState {
         ];
 
         let max_y_coord = self.glyphs.iter().flatten().map(|glyph| {
-            let (_, _, _, max_y) = self.get_glyph_bounding_box(glyph);
-            max_y as f64
+            glyph.tight_bounding_box().y_max.ceil() as f64
         }).max_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal)).unwrap_or(0.0);
 
         let mut pen_y = self.offset.1;
-        for line in &self.glyphs {
-            let mut pen_x = self.offset.0;
+        for (line_index, (line, positions)) in self.glyphs.iter().zip(&self.positions).enumerate() {
+            let line_offset = self.line_offsets.get(line_index).copied().unwrap_or(0) as f64;
+            let mut pen_x = self.offset.0 + line_offset * self.zoom_level;
 
-            for glyph in line {
-                let (min_x, max_x, min_y, max_y) = self.get_glyph_bounding_box(glyph);
+            for (glyph_index, (glyph, position)) in line.iter().zip(positions).enumerate() {
+                let font_index = self.font_index(line_index, glyph_index);
+                let max_y = glyph.tight_bounding_box().y_max.ceil() as f64;
 
-                let baseline = pen_y + (max_y_coord - max_y as f64) * self.zoom_level;
+                let baseline = pen_y + (max_y_coord - max_y - position.y_offset as f64) * self.zoom_level;
+                let glyph_x = pen_x + position.x_offset as f64 * self.zoom_level;
 
                 if self.debug {
                     // println!("Glyph dimensions: width = {}, height = {}", max_x - min_x, max_y - min_y);
@@ -149,16 +168,17 @@ impl AppState {
                     // println!("{:?}", glyph);
                 }
 
-                if self.glyph_cache.get_cached_data(glyph.glyph_index).is_none() {
-                    self.glyph_cache.update_cache(glyph, self.zoom_level);
+                let glyph_cache = &mut self.glyph_caches[font_index];
+                if glyph_cache.get_cached_data(glyph.glyph_index).is_none() {
+                    glyph_cache.update_cache(glyph, self.zoom_level);
                 }
 
-                let cached_data = self.glyph_cache.get_cached_data(glyph.glyph_index).unwrap();
+                let cached_data = glyph_cache.get_cached_data(glyph.glyph_index).unwrap();
                 let scaled_points = &cached_data.scaled_points;
                 let bounding_box = cached_data.bounding_box;
 
                 let transformed_points: Vec<(i16, i16)> = scaled_points.iter()
-                    .map(|&(x, y)| ((x as f64 + pen_x) as i16, (baseline - y as f64) as i16))
+                    .map(|&(x, y)| ((x as f64 + glyph_x) as i16, (baseline - y as f64) as i16))
                     .collect();
 
                 let mut start = 0;
@@ -173,7 +193,7 @@ impl AppState {
                 }
 
                 let dst_rect = sdl2::rect::Rect::new(
-                    pen_x as i32,
+                    glyph_x as i32,
                     baseline as i32,
                     (bounding_box.1 - bounding_box.0) as u32,
                     (bounding_box.3 - bounding_box.2) as u32
@@ -184,7 +204,7 @@ impl AppState {
                     canvas.draw_rect(dst_rect).expect("Failed to draw rect outline");
                 }
 
-                pen_x += glyph.advance_width * self.zoom_level;
+                pen_x += position.x_advance as f64 * self.zoom_level;
             }
 
             pen_y += self.line_height * self.zoom_level;
@@ -0,0 +1,499 @@
+use crate::glyph::Glyph;
+use crate::outline::{Outline, Segment};
+
+const RED: u8 = 1;
+const GREEN: u8 = 2;
+const BLUE: u8 = 4;
+const WHITE: u8 = RED | GREEN | BLUE;
+const CYAN: u8 = GREEN | BLUE;
+const MAGENTA: u8 = RED | BLUE;
+const YELLOW: u8 = RED | GREEN;
+
+// Directions meeting at less than this angle (in radians, from straight) count as smooth
+const CORNER_ANGLE_THRESHOLD: f64 = 3.0;
+
+// Distance field bitmap, one or three bytes per pixel. 128 sits on the outline, larger values
+// are inside, and `range` pixels of distance span the whole 0-255 scale. Placement follows
+// GlyphBitmap.
+#[derive(Debug, Clone, Default)]
+pub struct DistanceField {
+    pub width: usize,
+    pub height: usize,
+    pub left: i32,
+    pub top: i32,
+    pub channels: usize,
+    pub range: f32,
+    pub data: Vec<u8>,
+}
+
+type Vector = (f64, f64);
+
+fn sub(a: Vector, b: Vector) -> Vector {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+fn dot(a: Vector, b: Vector) -> f64 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+fn cross(a: Vector, b: Vector) -> f64 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+fn length(a: Vector) -> f64 {
+    dot(a, a).sqrt()
+}
+
+fn normalize(a: Vector) -> Vector {
+    let len = length(a);
+    if len == 0.0 { (0.0, 0.0) } else { (a.0 / len, a.1 / len) }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Edge {
+    segment: Segment,
+    color: u8,
+}
+
+// Closest approach of a point to an edge: signed distance (positive inside), how orthogonal the
+// approach is (to break ties at shared corners) and the curve parameter it occurs at
+#[derive(Debug, Clone, Copy)]
+struct SignedDistance {
+    distance: f64,
+    orthogonality: f64,
+    t: f64,
+}
+
+impl SignedDistance {
+    const FAR: SignedDistance = SignedDistance { distance: f64::INFINITY, orthogonality: 0.0, t: 0.0 };
+
+    fn is_closer_than(&self, other: &SignedDistance) -> bool {
+        let (a, b) = (self.distance.abs(), other.distance.abs());
+        a < b - 1e-9 || ((a - b).abs() <= 1e-9 && self.orthogonality > other.orthogonality)
+    }
+}
+
+impl Edge {
+    fn point(&self, t: f64) -> Vector {
+        match self.segment {
+            Segment::Line(p0, p1) => {
+                let (p0, p1) = (to_vector(p0), to_vector(p1));
+                (p0.0 + (p1.0 - p0.0) * t, p0.1 + (p1.1 - p0.1) * t)
+            }
+            Segment::Quad(p0, p1, p2) => {
+                let (p0, p1, p2) = (to_vector(p0), to_vector(p1), to_vector(p2));
+                let mt = 1.0 - t;
+                (
+                    mt * mt * p0.0 + 2.0 * mt * t * p1.0 + t * t * p2.0,
+                    mt * mt * p0.1 + 2.0 * mt * t * p1.1 + t * t * p2.1,
+                )
+            }
+        }
+    }
+
+    fn direction(&self, t: f64) -> Vector {
+        match self.segment {
+            Segment::Line(p0, p1) => sub(to_vector(p1), to_vector(p0)),
+            Segment::Quad(p0, p1, p2) => {
+                let (p0, p1, p2) = (to_vector(p0), to_vector(p1), to_vector(p2));
+                let direction = (
+                    2.0 * ((1.0 - t) * (p1.0 - p0.0) + t * (p2.0 - p1.0)),
+                    2.0 * ((1.0 - t) * (p1.1 - p0.1) + t * (p2.1 - p1.1)),
+                );
+                // A control point on an endpoint leaves no tangent there, so use the chord
+                if direction == (0.0, 0.0) { sub(p2, p0) } else { direction }
+            }
+        }
+    }
+
+    // Outer contours run clockwise in TrueType, so the inside is to the right of the direction
+    fn signed_distance(&self, point: Vector) -> SignedDistance {
+        let candidates: Vec<f64> = match self.segment {
+            Segment::Line(p0, p1) => {
+                let (p0, p1) = (to_vector(p0), to_vector(p1));
+                let direction = sub(p1, p0);
+                let squared_length = dot(direction, direction);
+                let t = if squared_length == 0.0 { 0.0 } else { dot(sub(point, p0), direction) / squared_length };
+                vec![t.clamp(0.0, 1.0)]
+            }
+            Segment::Quad(p0, p1, p2) => {
+                // Roots of (B(t) - point) . B'(t) = 0, plus both endpoints
+                let (p0, p1, p2) = (to_vector(p0), to_vector(p1), to_vector(p2));
+                let a = sub(p1, p0);
+                let b = (p2.0 - 2.0 * p1.0 + p0.0, p2.1 - 2.0 * p1.1 + p0.1);
+                let offset = sub(p0, point);
+                let mut candidates = solve_cubic(dot(b, b), 3.0 * dot(a, b), 2.0 * dot(a, a) + dot(offset, b), dot(offset, a));
+                candidates.retain(|t| (0.0..=1.0).contains(t));
+                candidates.push(0.0);
+                candidates.push(1.0);
+                candidates
+            }
+        };
+
+        let mut best = SignedDistance::FAR;
+        for t in candidates {
+            let to_point = sub(point, self.point(t));
+            let distance = length(to_point);
+            let direction = normalize(self.direction(t));
+            let side = cross(direction, to_point);
+            let signed = if side > 0.0 { -distance } else { distance };
+            let orthogonality = if distance == 0.0 { 1.0 } else { (side / distance).abs() };
+            let candidate = SignedDistance { distance: signed, orthogonality, t };
+            if candidate.is_closer_than(&best) {
+                best = candidate;
+            }
+        }
+        best
+    }
+
+    // Past an endpoint, measure against the tangent line there instead so corners stay sharp
+    fn pseudo_distance(&self, point: Vector, closest: &SignedDistance) -> f64 {
+        let t = closest.t;
+        let (at, outward) = if t <= 0.0 {
+            (0.0, -1.0)
+        } else if t >= 1.0 {
+            (1.0, 1.0)
+        } else {
+            return closest.distance;
+        };
+        let direction = normalize(self.direction(at));
+        let to_point = sub(point, self.point(at));
+        if dot(to_point, direction) * outward > 0.0 {
+            let perpendicular = -cross(direction, to_point);
+            if perpendicular.abs() <= closest.distance.abs() {
+                return perpendicular;
+            }
+        }
+        closest.distance
+    }
+}
+
+fn to_vector(point: (f32, f32)) -> Vector {
+    (point.0 as f64, point.1 as f64)
+}
+
+// Real roots of a*t^3 + b*t^2 + c*t + d, falling back to lower degrees when leading terms vanish
+fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    if a.abs() < 1e-12 {
+        if b.abs() < 1e-12 {
+            return if c.abs() < 1e-12 { vec![] } else { vec![-d / c] };
+        }
+        let discriminant = c * c - 4.0 * b * d;
+        if discriminant < 0.0 {
+            return vec![];
+        }
+        let root = discriminant.sqrt();
+        return vec![(-c + root) / (2.0 * b), (-c - root) / (2.0 * b)];
+    }
+
+    // Depressed cubic t = u - b/3a, u^3 + p*u + q = 0
+    let (b, c, d) = (b / a, c / a, d / a);
+    let p = c - b * b / 3.0;
+    let q = 2.0 * b * b * b / 27.0 - b * c / 3.0 + d;
+    let shift = -b / 3.0;
+    let discriminant = q * q / 4.0 + p * p * p / 27.0;
+
+    if discriminant > 0.0 {
+        let root = discriminant.sqrt();
+        vec![(-q / 2.0 + root).cbrt() + (-q / 2.0 - root).cbrt() + shift]
+    } else if p.abs() < 1e-12 {
+        vec![(-q).cbrt() + shift]
+    } else {
+        let radius = 2.0 * (-p / 3.0).sqrt();
+        let angle = ((3.0 * q / (2.0 * p)) * (-3.0 / p).sqrt()).clamp(-1.0, 1.0).acos() / 3.0;
+        (0..3).map(|k| radius * (angle - 2.0 * std::f64::consts::PI * k as f64 / 3.0).cos() + shift).collect()
+    }
+}
+
+// Non-zero winding number of the outline around a point, using a ray towards +x
+fn winding_number(edges: &[Edge], point: Vector) -> i32 {
+    let mut winding = 0;
+    for edge in edges {
+        let roots = match edge.segment {
+            Segment::Line(p0, p1) => {
+                let (p0, p1) = (to_vector(p0), to_vector(p1));
+                if p0.1 == p1.1 {
+                    continue;
+                }
+                vec![(point.1 - p0.1) / (p1.1 - p0.1)]
+            }
+            Segment::Quad(p0, p1, p2) => {
+                let (p0, p1, p2) = (to_vector(p0), to_vector(p1), to_vector(p2));
+                solve_cubic(0.0, p0.1 - 2.0 * p1.1 + p2.1, 2.0 * (p1.1 - p0.1), p0.1 - point.1)
+            }
+        };
+        for t in roots {
+            // Half-open parameter range so a crossing exactly at a shared endpoint counts once
+            if !(0.0..1.0).contains(&t) {
+                continue;
+            }
+            let crossing = edge.point(t);
+            if crossing.0 > point.0 {
+                let dy = edge.direction(t).1;
+                if dy > 0.0 {
+                    winding += 1;
+                } else if dy < 0.0 {
+                    winding -= 1;
+                }
+            }
+        }
+    }
+    winding
+}
+
+// Which of three runs, 0 to 2, a position from 0 to 1 along a teardrop contour falls in. The
+// split points sit near a third and two thirds, mirrored about the middle like msdfgen's, so the
+// runs at either end of the corner come out the same length.
+fn third_of(position: f64) -> usize {
+    ((0.0625 + 2.875 * position) as usize).min(2)
+}
+
+// Assigns channel colours so that the edges on either side of every sharp corner share at most
+// one channel, which is what lets the median of three channels reproduce the corner
+fn color_edges(outline: &Outline) -> Vec<Edge> {
+    let mut edges = Vec::new();
+    let corner_cross = CORNER_ANGLE_THRESHOLD.sin();
+
+    for contour in &outline.contours {
+        let count = contour.len();
+        let colored = |segment: &Segment| Edge { segment: *segment, color: WHITE };
+        let contour_edges: Vec<Edge> = contour.iter().map(colored).collect();
+
+        // A corner sits at the start of edge i when the incoming and outgoing directions disagree
+        let corners: Vec<usize> = (0..count)
+            .filter(|&i| {
+                let incoming = normalize(contour_edges[(i + count - 1) % count].direction(1.0));
+                let outgoing = normalize(contour_edges[i].direction(0.0));
+                dot(incoming, outgoing) <= 0.0 || cross(incoming, outgoing).abs() > corner_cross
+            })
+            .collect();
+
+        let mut contour_edges = contour_edges;
+        match corners.len() {
+            0 => {}
+            1 if count >= 3 => {
+                // Teardrop: split the contour into three runs around its single corner
+                let colors = [MAGENTA, WHITE, YELLOW];
+                let corner = corners[0];
+                for i in 0..count {
+                    let position = i as f64 / (count - 1) as f64;
+                    contour_edges[(corner + i) % count].color = colors[third_of(position)];
+                }
+            }
+            1 => {}
+            corner_count => {
+                let cycle = [CYAN, MAGENTA, YELLOW];
+                for (k, &corner) in corners.iter().enumerate() {
+                    let mut color = cycle[k % 3];
+                    // The last run wraps around to the first one, so it must not repeat its colour
+                    if k == corner_count - 1 && color == cycle[0] {
+                        color = cycle[1];
+                    }
+                    let next_corner = corners[(k + 1) % corner_count];
+                    let mut i = corner;
+                    loop {
+                        contour_edges[i].color = color;
+                        i = (i + 1) % count;
+                        if i == next_corner {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        edges.extend(contour_edges);
+    }
+
+    edges
+}
+
+struct FieldLayout {
+    width: usize,
+    height: usize,
+    left: i32,
+    top: i32,
+}
+
+fn field_layout(outline: &Outline, padding: u32) -> Option<FieldLayout> {
+    let (min_x, min_y, max_x, max_y) = outline.tight_bounds()?;
+    let left = min_x.floor() as i32 - padding as i32;
+    let bottom = min_y.floor() as i32 - padding as i32;
+    let right = max_x.ceil() as i32 + padding as i32;
+    let top = max_y.ceil() as i32 + padding as i32;
+    Some(FieldLayout {
+        width: (right - left).max(0) as usize,
+        height: (top - bottom).max(0) as usize,
+        left,
+        top,
+    })
+}
+
+fn encode(distance: f64, range: f32) -> u8 {
+    ((distance / range as f64 + 0.5).clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+// True signed distance to the closest edge, positive inside by the non-zero rule
+fn true_distance(edges: &[Edge], point: Vector) -> f64 {
+    let closest = edges.iter()
+        .map(|edge| edge.signed_distance(point).distance.abs())
+        .fold(f64::INFINITY, f64::min);
+    if winding_number(edges, point) != 0 { closest } else { -closest }
+}
+
+// Single-channel signed distance field of an outline in font units scaled by `scale`
+pub fn generate_sdf(outline: &Outline, scale: f32, range: f32, padding: u32) -> DistanceField {
+    let scaled = outline.transform(scale, scale, 0.0, 0.0);
+    let layout = match field_layout(&scaled, padding) {
+        Some(layout) => layout,
+        None => return DistanceField { channels: 1, range, ..DistanceField::default() },
+    };
+    // Only the multi-channel field reads edge colours
+    let edges: Vec<Edge> = scaled.contours.iter().flatten().map(|&segment| Edge { segment, color: WHITE }).collect();
+
+    let mut data = Vec::with_capacity(layout.width * layout.height);
+    for row in 0..layout.height {
+        for column in 0..layout.width {
+            let point = pixel_center(&layout, row, column);
+            data.push(encode(true_distance(&edges, point), range));
+        }
+    }
+
+    DistanceField {
+        width: layout.width,
+        height: layout.height,
+        left: layout.left,
+        top: layout.top,
+        channels: 1,
+        range,
+        data,
+    }
+}
+
+// Multi-channel distance field: each channel holds the pseudo-distance to the closest edge of
+// its colour, and shaders take the median of the three to recover sharp corners
+pub fn generate_msdf(outline: &Outline, scale: f32, range: f32, padding: u32) -> DistanceField {
+    let scaled = outline.transform(scale, scale, 0.0, 0.0);
+    let layout = match field_layout(&scaled, padding) {
+        Some(layout) => layout,
+        None => return DistanceField { channels: 3, range, ..DistanceField::default() },
+    };
+    let edges = color_edges(&scaled);
+
+    let mut data = Vec::with_capacity(layout.width * layout.height * 3);
+    for row in 0..layout.height {
+        for column in 0..layout.width {
+            let point = pixel_center(&layout, row, column);
+            let mut channels = [0.0; 3];
+            for (channel, value) in [RED, GREEN, BLUE].iter().zip(channels.iter_mut()) {
+                let mut closest: Option<(&Edge, SignedDistance)> = None;
+                for edge in edges.iter().filter(|edge| (edge.color & channel) != 0) {
+                    let distance = edge.signed_distance(point);
+                    if closest.is_none_or(|(_, best)| distance.is_closer_than(&best)) {
+                        closest = Some((edge, distance));
+                    }
+                }
+                *value = closest.map_or(-f64::INFINITY, |(edge, distance)| edge.pseudo_distance(point, &distance));
+            }
+
+            // Where the channels' median lands on the wrong side (overlaps, contours too small to
+            // colour), fall back to the true distance in every channel
+            let truth = true_distance(&edges, point);
+            let median = channels[0].max(channels[1]).min(channels[0].min(channels[1]).max(channels[2]));
+            if (median > 0.0) != (truth > 0.0) {
+                channels = [truth; 3];
+            }
+
+            data.extend(channels.iter().map(|&distance| encode(distance, range)));
+        }
+    }
+
+    DistanceField {
+        width: layout.width,
+        height: layout.height,
+        left: layout.left,
+        top: layout.top,
+        channels: 3,
+        range,
+        data,
+    }
+}
+
+fn pixel_center(layout: &FieldLayout, row: usize, column: usize) -> Vector {
+    (layout.left as f64 + column as f64 + 0.5, layout.top as f64 - row as f64 - 0.5)
+}
+
+pub fn generate_glyph_sdf(glyph: &Glyph, units_per_em: u16, pixel_size: f32, range: f32, padding: u32) -> DistanceField {
+    generate_sdf(&Outline::from_glyph(glyph), pixel_size / units_per_em as f32, range, padding)
+}
+
+pub fn generate_glyph_msdf(glyph: &Glyph, units_per_em: u16, pixel_size: f32, range: f32, padding: u32) -> DistanceField {
+    generate_msdf(&Outline::from_glyph(glyph), pixel_size / units_per_em as f32, range, padding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 10x10 pixel square, clockwise like TrueType outer contours. With 2 pixels of padding the
+    // field spans -2..12 on both axes and pixel (column, row) is centred on (column - 1.5, 11.5 - row).
+    fn square() -> Outline {
+        Outline::from_points(&[(0.0, 0.0), (0.0, 10.0), (10.0, 10.0), (10.0, 0.0)], &[true; 4], &[3])
+    }
+
+    const RANGE: f32 = 8.0;
+
+    fn sample(field: &DistanceField, column: usize, row: usize) -> &[u8] {
+        let start = (row * field.width + column) * field.channels;
+        &field.data[start..start + field.channels]
+    }
+
+    fn median(channels: &[u8]) -> u8 {
+        channels[0].max(channels[1]).min(channels[0].min(channels[1]).max(channels[2]))
+    }
+
+    #[test]
+    fn field_covers_the_outline_and_padding() {
+        let field = generate_sdf(&square(), 1.0, RANGE, 2);
+        assert_eq!((field.width, field.height, field.left, field.top), (14, 14, -2, 12));
+        assert_eq!(field.data.len(), 14 * 14);
+    }
+
+    #[test]
+    fn inside_is_above_the_midpoint_and_outside_below() {
+        let field = generate_sdf(&square(), 1.0, RANGE, 2);
+        assert!(sample(&field, 7, 7)[0] > 128);
+        assert!(sample(&field, 0, 7)[0] < 128);
+        assert!(sample(&field, 0, 0)[0] < 128);
+    }
+
+    #[test]
+    fn distances_are_encoded_against_the_range() {
+        let field = generate_sdf(&square(), 1.0, RANGE, 2);
+        // Half a pixel inside and outside the left edge
+        assert_eq!(sample(&field, 2, 7)[0], encode(0.5, RANGE));
+        assert_eq!(sample(&field, 1, 7)[0], encode(-0.5, RANGE));
+        assert_eq!(encode(0.5, RANGE), 143);
+        assert_eq!(encode(-0.5, RANGE), 112);
+        // Diagonally outside the top left corner the true distance is to the corner point
+        assert_eq!(sample(&field, 0, 0)[0], encode(-(1.5f64 * 1.5 * 2.0).sqrt(), RANGE));
+        // Deep inside the distance saturates
+        assert_eq!(sample(&field, 7, 7)[0], 255);
+    }
+
+    #[test]
+    fn msdf_median_keeps_the_corner_sharp() {
+        let sdf = generate_sdf(&square(), 1.0, RANGE, 2);
+        let msdf = generate_msdf(&square(), 1.0, RANGE, 2);
+        assert_eq!(msdf.channels, 3);
+
+        // Past the corner the median is the distance to the nearer edge's line, so the zero
+        // contour keeps a right angle where the single channel field rounds it off
+        let corner = median(sample(&msdf, 0, 0));
+        assert_eq!(corner, encode(-1.5, RANGE));
+        assert!(corner > sample(&sdf, 0, 0)[0]);
+
+        // Along the edges the median agrees with the true distance
+        assert_eq!(median(sample(&msdf, 2, 7)), sample(&sdf, 2, 7)[0]);
+        assert_eq!(median(sample(&msdf, 1, 7)), sample(&sdf, 1, 7)[0]);
+    }
+}
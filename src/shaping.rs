@@ -0,0 +1,257 @@
+use crate::arabic;
+use crate::indic;
+use crate::bidi::{bidi_class, mirrored, BidiClass, BidiParagraph};
+use crate::gdef::{GdefTable, GlyphClass};
+use crate::gpos::{GlyphPosition, GposTable, DEFAULT_FEATURES as DEFAULT_POSITIONING_FEATURES};
+use crate::gsub::{GlyphInfo, GsubTable, SubstitutionPlan, DEFAULT_FEATURES as DEFAULT_SUBSTITUTION_FEATURES};
+use crate::opentype::{merge_feature_settings, FeatureSetting};
+use crate::table::{CmapFormat4, HmtxTable, KernTable};
+use crate::thai;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Direction {
+    #[default]
+    LeftToRight,
+    RightToLeft,
+}
+
+// OpenType script tags of the scripts shaping can tell apart, by code point. Characters outside
+// these, like digits and punctuation, belong to whatever script is around them.
+const SCRIPT_RANGES: &[(u32, u32, [u8; 4])] = &[
+    (0x0041, 0x005A, *b"latn"), (0x0061, 0x007A, *b"latn"), (0x00C0, 0x024F, *b"latn"), (0x0370, 0x03FF, *b"grek"),
+    (0x0400, 0x052F, *b"cyrl"), (0x0590, 0x05FF, *b"hebr"), (0x0600, 0x06FF, *b"arab"), (0x0700, 0x074F, *b"syrc"),
+    (0x0750, 0x077F, *b"arab"), (0x07C0, 0x07FF, *b"nko "), (0x08A0, 0x08FF, *b"arab"), (0x0900, 0x097F, *b"dev2"),
+    (0x0980, 0x09FF, *b"bng2"), (0x0E00, 0x0E7F, *b"thai"), (0x0E80, 0x0EFF, *b"lao "), (0x1E00, 0x1EFF, *b"latn"),
+    (0x1F00, 0x1FFF, *b"grek"), (0xFB1D, 0xFB4F, *b"hebr"), (0xFB50, 0xFDFF, *b"arab"), (0xFE70, 0xFEFF, *b"arab"),
+];
+
+// The script of the first character in the text that has one
+pub fn detect_script(text: &str) -> Option<[u8; 4]> {
+    text.chars().find_map(|ch| {
+        SCRIPT_RANGES.iter()
+            .find(|&&(start, end, _)| (start..=end).contains(&(ch as u32)))
+            .map(|&(_, _, tag)| tag)
+    })
+}
+
+// Invisible characters fonts often leave out, like joiners and variation selectors, which are
+// dropped rather than shown as .notdef when a font lacks them
+pub fn is_default_ignorable(ch: char) -> bool {
+    matches!(ch as u32, 0xFE00..=0xFE0F | 0xE0100..=0xE01EF) || bidi_class(ch) == BidiClass::BN
+}
+
+// Script-specific substitution stages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shaper {
+    Default,
+    Arabic,
+    Indic,
+    Thai,
+}
+
+impl Shaper {
+    fn for_script(script_tag: &[u8; 4]) -> Self {
+        match script_tag {
+            b"arab" | b"syrc" | b"nko " => Shaper::Arabic,
+            b"dev2" | b"deva" | b"bng2" | b"beng" => Shaper::Indic,
+            b"thai" | b"lao " => Shaper::Thai,
+            _ => Shaper::Default,
+        }
+    }
+}
+
+// One glyph of shaped text, in visual order and font units. The cluster is the index of the first
+// character the glyph came from, shared by every glyph made from the same characters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PositionedGlyph {
+    pub glyph_id: u16,
+    pub cluster: usize,
+    pub font_index: usize, // Which font of a fallback chain the glyph and its units are from
+    pub x_advance: i32,
+    pub y_advance: i32,
+    pub x_offset: i32,
+    pub y_offset: i32,
+}
+
+impl PositionedGlyph {
+    pub fn position(&self) -> GlyphPosition {
+        GlyphPosition {
+            x_advance: self.x_advance,
+            y_advance: self.y_advance,
+            x_offset: self.x_offset,
+            y_offset: self.y_offset,
+        }
+    }
+}
+
+// The tables of one font that shaping reads. Only cmap and hmtx are required; the layout tables
+// are used when the font has them.
+pub struct ShapingFont<'a> {
+    pub cmap: &'a CmapFormat4,
+    pub hmtx: &'a HmtxTable,
+    pub gdef: Option<&'a GdefTable>,
+    pub gsub: Option<&'a GsubTable>,
+    pub gpos: Option<&'a GposTable>,
+    pub kern: Option<&'a KernTable>,
+}
+
+impl ShapingFont<'_> {
+    // Shapes a paragraph of mixed-direction text into positioned glyphs in visual order. The
+    // paragraph direction comes from its first strong character unless one is given, and each
+    // directional run is shaped on its own with clusters counted from the start of the text.
+    pub fn shape_paragraph(&self, text: &str, script_tag: &[u8; 4], language_tag: &[u8; 4], direction: Option<Direction>, features: &[FeatureSetting]) -> Vec<PositionedGlyph> {
+        let chars: Vec<char> = text.chars().collect();
+        let paragraph = BidiParagraph::new(&chars, direction);
+        paragraph.visual_runs(0..chars.len()).into_iter()
+            .flat_map(|run| {
+                let run_text: String = chars[run.start..run.end].iter().collect();
+                self.shape(&run_text, script_tag, language_tag, run.direction(), features).into_iter()
+                    .map(move |glyph| PositionedGlyph { cluster: glyph.cluster + run.start, ..glyph })
+            })
+            .collect()
+    }
+
+    // Shapes a run of one direction, given in logical order, into positioned glyphs in visual
+    // order. A DFLT script is detected from the text. `features` turns the default GSUB and GPOS
+    // features off or adds others. Characters the cmap doesn't map become glyph 0 (.notdef),
+    // apart from invisible ones like joiners, which are left out.
+    pub fn shape(&self, text: &str, script_tag: &[u8; 4], language_tag: &[u8; 4], direction: Direction, features: &[FeatureSetting]) -> Vec<PositionedGlyph> {
+        let script_tag = match script_tag {
+            b"DFLT" => &detect_script(text).unwrap_or(*b"DFLT"),
+            script_tag => script_tag,
+        };
+        let script_tag = &self.supported_script(script_tag);
+        let shaper = Shaper::for_script(script_tag);
+        let mut defaults: Vec<[u8; 4]> = DEFAULT_SUBSTITUTION_FEATURES.iter().chain(DEFAULT_POSITIONING_FEATURES.iter()).copied().collect();
+        match shaper {
+            Shaper::Arabic => defaults.extend_from_slice(&arabic::FORM_FEATURES),
+            Shaper::Indic => {
+                defaults.extend_from_slice(&indic::BASIC_FEATURES);
+                defaults.extend_from_slice(&indic::PRESENTATION_FEATURES);
+                defaults.extend_from_slice(&[*b"abvm", *b"blwm"]);
+            }
+            Shaper::Default | Shaper::Thai => {}
+        }
+
+        // Right to left, paired characters like brackets are mirrored through the cmap when the
+        // font has the mirrored character, and through the font's rtlm feature otherwise
+        let mut glyphs = Vec::new();
+        for (cluster, ch) in text.chars().enumerate() {
+            let mirror = match direction {
+                Direction::RightToLeft => mirrored(ch),
+                Direction::LeftToRight => None,
+            };
+            let glyph_index = match mirror.and_then(|mirror| self.glyph_index(mirror)) {
+                Some(glyph_index) => Some(glyph_index),
+                None => {
+                    if mirror.is_some() && !defaults.contains(b"rtlm") {
+                        defaults.push(*b"rtlm");
+                    }
+                    self.glyph_index(ch)
+                }
+            };
+            match glyph_index {
+                Some(glyph_index) => glyphs.push(GlyphInfo { glyph_index, cluster, ..GlyphInfo::default() }),
+                None if !is_default_ignorable(ch) => glyphs.push(GlyphInfo { glyph_index: 0, cluster, ..GlyphInfo::default() }),
+                None => {}
+            }
+        }
+        let settings = merge_feature_settings(&defaults, features);
+        // Script shapers reorder glyphs even when the font has no GSUB to substitute them
+        let empty_gsub = GsubTable::default();
+        let gsub = self.gsub.unwrap_or(&empty_gsub);
+        let chars: Vec<char> = text.chars().collect();
+        let mut plan = SubstitutionPlan::new(gsub, self.gdef, script_tag, language_tag, &settings);
+        match shaper {
+            Shaper::Arabic => arabic::substitute(&mut plan, &mut glyphs, &chars),
+            Shaper::Indic => indic::substitute(&mut plan, &mut glyphs, &chars, &|ch| self.glyph_index(ch)),
+            Shaper::Thai => thai::substitute(&mut plan, &mut glyphs, &chars, &|ch| self.glyph_index(ch)),
+            Shaper::Default => gsub.substitute(&mut glyphs, self.gdef, script_tag, language_tag, &settings),
+        }
+
+        let positions = self.position(&glyphs, script_tag, language_tag, direction, &settings);
+        let mut shaped: Vec<PositionedGlyph> = glyphs.iter()
+            .zip(positions)
+            .map(|(info, position)| PositionedGlyph {
+                glyph_id: info.glyph_index,
+                cluster: info.cluster,
+                font_index: 0,
+                x_advance: position.x_advance,
+                y_advance: position.y_advance,
+                x_offset: position.x_offset,
+                y_offset: position.y_offset,
+            })
+            .collect();
+        if direction == Direction::RightToLeft {
+            shaped.reverse();
+        }
+        shaped
+    }
+
+    // Fonts made before the second Indic specification only have the old script tags, whose
+    // shaping this falls back to
+    fn supported_script(&self, script_tag: &[u8; 4]) -> [u8; 4] {
+        let old_tag = match script_tag {
+            b"dev2" => b"deva",
+            b"bng2" => b"beng",
+            _ => return *script_tag,
+        };
+        let has_script = |tag: &[u8; 4]| {
+            self.gsub.is_some_and(|gsub| gsub.scripts.iter().any(|script| &script.tag == tag))
+                || self.gpos.is_some_and(|gpos| gpos.scripts.iter().any(|script| &script.tag == tag))
+        };
+        if !has_script(script_tag) && has_script(old_tag) {
+            *old_tag
+        } else {
+            *script_tag
+        }
+    }
+
+    // Whether the cmap maps a character to a glyph
+    pub fn covers(&self, ch: char) -> bool {
+        self.glyph_index(ch).is_some()
+    }
+
+    // Format 4 only covers the Basic Multilingual Plane. Segments can map code points in their
+    // range to glyph 0, which means the character is missing all the same.
+    fn glyph_index(&self, ch: char) -> Option<u16> {
+        self.cmap.char_to_glyph_index(u16::try_from(ch as u32).ok()?).filter(|&glyph_index| glyph_index != 0)
+    }
+
+    // Positions substituted glyphs in logical order. Advances start from hmtx with GDEF marks
+    // zeroed, then GPOS applies the enabled features. A font whose GPOS has no kern feature for
+    // the script falls back to its legacy kern table.
+    pub fn position(&self, glyphs: &[GlyphInfo], script_tag: &[u8; 4], language_tag: &[u8; 4], direction: Direction, settings: &[FeatureSetting]) -> Vec<GlyphPosition> {
+        let right_to_left = direction == Direction::RightToLeft;
+        let glyph_indices: Vec<u16> = glyphs.iter().map(|info| info.glyph_index).collect();
+        let mut positions: Vec<GlyphPosition> = glyph_indices.iter()
+            .map(|&glyph_index| {
+                let is_mark = self.gdef.is_some_and(|gdef| gdef.glyph_class(glyph_index) == GlyphClass::Mark);
+                let advance_width = self.hmtx.advance_widths.get(glyph_index as usize).copied().unwrap_or_default();
+                GlyphPosition {
+                    x_advance: if is_mark { 0 } else { advance_width as i32 },
+                    ..GlyphPosition::default()
+                }
+            })
+            .collect();
+
+        let has_kern_feature = self.gpos.is_some_and(|gpos| !gpos.lookup_indices(script_tag, language_tag, &[*b"kern"]).is_empty());
+        if let Some(gpos) = self.gpos {
+            let lookup_indices: Vec<u16> = gpos.lookups_for(script_tag, language_tag, settings).into_iter().map(|(lookup_index, _)| lookup_index).collect();
+            let ligature_components: Vec<Option<u16>> = glyphs.iter().map(|info| info.ligature_component).collect();
+            gpos.apply_lookups(&glyph_indices, &ligature_components, &mut positions, self.gdef, &lookup_indices, right_to_left);
+        }
+        let kern_enabled = settings.iter().any(|setting| &setting.tag == b"kern");
+        if let (Some(kern_table), false, true) = (self.kern, has_kern_feature, kern_enabled) {
+            // Pairs in the kern table are in visual order, which runs backwards through an RTL run
+            for (index, pair) in glyph_indices.windows(2).enumerate() {
+                if right_to_left {
+                    positions[index + 1].x_advance += kern_table.kerning(pair[1], pair[0]) as i32;
+                } else {
+                    positions[index].x_advance += kern_table.kerning(pair[0], pair[1]) as i32;
+                }
+            }
+        }
+        positions
+    }
+}
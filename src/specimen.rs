@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::glyph::Glyph;
+use crate::image::{Color, RgbImage};
+use crate::raster::{rasterize_glyph, FillRule};
+use crate::svg::{format_number, scaled_glyph_path_data};
+use crate::table::HheaTable;
+
+// Coverage used to draw the cell borders in the foreground colour
+const GRID_COVERAGE: u8 = 48;
+
+// Annotation lines under each glyph: id, post name and code points
+const LABEL_LINES: usize = 3;
+
+// One chart cell: the glyph plus what the font says about it
+#[derive(Debug, Clone)]
+pub struct SpecimenGlyph {
+    pub glyph: Glyph,
+    pub name: Option<String>,
+    pub code_points: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SpecimenOptions {
+    pub pixel_size: f32,
+    pub label_size: f32,
+    pub columns: usize,
+    pub rows_per_page: usize,
+    pub foreground: Color,
+    pub background: Color,
+}
+
+impl Default for SpecimenOptions {
+    fn default() -> Self {
+        SpecimenOptions {
+            pixel_size: 64.0,
+            label_size: 11.0,
+            columns: 16,
+            rows_per_page: 12,
+            foreground: Color::BLACK,
+            background: Color::WHITE,
+        }
+    }
+}
+
+// Cell geometry shared by the PNG and SVG charts, in pixels
+struct CellLayout {
+    width: f32,
+    height: f32,
+    ascender: f32,
+    label_line_height: f32,
+    glyph_height: f32,
+}
+
+impl CellLayout {
+    fn new(hhea_table: &HheaTable, units_per_em: u16, options: &SpecimenOptions) -> Self {
+        let scale = options.pixel_size / units_per_em as f32;
+        let glyph_height = (hhea_table.ascender as f32 - hhea_table.descender as f32) * scale;
+        let label_line_height = options.label_size * 1.25;
+        CellLayout {
+            width: (options.pixel_size * 1.5).max(options.label_size * 8.0).ceil(),
+            height: (glyph_height + label_line_height * LABEL_LINES as f32 + options.label_size).ceil(),
+            ascender: hhea_table.ascender as f32 * scale,
+            label_line_height,
+            glyph_height,
+        }
+    }
+
+    // Top-left corner of the cell at `index` within its page
+    fn origin(&self, index: usize, options: &SpecimenOptions) -> (f32, f32) {
+        let (row, column) = (index / options.columns, index % options.columns);
+        (column as f32 * self.width, row as f32 * self.height)
+    }
+
+    fn page_size(&self, cells: usize, options: &SpecimenOptions) -> (usize, usize) {
+        let rows = cells.div_ceil(options.columns);
+        (
+            (options.columns.min(cells) as f32 * self.width).ceil() as usize + 1,
+            (rows as f32 * self.height).ceil() as usize + 1,
+        )
+    }
+}
+
+fn format_code_points(code_points: &[u32]) -> String {
+    code_points.iter().map(|code_point| format!("U+{:04X}", code_point)).collect::<Vec<_>>().join(" ")
+}
+
+fn cell_labels(entry: &SpecimenGlyph) -> [String; LABEL_LINES] {
+    [
+        format!("#{}", entry.glyph.glyph_index),
+        entry.name.clone().unwrap_or_default(),
+        format_code_points(&entry.code_points),
+    ]
+}
+
+fn pages<'a>(glyphs: &'a [SpecimenGlyph], options: &SpecimenOptions) -> Vec<&'a [SpecimenGlyph]> {
+    let per_page = (options.columns * options.rows_per_page).max(1);
+    glyphs.chunks(per_page).collect()
+}
+
+// Draws `text` with the font's own glyphs, stopping before it would overflow `max_width`
+fn draw_label(image: &mut RgbImage, text: &str, position: (f32, f32), max_width: f32, label_glyphs: &HashMap<char, Glyph>, units_per_em: u16, options: &SpecimenOptions) {
+    let scale = options.label_size / units_per_em as f32;
+    let (mut pen_x, baseline) = position;
+    for ch in text.chars() {
+        let glyph = match label_glyphs.get(&ch) {
+            Some(glyph) => glyph,
+            None => continue,
+        };
+        let advance = glyph.advance_width as f32 * scale;
+        if pen_x + advance > position.0 + max_width {
+            break;
+        }
+        let origin_x = pen_x.floor();
+        let bitmap = rasterize_glyph(glyph, units_per_em, options.label_size, (pen_x - origin_x, 0.0), FillRule::NonZero);
+        image.draw_bitmap(&bitmap, origin_x as i32, baseline.round() as i32, options.foreground);
+        pen_x += advance;
+    }
+}
+
+fn draw_rect_outline(image: &mut RgbImage, x: i32, y: i32, width: i32, height: i32, color: Color) {
+    for i in 0..=width {
+        image.blend(x + i, y, GRID_COVERAGE, color);
+        image.blend(x + i, y + height, GRID_COVERAGE, color);
+    }
+    for i in 1..height {
+        image.blend(x, y + i, GRID_COVERAGE, color);
+        image.blend(x + width, y + i, GRID_COVERAGE, color);
+    }
+}
+
+// Renders the glyphs into grid pages of `columns` x `rows_per_page` cells. Labels are drawn with
+// the glyphs in `label_glyphs`, normally the font's own digits and letters.
+pub fn specimen_png_pages(glyphs: &[SpecimenGlyph], units_per_em: u16, hhea_table: &HheaTable, label_glyphs: &HashMap<char, Glyph>, options: &SpecimenOptions) -> Vec<RgbImage> {
+    let layout = CellLayout::new(hhea_table, units_per_em, options);
+    let scale = options.pixel_size / units_per_em as f32;
+
+    pages(glyphs, options).into_iter()
+        .map(|page| {
+            let (width, height) = layout.page_size(page.len(), options);
+            let mut image = RgbImage::new(width, height, options.background);
+            for (index, entry) in page.iter().enumerate() {
+                let (x, y) = layout.origin(index, options);
+                draw_rect_outline(&mut image, x as i32, y as i32, layout.width as i32, layout.height as i32, options.foreground);
+
+                // Centre the glyph's advance in the cell
+                let pen_x = x + (layout.width - entry.glyph.advance_width as f32 * scale) / 2.0;
+                let origin_x = pen_x.floor();
+                let bitmap = rasterize_glyph(&entry.glyph, units_per_em, options.pixel_size, (pen_x - origin_x, 0.0), FillRule::NonZero);
+                image.draw_bitmap(&bitmap, origin_x as i32, (y + layout.ascender).round() as i32, options.foreground);
+
+                let label_x = x + options.label_size * 0.25;
+                let max_width = layout.width - options.label_size * 0.5;
+                for (line, label) in cell_labels(entry).iter().enumerate() {
+                    let baseline = y + layout.glyph_height + layout.label_line_height * (line + 1) as f32;
+                    draw_label(&mut image, label, (label_x, baseline), max_width, label_glyphs, units_per_em, options);
+                }
+            }
+            image
+        })
+        .collect()
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// Same grid as the PNG chart with glyphs as paths and labels as SVG text
+pub fn specimen_svg_pages(glyphs: &[SpecimenGlyph], units_per_em: u16, hhea_table: &HheaTable, options: &SpecimenOptions) -> Vec<String> {
+    let layout = CellLayout::new(hhea_table, units_per_em, options);
+    let scale = options.pixel_size / units_per_em as f32;
+    let (foreground, background) = (options.foreground.to_hex(), options.background.to_hex());
+
+    pages(glyphs, options).into_iter()
+        .map(|page| {
+            let (width, height) = layout.page_size(page.len(), options);
+            let mut svg = String::new();
+            let _ = writeln!(
+                svg,
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}">"#,
+                width, height, width, height,
+            );
+            let _ = writeln!(svg, r#"  <rect width="100%" height="100%" fill="{}"/>"#, background);
+            let _ = writeln!(
+                svg,
+                r#"  <g fill="{}" font-family="monospace" font-size="{}">"#,
+                foreground, format_number(options.label_size),
+            );
+            for (index, entry) in page.iter().enumerate() {
+                let (x, y) = layout.origin(index, options);
+                let _ = writeln!(
+                    svg,
+                    r#"    <rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="{}" stroke-opacity="0.2"/>"#,
+                    format_number(x), format_number(y), format_number(layout.width), format_number(layout.height), foreground,
+                );
+
+                let pen_x = x + (layout.width - entry.glyph.advance_width as f32 * scale) / 2.0;
+                let data = scaled_glyph_path_data(&entry.glyph, units_per_em, options.pixel_size, pen_x, y + layout.ascender);
+                if !data.is_empty() {
+                    let _ = writeln!(svg, r#"    <path d="{}"/>"#, data);
+                }
+
+                let label_x = x + options.label_size * 0.25;
+                for (line, label) in cell_labels(entry).iter().enumerate() {
+                    if label.is_empty() {
+                        continue;
+                    }
+                    let baseline = y + layout.glyph_height + layout.label_line_height * (line + 1) as f32;
+                    let _ = writeln!(
+                        svg,
+                        r#"    <text x="{}" y="{}" textLength="{}" lengthAdjust="spacingAndGlyphs">{}</text>"#,
+                        format_number(label_x),
+                        format_number(baseline),
+                        format_number((label.chars().count() as f32 * options.label_size * 0.6).min(layout.width - options.label_size * 0.5)),
+                        escape_xml(label),
+                    );
+                }
+            }
+            svg.push_str("  </g>\n</svg>\n");
+            svg
+        })
+        .collect()
+}
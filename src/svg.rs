@@ -0,0 +1,100 @@
+use std::fmt::Write;
+
+use crate::glyph::Glyph;
+use crate::gpos::GlyphPosition;
+use crate::headless::{layout_lines, RenderOptions};
+use crate::metrics::LineMetrics;
+use crate::outline::{Outline, Segment};
+use crate::table::HheaTable;
+
+// Shortest form of a coordinate: integers stay integers, anything else keeps two decimals
+pub(crate) fn format_number(value: f32) -> String {
+    let rounded = (value * 100.0).round() / 100.0;
+    if rounded == rounded.trunc() {
+        format!("{}", rounded as i64)
+    } else {
+        format!("{:.2}", rounded).trim_end_matches('0').to_string()
+    }
+}
+
+fn push_command(data: &mut String, command: char, points: &[(f32, f32)]) {
+    if !data.is_empty() {
+        data.push(' ');
+    }
+    data.push(command);
+    let coordinates: Vec<String> = points.iter()
+        .flat_map(|&(x, y)| [format_number(x), format_number(y)])
+        .collect();
+    data.push_str(&coordinates.join(" "));
+}
+
+// Path data using M, L, Q and Z commands, with coordinates exactly as stored in the outline
+pub fn outline_path_data(outline: &Outline) -> String {
+    let mut data = String::new();
+    for contour in &outline.contours {
+        if let Some(first) = contour.first() {
+            push_command(&mut data, 'M', &[first.start()]);
+        }
+        for segment in contour {
+            match *segment {
+                Segment::Line(_, p1) => push_command(&mut data, 'L', &[p1]),
+                Segment::Quad(_, p1, p2) => push_command(&mut data, 'Q', &[p1, p2]),
+            }
+        }
+        push_command(&mut data, 'Z', &[]);
+    }
+    data
+}
+
+// Path data in font units with y pointing up, as stored in the glyf table
+pub fn glyph_path_data(glyph: &Glyph) -> String {
+    outline_path_data(&Outline::from_glyph(glyph))
+}
+
+// Path data scaled to `pixel_size` with y flipped to point down, origin at (`x`, `baseline`)
+pub fn scaled_glyph_path_data(glyph: &Glyph, units_per_em: u16, pixel_size: f32, x: f32, baseline: f32) -> String {
+    let scale = pixel_size / units_per_em as f32;
+    outline_path_data(&Outline::from_glyph(glyph).transform(scale, -scale, x, baseline))
+}
+
+// Standalone SVG of a single glyph in font units. The view box spans the advance width and the
+// hhea ascender to descender, flipped so the glyph stands upright.
+pub fn glyph_svg(glyph: &Glyph, hhea_table: &HheaTable) -> String {
+    let height = hhea_table.ascender as i32 - hhea_table.descender as i32;
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 {} {} {}">"#,
+        -(hhea_table.ascender as i32),
+        format_number(glyph.advance_width as f32),
+        height,
+    );
+    let _ = writeln!(svg, r#"  <path transform="scale(1 -1)" d="{}"/>"#, glyph_path_data(glyph));
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// SVG of lines of glyphs laid out the same way as the headless PNG render, one path per glyph
+pub fn text_svg(lines: &[Vec<Glyph>], positions: &[Vec<GlyphPosition>], line_offsets: &[i32], units_per_em: u16, metrics: &LineMetrics, options: &RenderOptions) -> String {
+    let layout = layout_lines(lines, positions, line_offsets, units_per_em, metrics, options);
+    let (width, height) = (format_number(layout.width), format_number(layout.height));
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}">"#,
+        width, height, width, height,
+    );
+    let _ = writeln!(svg, r#"  <rect width="100%" height="100%" fill="{}"/>"#, options.background.to_hex());
+    let _ = writeln!(svg, r#"  <g fill="{}">"#, options.foreground.to_hex());
+    for (line, origins) in lines.iter().zip(&layout.origins) {
+        for (glyph, &(x, baseline)) in line.iter().zip(origins) {
+            let data = scaled_glyph_path_data(glyph, units_per_em, options.pixel_size, x, baseline);
+            if !data.is_empty() {
+                let _ = writeln!(svg, r#"    <path d="{}"/>"#, data);
+            }
+        }
+    }
+    svg.push_str("  </g>\n</svg>\n");
+    svg
+}
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 #[derive(Debug, Clone, Copy)]
 pub enum TableName {
     Maxp,
@@ -7,6 +9,18 @@ pub enum TableName {
     Hmtx,
     Loca,
     Glyf,
+    Fpgm,
+    Prep,
+    Cvt,
+    Post,
+    Vhea,
+    Vmtx,
+    Vorg,
+    Os2,
+    Kern,
+    Gdef,
+    Gpos,
+    Gsub,
 }
 
 impl TableName {
@@ -19,6 +33,18 @@ impl TableName {
             TableName::Hmtx => b"hmtx",
             TableName::Loca => b"loca",
             TableName::Glyf => b"glyf",
+            TableName::Fpgm => b"fpgm",
+            TableName::Prep => b"prep",
+            TableName::Cvt => b"cvt ",
+            TableName::Post => b"post",
+            TableName::Vhea => b"vhea",
+            TableName::Vmtx => b"vmtx",
+            TableName::Vorg => b"VORG",
+            TableName::Os2 => b"OS/2",
+            TableName::Kern => b"kern",
+            TableName::Gdef => b"GDEF",
+            TableName::Gpos => b"GPOS",
+            TableName::Gsub => b"GSUB",
         }
     }
 }
@@ -27,20 +53,39 @@ impl TableName {
 pub struct TableRecord {
     pub tag: [u8; 4],
     pub absolute_offset: u32,
+    pub length: u32,
 }
 
+// Fields after num_glyphs only exist in version 1.0 (TrueType outlines) and are zero otherwise
 #[derive(Debug)]
 pub struct MaxpTable {
     pub num_glyphs: u16,
+    pub max_points: u16,
+    pub max_contours: u16,
+    pub max_composite_points: u16,
+    pub max_composite_contours: u16,
+    pub max_zones: u16,
+    pub max_twilight_points: u16,
+    pub max_storage: u16,
+    pub max_function_defs: u16,
+    pub max_instruction_defs: u16,
+    pub max_stack_elements: u16,
+    pub max_size_of_instructions: u16,
+    pub max_component_elements: u16,
+    pub max_component_depth: u16,
 }
 
 #[derive(Debug)]
 pub struct HeadTable {
+    pub units_per_em: u16,
     pub index_to_loc_format: i16,
 }
 
 #[derive(Debug)]
 pub struct HheaTable {
+    pub ascender: i16,
+    pub descender: i16,
+    pub line_gap: i16,
     pub num_h_metrics: u16,
 }
 
@@ -50,6 +95,209 @@ pub struct HmtxTable {
     pub left_side_bearings: Vec<i16>,
 }
 
+// Vertical counterpart of hhea; ascender and descender are measured from the vertical centre line
+#[derive(Debug)]
+pub struct VheaTable {
+    pub ascender: i16,
+    pub descender: i16,
+    pub line_gap: i16,
+    pub advance_height_max: u16,
+    pub num_v_metrics: u16,
+}
+
+#[derive(Debug)]
+pub struct VmtxTable {
+    pub advance_heights: Vec<u16>,
+    pub top_side_bearings: Vec<i16>,
+}
+
+// Vertical origins for CFF fonts, which have no phantom points to carry them
+#[derive(Debug)]
+pub struct VorgTable {
+    pub default_vert_origin_y: i16,
+    pub vert_origin_y_metrics: Vec<(u16, i16)>, // Sorted by glyph index
+}
+
+impl VorgTable {
+    pub fn vert_origin_y(&self, glyph_index: u16) -> i16 {
+        self.vert_origin_y_metrics.binary_search_by_key(&glyph_index, |&(index, _)| index)
+            .map_or(self.default_vert_origin_y, |found| self.vert_origin_y_metrics[found].1)
+    }
+}
+
+// Fields after win_descent only exist from version 2 and are zero otherwise
+#[derive(Debug)]
+pub struct Os2Table {
+    pub version: u16,
+    pub x_avg_char_width: i16,
+    pub weight_class: u16,
+    pub width_class: u16,
+    pub fs_selection: u16,
+    pub typo_ascender: i16,
+    pub typo_descender: i16,
+    pub typo_line_gap: i16,
+    pub win_ascent: u16,
+    pub win_descent: u16,
+    pub x_height: i16,
+    pub cap_height: i16,
+}
+
+// Legacy pair kerning, in either the Windows version 0 or the Apple version 1 layout
+#[derive(Debug)]
+pub struct KernTable {
+    pub version: u32,
+    pub subtables: Vec<KernSubtable>,
+}
+
+impl KernTable {
+    // Horizontal kerning between two adjacent glyphs in font units, summed over every subtable
+    // that applies to horizontal text. An override subtable replaces what came before it.
+    pub fn kerning(&self, left_glyph: u16, right_glyph: u16) -> i16 {
+        let mut total: i16 = 0;
+        for subtable in &self.subtables {
+            if !subtable.horizontal || subtable.cross_stream || subtable.variation || subtable.minimum {
+                continue;
+            }
+            let value = subtable.kerning(left_glyph, right_glyph);
+            if subtable.is_override {
+                if value != 0 {
+                    total = value;
+                }
+            } else {
+                total = total.saturating_add(value);
+            }
+        }
+        total
+    }
+}
+
+// Coverage bits are normalised across both versions: version 0 has no variation subtables and
+// version 1 has no minimum or override ones
+#[derive(Debug)]
+pub struct KernSubtable {
+    pub horizontal: bool,
+    pub minimum: bool,
+    pub cross_stream: bool,
+    pub variation: bool,
+    pub is_override: bool,
+    pub format: KernFormat,
+}
+
+impl KernSubtable {
+    pub fn kerning(&self, left_glyph: u16, right_glyph: u16) -> i16 {
+        match &self.format {
+            KernFormat::Pairs(pairs) => {
+                let key = (left_glyph as u32) << 16 | right_glyph as u32;
+                pairs.binary_search_by_key(&key, |&(pair, _)| pair).map_or(0, |found| pairs[found].1)
+            }
+            KernFormat::ClassArray { left_classes, right_classes, array_offset, values } => {
+                // Class values are byte offsets from the start of the subtable, so their sum
+                // lands in the value array; glyphs outside a class table get 0, which does not
+                let offset = left_classes.class(left_glyph) as usize + right_classes.class(right_glyph) as usize;
+                match offset.checked_sub(*array_offset as usize) {
+                    Some(offset) => values.get(offset / 2).copied().unwrap_or(0),
+                    None => 0,
+                }
+            }
+            KernFormat::IndexArray { left_classes, right_classes, right_class_count, kern_index, kern_values } => {
+                let left_class = left_classes.get(left_glyph as usize).copied().unwrap_or(0) as usize;
+                let right_class = right_classes.get(right_glyph as usize).copied().unwrap_or(0) as usize;
+                kern_index.get(left_class * *right_class_count as usize + right_class)
+                    .and_then(|&index| kern_values.get(index as usize))
+                    .copied()
+                    .unwrap_or(0)
+            }
+            KernFormat::Unsupported(_) => 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum KernFormat {
+    // Format 0: (left << 16 | right, value), sorted by pair
+    Pairs(Vec<(u32, i16)>),
+    // Format 2: two-dimensional array indexed by class offsets
+    ClassArray {
+        left_classes: KernClassTable,
+        right_classes: KernClassTable,
+        array_offset: u16,
+        values: Vec<i16>,
+    },
+    // Format 3 (Apple only): byte classes for every glyph and an index into a table of values
+    IndexArray {
+        left_classes: Vec<u8>,
+        right_classes: Vec<u8>,
+        right_class_count: u8,
+        kern_index: Vec<u8>,
+        kern_values: Vec<i16>,
+    },
+    Unsupported(u8),
+}
+
+#[derive(Debug)]
+pub struct KernClassTable {
+    pub first_glyph: u16,
+    pub classes: Vec<u16>,
+}
+
+impl KernClassTable {
+    pub fn class(&self, glyph_index: u16) -> u16 {
+        glyph_index.checked_sub(self.first_glyph)
+            .and_then(|index| self.classes.get(index as usize).copied())
+            .unwrap_or(0)
+    }
+}
+
+#[derive(Debug)]
+pub struct PostTable {
+    pub version: u32,
+    pub italic_angle: i32,
+    pub underline_position: i16,
+    pub underline_thickness: i16,
+    pub is_fixed_pitch: u32,
+    // One name per glyph for versions 1.0, 2.0 and 2.5; empty for 3.0, which stores no names
+    pub glyph_names: Vec<String>,
+}
+
+impl PostTable {
+    pub fn glyph_name(&self, glyph_index: u16) -> Option<&str> {
+        self.glyph_names.get(glyph_index as usize).map(|name| name.as_str())
+    }
+}
+
+// Names of the standard Macintosh glyph order, used by post format 1 and indexed by format 2
+pub const MAC_GLYPH_NAMES: [&str; 258] = [
+    ".notdef", ".null", "nonmarkingreturn", "space", "exclam", "quotedbl", "numbersign", "dollar",
+    "percent", "ampersand", "quotesingle", "parenleft", "parenright", "asterisk", "plus", "comma",
+    "hyphen", "period", "slash", "zero", "one", "two", "three", "four", "five", "six", "seven",
+    "eight", "nine", "colon", "semicolon", "less", "equal", "greater", "question", "at", "A", "B",
+    "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M", "N", "O", "P", "Q", "R", "S", "T", "U",
+    "V", "W", "X", "Y", "Z", "bracketleft", "backslash", "bracketright", "asciicircum",
+    "underscore", "grave", "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n",
+    "o", "p", "q", "r", "s", "t", "u", "v", "w", "x", "y", "z", "braceleft", "bar", "braceright",
+    "asciitilde", "Adieresis", "Aring", "Ccedilla", "Eacute", "Ntilde", "Odieresis", "Udieresis",
+    "aacute", "agrave", "acircumflex", "adieresis", "atilde", "aring", "ccedilla", "eacute",
+    "egrave", "ecircumflex", "edieresis", "iacute", "igrave", "icircumflex", "idieresis", "ntilde",
+    "oacute", "ograve", "ocircumflex", "odieresis", "otilde", "uacute", "ugrave", "ucircumflex",
+    "udieresis", "dagger", "degree", "cent", "sterling", "section", "bullet", "paragraph",
+    "germandbls", "registered", "copyright", "trademark", "acute", "dieresis", "notequal", "AE",
+    "Oslash", "infinity", "plusminus", "lessequal", "greaterequal", "yen", "mu", "partialdiff",
+    "summation", "product", "pi", "integral", "ordfeminine", "ordmasculine", "Omega", "ae",
+    "oslash", "questiondown", "exclamdown", "logicalnot", "radical", "florin", "approxequal",
+    "Delta", "guillemotleft", "guillemotright", "ellipsis", "nonbreakingspace", "Agrave", "Atilde",
+    "Otilde", "OE", "oe", "endash", "emdash", "quotedblleft", "quotedblright", "quoteleft",
+    "quoteright", "divide", "lozenge", "ydieresis", "Ydieresis", "fraction", "currency",
+    "guilsinglleft", "guilsinglright", "fi", "fl", "daggerdbl", "periodcentered", "quotesinglbase",
+    "quotedblbase", "perthousand", "Acircumflex", "Ecircumflex", "Aacute", "Edieresis", "Egrave",
+    "Iacute", "Icircumflex", "Idieresis", "Igrave", "Oacute", "Ocircumflex", "apple", "Ograve",
+    "Uacute", "Ucircumflex", "Ugrave", "dotlessi", "circumflex", "tilde", "macron", "breve",
+    "dotaccent", "ring", "cedilla", "hungarumlaut", "ogonek", "caron", "Lslash", "lslash",
+    "Scaron", "scaron", "Zcaron", "zcaron", "brokenbar", "Eth", "eth", "Yacute", "yacute", "Thorn",
+    "thorn", "minus", "multiply", "onesuperior", "twosuperior", "threesuperior", "onehalf",
+    "onequarter", "threequarters", "franc", "Gbreve", "gbreve", "Idotaccent", "Scedilla",
+    "scedilla", "Cacute", "cacute", "Ccaron", "ccaron", "dcroat",
+];
+
 #[derive(Debug)]
 pub struct CmapTable {
     pub num_tables: u16,
@@ -83,14 +331,34 @@ impl CmapFormat4 {
     pub fn char_to_glyph_index(&self, char_code: u16) -> Option<u16> {
         for i in 0..self.end_code.len() {
             if char_code >= self.start_code[i] && char_code <= self.end_code[i] {
-                if self.id_range_offset[i] == 0 {
-                    return Some((((char_code as i32 + self.id_delta[i] as i32) % 65536) & 0xFFFF) as u16);
-                } else {
-                    let offset = (self.id_range_offset[i] as usize / 2 + (char_code - self.start_code[i]) as usize - (self.end_code.len() - i)) as usize;
-                    return Some(self.glyph_id_array[offset]);
-                }
+                return self.segment_glyph_index(i, char_code);
             }
         }
         None
-    }    
+    }
+
+    // Every mapped code point grouped by the glyph it maps to, in ascending order
+    pub fn code_points_by_glyph(&self) -> HashMap<u16, Vec<u32>> {
+        let mut code_points: HashMap<u16, Vec<u32>> = HashMap::new();
+        for i in 0..self.end_code.len() {
+            // The final 0xFFFF segment only terminates the table
+            for char_code in self.start_code[i]..=self.end_code[i].min(0xFFFE) {
+                if let Some(glyph_index) = self.segment_glyph_index(i, char_code) {
+                    if glyph_index != 0 {
+                        code_points.entry(glyph_index).or_default().push(char_code as u32);
+                    }
+                }
+            }
+        }
+        code_points
+    }
+
+    fn segment_glyph_index(&self, i: usize, char_code: u16) -> Option<u16> {
+        if self.id_range_offset[i] == 0 {
+            Some((((char_code as i32 + self.id_delta[i] as i32) % 65536) & 0xFFFF) as u16)
+        } else {
+            let offset = self.id_range_offset[i] as usize / 2 + (char_code - self.start_code[i]) as usize - (self.end_code.len() - i);
+            self.glyph_id_array.get(offset).copied()
+        }
+    }
 }
@@ -0,0 +1,47 @@
+use crate::gsub::{GlyphInfo, SubstitutionPlan};
+
+// SARA AM and its Lao equivalent, with the NIKHAHIT and SARA AA they're drawn as
+const SARA_AM: [(char, char, char); 2] = [('\u{0E33}', '\u{0E4D}', '\u{0E32}'), ('\u{0EB3}', '\u{0ECD}', '\u{0EB2}')];
+
+// Vowels and tone marks drawn above the consonant, which the NIKHAHIT goes under
+fn is_above_mark(ch: char) -> bool {
+    matches!(ch as u32, 0x0E31 | 0x0E34..=0x0E37 | 0x0E47..=0x0E4E | 0x0EB1 | 0x0EB4..=0x0EB7 | 0x0EBB | 0x0EC8..=0x0ECD)
+}
+
+// Substitutes a run of Thai or Lao. SARA AM is split into NIKHAHIT and SARA AA, and the NIKHAHIT
+// moves in front of the above-base marks before it so that they stack over it, as fonts without
+// a ccmp feature for this expect. Fonts that don't have both parts keep SARA AM whole.
+// Clusters index `text`, the run's characters.
+pub fn substitute(plan: &mut SubstitutionPlan, glyphs: &mut Vec<GlyphInfo>, text: &[char], glyph_index: &dyn Fn(char) -> Option<u16>) {
+    decompose_sara_am(glyphs, text, glyph_index);
+    plan.apply_remaining(glyphs);
+}
+
+fn decompose_sara_am(glyphs: &mut Vec<GlyphInfo>, text: &[char], glyph_index: &dyn Fn(char) -> Option<u16>) {
+    let mut index = 0;
+    while index < glyphs.len() {
+        let ch = text.get(glyphs[index].cluster).copied();
+        let parts = SARA_AM.iter()
+            .find(|&&(sara_am, _, _)| Some(sara_am) == ch)
+            .and_then(|&(_, nikhahit, sara_aa)| Some((glyph_index(nikhahit)?, glyph_index(sara_aa)?)));
+        let Some((nikhahit, sara_aa)) = parts else {
+            index += 1;
+            continue;
+        };
+
+        let original = glyphs[index];
+        glyphs[index].glyph_index = sara_aa;
+        let mut target = index;
+        while target > 0 && text.get(glyphs[target - 1].cluster).is_some_and(|&ch| is_above_mark(ch)) {
+            target -= 1;
+        }
+        glyphs.insert(target, GlyphInfo { glyph_index: nikhahit, ..original });
+
+        // Everything the NIKHAHIT moved past now forms one cluster with it
+        let cluster = glyphs[target..=index + 1].iter().map(|info| info.cluster).min().unwrap_or(original.cluster);
+        for info in &mut glyphs[target..=index + 1] {
+            info.cluster = cluster;
+        }
+        index += 2;
+    }
+}
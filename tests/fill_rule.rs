@@ -0,0 +1,163 @@
+use ttf_parser::buffer::ByteBuffer;
+use ttf_parser::glyph::Glyph;
+use ttf_parser::raster::{rasterize_glyph, FillRule, GlyphBitmap};
+use ttf_parser::reader::{read_table_directory, FontParser};
+use ttf_parser::table::HmtxTable;
+
+const UNITS_PER_EM: u16 = 1000;
+const PIXEL_SIZE: f32 = 100.0;
+
+// Clockwise square contour in font units, as TrueType expects for filled areas
+fn square(x: i16, y: i16, size: i16) -> Vec<(i16, i16)> {
+    vec![(x, y), (x, y + size), (x + size, y + size), (x + size, y)]
+}
+
+// Simple glyph record with on-curve points only. `overlap_simple` sets OVERLAP_SIMPLE on the first
+// point, which variable font instancers emit when they keep overlapping contours.
+fn simple_glyph_record(contours: &[Vec<(i16, i16)>], overlap_simple: bool) -> Vec<u8> {
+    let points: Vec<(i16, i16)> = contours.iter().flatten().copied().collect();
+    let mut record = Vec::new();
+    record.extend((contours.len() as i16).to_be_bytes());
+    for value in [
+        points.iter().map(|p| p.0).min().unwrap(),
+        points.iter().map(|p| p.1).min().unwrap(),
+        points.iter().map(|p| p.0).max().unwrap(),
+        points.iter().map(|p| p.1).max().unwrap(),
+    ] {
+        record.extend(value.to_be_bytes());
+    }
+    let mut end = 0u16;
+    for contour in contours {
+        end += contour.len() as u16;
+        record.extend((end - 1).to_be_bytes());
+    }
+    record.extend(0u16.to_be_bytes()); // No instructions
+    for i in 0..points.len() {
+        record.push(if i == 0 && overlap_simple { 0x41 } else { 0x01 });
+    }
+    let (mut previous_x, mut previous_y) = (0, 0);
+    for &(x, _) in &points {
+        record.extend((x - previous_x).to_be_bytes());
+        previous_x = x;
+    }
+    for &(_, y) in &points {
+        record.extend((y - previous_y).to_be_bytes());
+        previous_y = y;
+    }
+    record
+}
+
+// Composite record placing glyph `component` at each offset, flagged OVERLAP_COMPOUND
+fn composite_glyph_record(component: u16, offsets: &[(i16, i16)]) -> Vec<u8> {
+    let mut record = Vec::new();
+    record.extend((-1i16).to_be_bytes());
+    record.extend([0u8; 8]); // Bounding box is not used for rendering
+    for (i, &(dx, dy)) in offsets.iter().enumerate() {
+        let mut flags = 0x0001 | 0x0002; // ARG_1_AND_2_ARE_WORDS | ARGS_ARE_XY_VALUES
+        if i == 0 {
+            flags |= 0x0400; // OVERLAP_COMPOUND
+        }
+        if i + 1 < offsets.len() {
+            flags |= 0x0020; // MORE_COMPONENTS
+        }
+        record.extend((flags as u16).to_be_bytes());
+        record.extend(component.to_be_bytes());
+        record.extend(dx.to_be_bytes());
+        record.extend(dy.to_be_bytes());
+    }
+    record
+}
+
+// Builds a font containing only a glyf table and reads every glyph back through FontParser
+fn read_glyphs(records: &[Vec<u8>]) -> Vec<Glyph> {
+    let mut font = Vec::new();
+    font.extend(0x00010000u32.to_be_bytes());
+    font.extend(1u16.to_be_bytes());
+    font.extend([0u8; 6]);
+    font.extend(b"glyf");
+    font.extend(0u32.to_be_bytes());
+    font.extend(28u32.to_be_bytes()); // Offset just past this single table record
+    let glyf_length: usize = records.iter().map(|record| record.len()).sum();
+    font.extend((glyf_length as u32).to_be_bytes());
+
+    let mut glyph_offsets = vec![0u32];
+    for record in records {
+        font.extend(record);
+        glyph_offsets.push(glyph_offsets.last().unwrap() + record.len() as u32);
+    }
+
+    let mut buffer = ByteBuffer::new(font);
+    let table_records = read_table_directory(&mut buffer);
+    let mut parser = FontParser::new(buffer, table_records);
+    let hmtx_table = HmtxTable {
+        advance_widths: vec![UNITS_PER_EM; records.len()],
+        left_side_bearings: vec![0; records.len()],
+    };
+    (0..records.len() as u16)
+        .map(|glyph_index| parser.read_glyph(&glyph_offsets, glyph_index, &hmtx_table).expect("glyph should parse"))
+        .collect()
+}
+
+// Coverage of the pixel whose bottom-left corner is at (x, y) pixels from the glyph origin, y up
+fn coverage_at(bitmap: &GlyphBitmap, x: i32, y: i32) -> u8 {
+    let column = (x - bitmap.left) as usize;
+    let row = (bitmap.top - 1 - y) as usize;
+    bitmap.coverage[row * bitmap.width + column]
+}
+
+// Both test glyphs are two 60px squares overlapping in the 30..60px range on both axes
+fn assert_overlap_coverage(glyph: &Glyph) {
+    let non_zero = rasterize_glyph(glyph, UNITS_PER_EM, PIXEL_SIZE, (0.0, 0.0), FillRule::NonZero);
+    assert_eq!(coverage_at(&non_zero, 10, 10), 255);
+    assert_eq!(coverage_at(&non_zero, 45, 45), 255);
+    assert_eq!(coverage_at(&non_zero, 80, 80), 255);
+    assert_eq!(coverage_at(&non_zero, 80, 10), 0);
+
+    let even_odd = rasterize_glyph(glyph, UNITS_PER_EM, PIXEL_SIZE, (0.0, 0.0), FillRule::EvenOdd);
+    assert_eq!(coverage_at(&even_odd, 10, 10), 255);
+    assert_eq!(coverage_at(&even_odd, 45, 45), 0);
+    assert_eq!(coverage_at(&even_odd, 80, 80), 255);
+    assert_eq!(coverage_at(&even_odd, 80, 10), 0);
+
+    // Edges at pixel boundaries stay identical between the two rules
+    assert_eq!(coverage_at(&non_zero, 29, 10), coverage_at(&even_odd, 29, 10));
+}
+
+#[test]
+fn overlapping_simple_contours_depend_on_fill_rule() {
+    let glyphs = read_glyphs(&[simple_glyph_record(&[square(0, 0, 600), square(300, 300, 600)], true)]);
+    assert_overlap_coverage(&glyphs[0]);
+}
+
+#[test]
+fn overlap_compound_components_depend_on_fill_rule() {
+    let glyphs = read_glyphs(&[
+        simple_glyph_record(&[square(0, 0, 600)], false),
+        composite_glyph_record(0, &[(0, 0), (300, 300)]),
+    ]);
+    assert_eq!(glyphs[1].raw_end_pts_of_contours, vec![3, 7]);
+    assert_overlap_coverage(&glyphs[1]);
+}
+
+#[test]
+fn counters_stay_empty_under_both_fill_rules() {
+    // Outer contour clockwise, inner contour counter-clockwise, like the counter of an 'O'
+    let mut inner = square(200, 200, 200);
+    inner.reverse();
+    let glyphs = read_glyphs(&[simple_glyph_record(&[square(0, 0, 600), inner], false)]);
+
+    for fill_rule in [FillRule::NonZero, FillRule::EvenOdd] {
+        let bitmap = rasterize_glyph(&glyphs[0], UNITS_PER_EM, PIXEL_SIZE, (0.0, 0.0), fill_rule);
+        assert_eq!(coverage_at(&bitmap, 10, 10), 255);
+        assert_eq!(coverage_at(&bitmap, 30, 30), 0);
+    }
+}
+
+#[test]
+fn partial_pixels_get_fractional_coverage() {
+    let glyphs = read_glyphs(&[simple_glyph_record(&[square(0, 0, 600)], false)]);
+    let bitmap = rasterize_glyph(&glyphs[0], UNITS_PER_EM, PIXEL_SIZE, (0.5, 0.0), FillRule::NonZero);
+    assert_eq!(bitmap.left, 0);
+    assert_eq!(coverage_at(&bitmap, 0, 10), 128);
+    assert_eq!(coverage_at(&bitmap, 1, 10), 255);
+}